@@ -1,7 +1,11 @@
 pub use crate::device_config::DeviceConfig;
+pub use crate::sample::Sample;
 pub use realtime_tools::interleaved_audio::{InterleavedAudio, InterleavedAudioMut};
 
-pub trait AudioProcessor {
+/// Processes audio in the sample type `T`, which is `f32` if not specified.
+/// Implement `AudioProcessor<f64>` to process in double precision, the engine then converts the
+/// buffers of the backend in buffers allocated before the stream starts.
+pub trait AudioProcessor<T: Sample = f32> {
     type Message;
 
     /// prepare is called just before the audio engine is started, so that anything can be handled
@@ -14,7 +18,23 @@ pub trait AudioProcessor {
     /// to the processor.
     fn message_process(&mut self, message: Self::Message);
 
+    /// returns a key for messages that overwrite each other, like two `Gain` messages.
+    /// if the message queue is full and `OverflowPolicy::Coalesce` is selected, only the latest
+    /// pending message per key is kept. messages without a key are never merged.
+    fn coalesce_key(_message: &Self::Message) -> Option<u64>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// the latency in frames the processor adds to the audio, e.g. by a lookahead.
+    /// the `AudioGraph` reads it after `prepare` to delay parallel paths by the same amount.
+    fn latency(&self) -> usize {
+        0
+    }
+
     /// here you can manipulate the audio streams, copy incoming to outgoing data.
     /// do not do anything that blocks the audio stream.
-    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>);
+    fn process(&mut self, output: InterleavedAudioMut<'_, T>, input: InterleavedAudio<'_, T>);
 }
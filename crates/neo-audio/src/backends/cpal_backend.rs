@@ -6,7 +6,10 @@ use realtime_tools::interleaved_audio::{InterleavedAudio, InterleavedAudioMut};
 use ringbuf::{traits::*, HeapRb};
 
 use crate::{
-    audio_backend_error::AudioBackendError, backends::COMMON_SAMPLE_RATES, device_name::Device,
+    audio_backend_error::AudioBackendError,
+    backends::COMMON_SAMPLE_RATES,
+    device_name::Device,
+    sample::{self, Converter},
     AudioBackend, DEFAULT_NUM_FRAMES, DEFAULT_SAMPLE_RATE,
 };
 
@@ -24,12 +27,48 @@ pub struct CpalBackend {
     selected_num_input_channels: u16,
     selected_sample_rate: u32,
     selected_num_frames: u32,
+    selected_sample_format: sample::SampleFormat,
     output_stream: Option<Stream>,
     input_stream: Option<Stream>,
 }
 
+/// the cpal formats the engine can convert, in the order they are preferred.
+const SAMPLE_FORMATS: &[(SampleFormat, sample::SampleFormat)] = &[
+    (SampleFormat::F32, sample::SampleFormat::F32),
+    (SampleFormat::I32, sample::SampleFormat::I32),
+    (SampleFormat::I16, sample::SampleFormat::I16),
+];
+
+fn to_cpal_format(sample_format: sample::SampleFormat) -> Option<SampleFormat> {
+    SAMPLE_FORMATS
+        .iter()
+        .find(|(_, format)| *format == sample_format)
+        .map(|(cpal_format, _)| *cpal_format)
+}
+
+/// picks the config with the selected format, or the most preferred one the device supports.
+/// devices without native `f32` support are opened in an integer format and converted.
+fn best_config(
+    configs: impl Iterator<Item = SupportedStreamConfigRange>,
+    sample_format: sample::SampleFormat,
+) -> Option<SupportedStreamConfigRange> {
+    let rank = |config: &SupportedStreamConfigRange| {
+        if Some(config.sample_format()) == to_cpal_format(sample_format) {
+            return Some(0);
+        }
+        SAMPLE_FORMATS
+            .iter()
+            .position(|(format, _)| *format == config.sample_format())
+            .map(|position| position + 1)
+    };
+    configs
+        .filter_map(|config| rank(&config).map(|rank| (rank, config)))
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, config)| config)
+}
+
 impl CpalBackend {
-    fn output_config_f32(&self) -> Option<SupportedStreamConfigRange> {
+    fn output_config(&self) -> Option<SupportedStreamConfigRange> {
         if let Some(device) = self.selected_output_device.as_ref() {
             let formats = match device.supported_output_configs() {
                 Ok(formats) => formats,
@@ -38,15 +77,13 @@ impl CpalBackend {
                     return None;
                 }
             };
-            formats
-                .filter(|f| f.sample_format() == SampleFormat::F32)
-                .last()
+            best_config(formats, self.selected_sample_format)
         } else {
             None
         }
     }
 
-    fn input_config_f32(&self) -> Option<SupportedStreamConfigRange> {
+    fn input_config(&self) -> Option<SupportedStreamConfigRange> {
         if let Some(device) = self.selected_input_device.as_ref() {
             let formats = match device.supported_input_configs() {
                 Ok(formats) => formats,
@@ -55,9 +92,7 @@ impl CpalBackend {
                     return None;
                 }
             };
-            formats
-                .filter(|f| f.sample_format() == SampleFormat::F32)
-                .last()
+            best_config(formats, self.selected_sample_format)
         } else {
             None
         }
@@ -83,6 +118,7 @@ impl AudioBackend for CpalBackend {
             selected_num_input_channels: 0,
             selected_sample_rate: DEFAULT_SAMPLE_RATE,
             selected_num_frames: DEFAULT_NUM_FRAMES,
+            selected_sample_format: sample::SampleFormat::F32,
             output_stream: None,
             input_stream: None,
         };
@@ -231,7 +267,7 @@ impl AudioBackend for CpalBackend {
     }
 
     fn available_num_output_channels(&self) -> u16 {
-        self.output_config_f32()
+        self.output_config()
             .map(|c| c.channels() as u16)
             .unwrap_or(0)
     }
@@ -249,7 +285,7 @@ impl AudioBackend for CpalBackend {
     }
 
     fn available_num_input_channels(&self) -> u16 {
-        self.input_config_f32()
+        self.input_config()
             .map(|c| c.channels() as u32)
             .unwrap_or(0)
     }
@@ -267,7 +303,7 @@ impl AudioBackend for CpalBackend {
     }
 
     fn available_sample_rates(&self) -> Vec<u32> {
-        let (min, max) = match (self.input_config_f32(), self.output_config_f32()) {
+        let (min, max) = match (self.input_config(), self.output_config()) {
             (Some(inp), Some(out)) => (
                 inp.min_sample_rate().0.max(out.max_sample_rate().0),
                 inp.max_sample_rate().0.min(out.max_sample_rate().0),
@@ -322,12 +358,83 @@ impl AudioBackend for CpalBackend {
         self.selected_num_frames
     }
 
+    fn available_sample_formats(&self) -> Vec<sample::SampleFormat> {
+        let Some(device) = self.selected_output_device.as_ref() else {
+            return vec![sample::SampleFormat::F32];
+        };
+        let configs = device
+            .supported_output_configs()
+            .map(|configs| configs.collect::<Vec<_>>())
+            .unwrap_or_default();
+        SAMPLE_FORMATS
+            .iter()
+            .filter(|(format, _)| configs.iter().any(|c| c.sample_format() == *format))
+            .map(|(_, format)| *format)
+            .collect()
+    }
+
+    fn set_sample_format(
+        &mut self,
+        sample_format: sample::SampleFormat,
+    ) -> Result<(), crate::audio_backend_error::AudioBackendError> {
+        if self.available_sample_formats().contains(&sample_format) {
+            self.selected_sample_format = sample_format;
+            Ok(())
+        } else {
+            Err(AudioBackendError::SampleFormat)
+        }
+    }
+
+    fn sample_format(&self) -> sample::SampleFormat {
+        self.selected_sample_format
+    }
+
     fn start_stream(
         &mut self,
-        mut process_fn: impl FnMut(
-                realtime_tools::interleaved_audio::InterleavedAudioMut<'_, f32>,
-                realtime_tools::interleaved_audio::InterleavedAudio<'_, f32>,
-            ) + Send
+        process_fn: impl FnMut(InterleavedAudioMut<'_, f32>, InterleavedAudio<'_, f32>) + Send + 'static,
+    ) -> Result<(), crate::audio_backend_error::AudioBackendError> {
+        // the input device can use another format than the output device
+        match self.input_config().map(|config| config.sample_format()) {
+            Some(SampleFormat::I32) => self.start_with_input::<i32>(process_fn),
+            Some(SampleFormat::I16) => self.start_with_input::<i16>(process_fn),
+            _ => self.start_with_input::<f32>(process_fn),
+        }
+    }
+
+    fn stop_stream(&mut self) -> Result<(), crate::audio_backend_error::AudioBackendError> {
+        self.output_stream.as_mut().map(|s| s.pause());
+        self.input_stream.as_mut().map(|s| s.pause());
+        self.output_stream = None;
+        self.input_stream = None;
+        Ok(())
+    }
+
+    fn stream_error(&self) -> Result<(), crate::audio_backend_error::AudioBackendError> {
+        Ok(())
+    }
+}
+
+impl CpalBackend {
+    fn start_with_input<I: cpal::SizedSample + sample::Sample>(
+        &mut self,
+        process_fn: impl FnMut(InterleavedAudioMut<'_, f32>, InterleavedAudio<'_, f32>) + Send + 'static,
+    ) -> Result<(), crate::audio_backend_error::AudioBackendError> {
+        match self.output_config().map(|config| config.sample_format()) {
+            Some(SampleFormat::I32) => self.build_streams::<i32, I>(process_fn),
+            Some(SampleFormat::I16) => self.build_streams::<i16, I>(process_fn),
+            _ => self.build_streams::<f32, I>(process_fn),
+        }
+    }
+
+    /// opens the output device with the sample type `S` and the input device with `I`, the
+    /// input is converted to `S` when it is received and both are converted to `f32`
+    fn build_streams<
+        S: cpal::SizedSample + sample::Sample,
+        I: cpal::SizedSample + sample::Sample,
+    >(
+        &mut self,
+        mut process_fn: impl FnMut(InterleavedAudioMut<'_, f32>, InterleavedAudio<'_, f32>)
+            + Send
             + 'static,
     ) -> Result<(), crate::audio_backend_error::AudioBackendError> {
         match (
@@ -340,16 +447,21 @@ impl AudioBackend for CpalBackend {
             (Some(output), Some(input)) => {
                 let latency_samples = self.selected_num_frames as usize * 2;
                 let ring_buffer =
-                    HeapRb::<f32>::new(self.selected_num_frames as usize * 2 + latency_samples * 2);
+                    HeapRb::<S>::new(self.selected_num_frames as usize * 2 + latency_samples * 2);
                 let (mut producer, mut consumer) = ring_buffer.split();
                 for _ in 0..latency_samples {
-                    producer.try_push(0.0).unwrap();
+                    producer.try_push(S::default()).unwrap();
                 }
                 let mut input_buffer = vec![
-                    0.0;
+                    S::default();
                     self.selected_num_frames as usize
                         * self.selected_num_input_channels as usize
                 ];
+                let mut converter = Converter::<f32>::new(
+                    self.selected_num_frames as usize,
+                    self.selected_num_output_channels as usize,
+                    self.selected_num_input_channels as usize,
+                );
 
                 let config = cpal::StreamConfig {
                     channels: self.selected_num_output_channels as u16,
@@ -362,21 +474,28 @@ impl AudioBackend for CpalBackend {
 
                 let output_stream = output.build_output_stream(
                     &config,
-                    move |data: &mut [f32], _info: &cpal::OutputCallbackInfo| {
+                    move |data: &mut [S], _info: &cpal::OutputCallbackInfo| {
                         let _consumed = consumer.pop_slice(&mut input_buffer);
-                        process_fn(
+                        converter.process(
                             InterleavedAudioMut::from_slice(data, num_output_ch as usize),
                             InterleavedAudio::from_slice(&input_buffer, num_input_ch as usize),
+                            &mut process_fn,
                         );
                     },
                     move |err| eprintln!("Error in output stream: {:?}", err),
                     None,
                 )?;
 
+                let input_config = cpal::StreamConfig {
+                    channels: num_input_ch,
+                    ..config.clone()
+                };
                 let input_stream = input.build_input_stream(
-                    &config,
-                    move |data: &[f32], _info: &cpal::InputCallbackInfo| {
-                        producer.push_slice(data);
+                    &input_config,
+                    move |data: &[I], _info: &cpal::InputCallbackInfo| {
+                        for sample in data {
+                            let _ = producer.try_push(S::from_f32(sample.to_f32()));
+                        }
                     },
                     move |err| eprintln!("Error in input stream: {:?}", err),
                     None,
@@ -392,18 +511,6 @@ impl AudioBackend for CpalBackend {
 
         Ok(())
     }
-
-    fn stop_stream(&mut self) -> Result<(), crate::audio_backend_error::AudioBackendError> {
-        self.output_stream.as_mut().map(|s| s.pause());
-        self.input_stream.as_mut().map(|s| s.pause());
-        self.output_stream = None;
-        self.input_stream = None;
-        Ok(())
-    }
-
-    fn stream_error(&self) -> Result<(), crate::audio_backend_error::AudioBackendError> {
-        Ok(())
-    }
 }
 
 impl From<cpal::StreamError> for AudioBackendError {
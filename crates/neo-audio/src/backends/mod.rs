@@ -12,9 +12,15 @@ pub mod rtaudio_backend;
 #[cfg(feature = "webaudio")]
 pub mod webaudio_backend;
 
-use realtime_tools::interleaved_audio::{InterleavedAudio, InterleavedAudioMut};
+use realtime_tools::{
+    channel_layout::ChannelLayout,
+    interleaved_audio::{InterleavedAudio, InterleavedAudioMut},
+};
 
-use crate::prelude::{Device, DeviceConfig, NeoAudioError};
+use crate::{
+    prelude::{Device, DeviceConfig, NeoAudioError},
+    sample::SampleFormat,
+};
 
 pub trait AudioBackend {
     fn default() -> Result<Self, NeoAudioError>
@@ -66,7 +72,54 @@ pub trait AudioBackend {
     fn set_num_frames(&mut self, num_frames: u32) -> Result<(), NeoAudioError>;
     fn num_frames(&self) -> u32;
 
-    /// set config all at once, good for loading application state at the start of the application
+    /// the formats the selected devices can be opened with natively.
+    /// the samples are converted, so the processor still gets `f32` or `f64`.
+    fn available_sample_formats(&self) -> Vec<SampleFormat> {
+        vec![SampleFormat::F32]
+    }
+    /// returns an error if the format is not available
+    fn set_sample_format(&mut self, sample_format: SampleFormat) -> Result<(), NeoAudioError> {
+        if sample_format == SampleFormat::F32 {
+            Ok(())
+        } else {
+            Err(NeoAudioError::SampleFormat)
+        }
+    }
+    fn sample_format(&self) -> SampleFormat {
+        SampleFormat::F32
+    }
+
+    /// the speakers of the output channels, returns an error if the layout has another number
+    /// of channels than the output. backends that can't store it only take the common layout.
+    fn set_output_layout(&mut self, layout: ChannelLayout) -> Result<(), NeoAudioError> {
+        if layout == ChannelLayout::from_num_channels(self.num_output_channels() as usize) {
+            Ok(())
+        } else {
+            Err(NeoAudioError::ChannelLayout)
+        }
+    }
+    /// the common layout for the number of output channels, if no other one was set
+    fn output_layout(&self) -> ChannelLayout {
+        ChannelLayout::from_num_channels(self.num_output_channels() as usize)
+    }
+
+    /// the speakers of the input channels, returns an error if the layout has another number
+    /// of channels than the input. backends that can't store it only take the common layout.
+    fn set_input_layout(&mut self, layout: ChannelLayout) -> Result<(), NeoAudioError> {
+        if layout == ChannelLayout::from_num_channels(self.num_input_channels() as usize) {
+            Ok(())
+        } else {
+            Err(NeoAudioError::ChannelLayout)
+        }
+    }
+    /// the common layout for the number of input channels, if no other one was set
+    fn input_layout(&self) -> ChannelLayout {
+        ChannelLayout::from_num_channels(self.num_input_channels() as usize)
+    }
+
+    /// set config all at once, good for loading application state at the start of the application.
+    /// the layouts are only set if the backend kept the number of channels of the config, else
+    /// the common layouts for the available channels are used.
     fn set_config(&mut self, config: &DeviceConfig) -> Result<DeviceConfig, NeoAudioError> {
         if config.api != self.api() {
             self.set_api(&config.api)?;
@@ -78,8 +131,15 @@ pub trait AudioBackend {
             self.set_input_device(config.input_device.clone())?;
             self.set_num_output_channels(config.num_output_ch)?;
             self.set_num_input_channels(config.num_input_ch)?;
+            if self.num_output_channels() == config.num_output_ch {
+                self.set_output_layout(config.output_layout.clone())?;
+            }
+            if self.num_input_channels() == config.num_input_ch {
+                self.set_input_layout(config.input_layout.clone())?;
+            }
             self.set_sample_rate(config.sample_rate)?;
             self.set_num_frames(config.num_frames)?;
+            self.set_sample_format(config.sample_format)?;
         }
         Ok(self.config())
     }
@@ -92,8 +152,11 @@ pub trait AudioBackend {
             input_device: self.input_device().as_ref().into(),
             num_output_ch: self.num_output_channels(),
             num_input_ch: self.num_input_channels(),
+            output_layout: self.output_layout(),
+            input_layout: self.input_layout(),
             sample_rate: self.sample_rate(),
             num_frames: self.num_frames(),
+            sample_format: self.sample_format(),
         }
     }
 
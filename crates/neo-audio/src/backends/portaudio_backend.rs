@@ -1,13 +1,18 @@
 use portaudio::{
     DeviceIndex, Duplex, DuplexStreamCallbackArgs, DuplexStreamSettings, HostApiIndex, Input,
-    NonBlocking, Output, OutputStreamSettings, PortAudio, Stream, StreamParameters,
+    NonBlocking, Output, OutputStreamSettings, PortAudio, Sample as PaSample, Stream,
+    StreamParameters,
+};
+use realtime_tools::{
+    channel_layout::ChannelLayout,
+    interleaved_audio::{InterleavedAudio, InterleavedAudioMut},
 };
-use realtime_tools::interleaved_audio::{InterleavedAudio, InterleavedAudioMut};
 
 use crate::{
     backends::{AudioBackend, DEFAULT_NUM_FRAMES, DEFAULT_SAMPLE_RATE},
     device_name::Device,
     prelude::NeoAudioError,
+    sample::{Converter, Sample, SampleFormat},
 };
 
 use super::{COMMON_FRAMES_PER_BUFFER, COMMON_SAMPLE_RATES};
@@ -16,10 +21,36 @@ use super::{COMMON_FRAMES_PER_BUFFER, COMMON_SAMPLE_RATES};
 // TODO: Find a solution to check if sample rates and frame sizes are working upfront
 // TODO: Stream Errors?
 
-enum StreamType {
-    Duplex(Stream<NonBlocking, Duplex<f32, f32>>),
-    Output(Stream<NonBlocking, Output<f32>>),
-    Input(Stream<NonBlocking, Input<f32>>),
+/// the formats PortAudio can open natively, packed 24 bit has no sample type in the bindings
+const SAMPLE_FORMATS: &[SampleFormat] = &[SampleFormat::F32, SampleFormat::I32, SampleFormat::I16];
+
+/// the layout that was set, as long as the number of channels didn't change since
+fn stored_layout(layout: &Option<ChannelLayout>, num_channels: u16) -> ChannelLayout {
+    layout
+        .clone()
+        .filter(|layout| layout.num_channels() == num_channels as usize)
+        .unwrap_or_else(|| ChannelLayout::from_num_channels(num_channels as usize))
+}
+
+enum StreamType<S: PaSample> {
+    Duplex(Stream<NonBlocking, Duplex<S, S>>),
+    Output(Stream<NonBlocking, Output<S>>),
+    Input(Stream<NonBlocking, Input<S>>),
+}
+
+/// streams of all sample formats, so they can be stored in one field
+trait RunningStream {
+    fn stop(&mut self) -> Result<(), portaudio::Error>;
+}
+
+impl<S: PaSample + 'static> RunningStream for StreamType<S> {
+    fn stop(&mut self) -> Result<(), portaudio::Error> {
+        match self {
+            StreamType::Duplex(stream) => stream.stop(),
+            StreamType::Output(stream) => stream.stop(),
+            StreamType::Input(stream) => stream.stop(),
+        }
+    }
 }
 
 pub struct PortAudioBackend {
@@ -33,9 +64,12 @@ pub struct PortAudioBackend {
     selected_input_device: Option<DeviceIndex>,
     selected_num_input_channels: i32,
     selected_num_output_channels: i32,
+    selected_input_layout: Option<ChannelLayout>,
+    selected_output_layout: Option<ChannelLayout>,
     selected_sample_rate: f64,
     selected_num_frames: u32,
-    stream: Option<StreamType>,
+    selected_sample_format: SampleFormat,
+    stream: Option<Box<dyn RunningStream>>,
 }
 
 impl AudioBackend for PortAudioBackend {
@@ -54,8 +88,11 @@ impl AudioBackend for PortAudioBackend {
             selected_input_device: None,
             selected_num_input_channels: 0,
             selected_num_output_channels: 0,
+            selected_input_layout: None,
+            selected_output_layout: None,
             selected_sample_rate: DEFAULT_SAMPLE_RATE as f64,
             selected_num_frames: DEFAULT_NUM_FRAMES,
+            selected_sample_format: SampleFormat::F32,
             stream: None,
             pa,
         };
@@ -359,18 +396,88 @@ impl AudioBackend for PortAudioBackend {
         self.selected_num_frames
     }
 
+    fn available_sample_formats(&self) -> Vec<SampleFormat> {
+        SAMPLE_FORMATS.to_vec()
+    }
+
+    fn set_sample_format(&mut self, sample_format: SampleFormat) -> Result<(), NeoAudioError> {
+        if self.stream.is_some() {
+            return Err(NeoAudioError::StreamRunning);
+        }
+        self.selected_sample_format = sample_format;
+        Ok(())
+    }
+
+    fn sample_format(&self) -> SampleFormat {
+        self.selected_sample_format
+    }
+
+    fn set_output_layout(&mut self, layout: ChannelLayout) -> Result<(), NeoAudioError> {
+        if layout.num_channels() != self.num_output_channels() as usize {
+            return Err(NeoAudioError::ChannelLayout);
+        }
+        self.selected_output_layout = Some(layout);
+        Ok(())
+    }
+
+    fn output_layout(&self) -> ChannelLayout {
+        stored_layout(&self.selected_output_layout, self.num_output_channels())
+    }
+
+    fn set_input_layout(&mut self, layout: ChannelLayout) -> Result<(), NeoAudioError> {
+        if layout.num_channels() != self.num_input_channels() as usize {
+            return Err(NeoAudioError::ChannelLayout);
+        }
+        self.selected_input_layout = Some(layout);
+        Ok(())
+    }
+
+    fn input_layout(&self) -> ChannelLayout {
+        stored_layout(&self.selected_input_layout, self.num_input_channels())
+    }
+
     fn start_stream(
         &mut self,
-        mut process_fn: impl FnMut(
-                realtime_tools::interleaved_audio::InterleavedAudioMut<'_, f32>,
-                realtime_tools::interleaved_audio::InterleavedAudio<'_, f32>,
-            ) + Send
+        process_fn: impl FnMut(InterleavedAudioMut<'_, f32>, InterleavedAudio<'_, f32>) + Send + 'static,
+    ) -> Result<(), NeoAudioError> {
+        match self.selected_sample_format {
+            SampleFormat::F32 => self.open_stream::<f32>(process_fn),
+            SampleFormat::I32 => self.open_stream::<i32>(process_fn),
+            SampleFormat::I16 => self.open_stream::<i16>(process_fn),
+        }
+    }
+
+    fn stop_stream(&mut self) -> Result<(), NeoAudioError> {
+        if let Some(stream) = self.stream.as_mut() {
+            stream.stop()?;
+        }
+        self.stream = None;
+        Ok(())
+    }
+
+    fn stream_error(&self) -> Result<(), NeoAudioError> {
+        Ok(())
+    }
+}
+
+impl PortAudioBackend {
+    /// opens the devices with the sample type `S` and converts the buffers to `f32`
+    fn open_stream<S: PaSample + Sample>(
+        &mut self,
+        mut process_fn: impl FnMut(InterleavedAudioMut<'_, f32>, InterleavedAudio<'_, f32>)
+            + Send
             + 'static,
     ) -> Result<(), NeoAudioError> {
+        let mut converter = Converter::<f32>::new(
+            self.selected_num_frames as usize,
+            self.selected_num_output_channels as usize,
+            self.selected_num_input_channels as usize,
+        );
+
         let output_params = if let Some(output_device) = self.selected_output_device {
             let info = self.pa.device_info(output_device)?;
             let latency = info.default_low_output_latency;
-            Some(StreamParameters::<f32>::new(
+            Some(StreamParameters::<S>::new(
                 output_device,
                 self.selected_num_output_channels,
                 true,
@@ -387,7 +494,7 @@ impl AudioBackend for PortAudioBackend {
                 let info = self.pa.device_info(input_device)?;
                 info.default_low_output_latency
             };
-            Some(StreamParameters::<f32>::new(
+            Some(StreamParameters::<S>::new(
                 input_device,
                 self.selected_num_input_channels,
                 true,
@@ -405,7 +512,7 @@ impl AudioBackend for PortAudioBackend {
                     self.selected_sample_rate,
                     self.selected_num_frames,
                 );
-                let callback = move |DuplexStreamCallbackArgs::<f32, f32> {
+                let callback = move |DuplexStreamCallbackArgs::<S, S> {
                                          in_buffer,
                                          out_buffer,
                                          frames,
@@ -413,15 +520,16 @@ impl AudioBackend for PortAudioBackend {
                                      }| {
                     let num_output_channels = out_buffer.len() / frames;
                     let num_input_channels = in_buffer.len() / frames;
-                    process_fn(
+                    converter.process(
                         InterleavedAudioMut::from_slice(out_buffer, num_output_channels),
                         InterleavedAudio::from_slice(in_buffer, num_input_channels),
+                        &mut process_fn,
                     );
                     portaudio::Continue
                 };
                 let mut stream = self.pa.open_non_blocking_stream(settings, callback)?;
                 stream.start()?;
-                self.stream = Some(StreamType::Duplex(stream));
+                self.stream = Some(Box::new(StreamType::Duplex(stream)));
             }
             (Some(output_params), None) => {
                 let settings = OutputStreamSettings::new(
@@ -430,17 +538,18 @@ impl AudioBackend for PortAudioBackend {
                     self.selected_num_frames,
                 );
                 let callback =
-                    move |portaudio::OutputStreamCallbackArgs::<f32> { buffer, frames, .. }| {
+                    move |portaudio::OutputStreamCallbackArgs::<S> { buffer, frames, .. }| {
                         let num_channels = buffer.len() / frames;
-                        process_fn(
+                        converter.process(
                             InterleavedAudioMut::from_slice(buffer, num_channels),
                             InterleavedAudio::from_slice(&[], 0),
+                            &mut process_fn,
                         );
                         portaudio::Continue
                     };
                 let mut stream = self.pa.open_non_blocking_stream(settings, callback)?;
                 stream.start()?;
-                self.stream = Some(StreamType::Output(stream));
+                self.stream = Some(Box::new(StreamType::Output(stream)));
             }
             (None, Some(input_params)) => {
                 let settings = portaudio::InputStreamSettings::new(
@@ -449,17 +558,18 @@ impl AudioBackend for PortAudioBackend {
                     self.selected_num_frames,
                 );
                 let callback =
-                    move |portaudio::InputStreamCallbackArgs::<f32> { buffer, frames, .. }| {
+                    move |portaudio::InputStreamCallbackArgs::<S> { buffer, frames, .. }| {
                         let num_channels = buffer.len() / frames;
-                        process_fn(
+                        converter.process(
                             InterleavedAudioMut::from_slice(&mut [], 0),
                             InterleavedAudio::from_slice(buffer, num_channels),
+                            &mut process_fn,
                         );
                         portaudio::Continue
                     };
                 let mut stream = self.pa.open_non_blocking_stream(settings, callback)?;
                 stream.start()?;
-                self.stream = Some(StreamType::Input(stream));
+                self.stream = Some(Box::new(StreamType::Input(stream)));
             }
             _ => {
                 self.stream = None;
@@ -467,22 +577,6 @@ impl AudioBackend for PortAudioBackend {
         }
         Ok(())
     }
-
-    fn stop_stream(&mut self) -> Result<(), NeoAudioError> {
-        if let Some(stream) = self.stream.as_mut() {
-            match stream {
-                StreamType::Duplex(stream) => stream.stop()?,
-                StreamType::Output(stream) => stream.stop()?,
-                StreamType::Input(stream) => stream.stop()?,
-            }
-        }
-        self.stream = None;
-        Ok(())
-    }
-
-    fn stream_error(&self) -> Result<(), NeoAudioError> {
-        Ok(())
-    }
 }
 
 impl From<portaudio::Error> for NeoAudioError {
@@ -0,0 +1,323 @@
+//! Loads WAV, FLAC, MP3 and Ogg Vorbis files into the layout of the `PlayerProcessor`, or
+//! decodes them while they are played by the `StreamingPlayerProcessor`.
+
+use std::{fs::File, io::ErrorKind, path::Path};
+
+use ndarray::Array2;
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL},
+    errors::{Error, SeekErrorKind},
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+    io::{MediaSource, MediaSourceStream},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+use crate::{error::NeoAudioError, processors::streaming_player::StreamSource};
+
+/// The decoded samples of a file, one row per channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedAudio {
+    pub audio: Array2<f32>,
+    pub sample_rate: u32,
+}
+
+impl DecodedAudio {
+    pub fn num_channels(&self) -> usize {
+        self.audio.nrows()
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.audio.ncols()
+    }
+
+    /// the duration in seconds
+    pub fn duration(&self) -> f64 {
+        self.num_frames() as f64 / self.sample_rate.max(1) as f64
+    }
+}
+
+/// decodes the whole file, the format is detected from the content and the file extension
+pub fn decode_file(path: impl AsRef<Path>) -> Result<DecodedAudio, NeoAudioError> {
+    decode_stream(FileStream::open(path)?)
+}
+
+/// decodes a whole stream, e.g. a `std::io::Cursor` of a file that is embedded in the binary.
+/// the extension is an optional hint for the format.
+pub fn decode(
+    source: Box<dyn MediaSource>,
+    extension: Option<&str>,
+) -> Result<DecodedAudio, NeoAudioError> {
+    decode_stream(FileStream::new(source, extension)?)
+}
+
+fn decode_stream(mut stream: FileStream) -> Result<DecodedAudio, NeoAudioError> {
+    let num_channels = stream.num_channels();
+    let mut channels = vec![Vec::new(); num_channels];
+    let mut buffer = vec![0.0; 4096 * num_channels];
+    loop {
+        let num_frames = stream.read(&mut buffer)?;
+        if num_frames == 0 {
+            break;
+        }
+        for frame in buffer[..num_frames * num_channels].chunks_exact(num_channels) {
+            for (channel, sample) in channels.iter_mut().zip(frame) {
+                channel.push(*sample);
+            }
+        }
+    }
+
+    let num_frames = channels.first().map_or(0, |c| c.len());
+    let audio = Array2::from_shape_vec((num_channels, num_frames), channels.concat())
+        .map_err(|e| NeoAudioError::Decode(e.to_string()))?;
+    Ok(DecodedAudio {
+        audio,
+        sample_rate: stream.sample_rate(),
+    })
+}
+
+/// Decodes a file while it is read, to play it with the `StreamingPlayerProcessor`.
+pub struct FileStream {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    num_channels: usize,
+    sample_rate: u32,
+    samples: Option<SampleBuffer<f32>>,
+    // decoded interleaved samples, that were not read yet
+    pending: Vec<f32>,
+    read_position: usize,
+    // the frame that was seeked to, earlier frames of the next packets are skipped
+    seek_frame: Option<u64>,
+    finished: bool,
+}
+
+impl FileStream {
+    /// the format is detected from the content and the file extension
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, NeoAudioError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| NeoAudioError::Decode(e.to_string()))?;
+        let extension = path.extension().and_then(|e| e.to_str());
+        Self::new(Box::new(file), extension)
+    }
+
+    /// the extension is an optional hint for the format
+    pub fn new(
+        source: Box<dyn MediaSource>,
+        extension: Option<&str>,
+    ) -> Result<Self, NeoAudioError> {
+        let stream = MediaSourceStream::new(source, Default::default());
+        let mut hint = Hint::new();
+        if let Some(extension) = extension {
+            hint.with_extension(extension);
+        }
+        let format = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(decode_error)?
+            .format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| NeoAudioError::Decode("no audio track found".into()))?;
+        let params = &track.codec_params;
+        let num_channels = params
+            .channels
+            .map(|channels| channels.count())
+            .ok_or_else(|| NeoAudioError::Decode("unknown number of channels".into()))?;
+        let sample_rate = params
+            .sample_rate
+            .ok_or_else(|| NeoAudioError::Decode("unknown sample rate".into()))?;
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .map_err(decode_error)?;
+
+        Ok(Self {
+            track_id: track.id,
+            format,
+            decoder,
+            num_channels,
+            sample_rate,
+            samples: None,
+            pending: Vec::new(),
+            read_position: 0,
+            seek_frame: None,
+            finished: false,
+        })
+    }
+
+    /// decodes the next packet into the pending samples, returns false at the end of the file
+    fn decode_packet(&mut self) -> Result<bool, NeoAudioError> {
+        let packet = match self.format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(decode_error(e)),
+        };
+        self.pending.clear();
+        self.read_position = 0;
+        if packet.track_id() != self.track_id {
+            return Ok(true);
+        }
+
+        let decoded = match self.decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // a corrupt packet is skipped, like players do
+            Err(Error::DecodeError(_)) => return Ok(true),
+            Err(e) => return Err(decode_error(e)),
+        };
+        let spec = *decoded.spec();
+        if spec.channels.count() != self.num_channels {
+            return Err(NeoAudioError::Decode(
+                "the number of channels changed".into(),
+            ));
+        }
+        let samples = match &mut self.samples {
+            Some(samples) if samples.capacity() >= decoded.capacity() * self.num_channels => {
+                samples
+            }
+            _ => self
+                .samples
+                .insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        samples.copy_interleaved_ref(decoded);
+        self.pending.extend_from_slice(samples.samples());
+
+        // seeking lands on a packet before the frame
+        if let Some(seek_frame) = self.seek_frame.take() {
+            let skip = seek_frame.saturating_sub(packet.ts()) as usize * self.num_channels;
+            if skip >= self.pending.len() {
+                self.seek_frame = Some(seek_frame);
+            }
+            self.read_position = skip.min(self.pending.len());
+        }
+        Ok(true)
+    }
+}
+
+impl StreamSource for FileStream {
+    fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize, NeoAudioError> {
+        let mut written = 0;
+        while written < buffer.len() && !self.finished {
+            if self.read_position == self.pending.len() {
+                self.finished = !self.decode_packet()?;
+                continue;
+            }
+            let len = (buffer.len() - written).min(self.pending.len() - self.read_position);
+            buffer[written..written + len]
+                .copy_from_slice(&self.pending[self.read_position..self.read_position + len]);
+            written += len;
+            self.read_position += len;
+        }
+        Ok(written / self.num_channels)
+    }
+
+    fn seek(&mut self, frame: u64) -> Result<(), NeoAudioError> {
+        self.pending.clear();
+        self.read_position = 0;
+        self.decoder.reset();
+        let to = SeekTo::TimeStamp {
+            ts: frame,
+            track_id: self.track_id,
+        };
+        match self.format.seek(SeekMode::Accurate, to) {
+            Ok(_) => {
+                self.seek_frame = Some(frame);
+                self.finished = false;
+                Ok(())
+            }
+            // seeking behind the end finishes the stream
+            Err(Error::SeekError(SeekErrorKind::OutOfRange)) => {
+                self.finished = true;
+                Ok(())
+            }
+            Err(e) => Err(decode_error(e)),
+        }
+    }
+}
+
+fn decode_error(error: Error) -> NeoAudioError {
+    NeoAudioError::Decode(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// a 16 bit PCM WAV file
+    fn wav(samples: &[i16], num_channels: u16, sample_rate: u32) -> Vec<u8> {
+        let data_len = samples.len() as u32 * 2;
+        let mut file = Vec::new();
+        file.extend_from_slice(b"RIFF");
+        file.extend_from_slice(&(36 + data_len).to_le_bytes());
+        file.extend_from_slice(b"WAVEfmt ");
+        file.extend_from_slice(&16u32.to_le_bytes());
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&num_channels.to_le_bytes());
+        file.extend_from_slice(&sample_rate.to_le_bytes());
+        file.extend_from_slice(&(sample_rate * num_channels as u32 * 2).to_le_bytes());
+        file.extend_from_slice(&(num_channels * 2).to_le_bytes());
+        file.extend_from_slice(&16u16.to_le_bytes());
+        file.extend_from_slice(b"data");
+        file.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            file.extend_from_slice(&sample.to_le_bytes());
+        }
+        file
+    }
+
+    #[test]
+    fn decode_wav() {
+        let file = wav(&[16384, -16384, 0, 8192, -32768, 0], 2, 44100);
+        let decoded = decode(Box::new(Cursor::new(file)), Some("wav")).unwrap();
+        assert_eq!(decoded.sample_rate, 44100);
+        assert_eq!(decoded.num_channels(), 2);
+        assert_eq!(decoded.num_frames(), 3);
+        assert_eq!(
+            decoded.audio,
+            ndarray::arr2(&[[0.5, 0.0, -1.0], [-0.5, 0.25, 0.0]])
+        );
+    }
+
+    #[test]
+    fn stream_wav() {
+        let samples = (0..1000).map(|n| n as i16 * 16).collect::<Vec<_>>();
+        let file = wav(&samples, 1, 48000);
+        let mut stream = FileStream::new(Box::new(Cursor::new(file)), Some("wav")).unwrap();
+        assert_eq!(stream.num_channels(), 1);
+
+        let mut buffer = [0.0; 4];
+        assert_eq!(stream.read(&mut buffer).unwrap(), 4);
+        assert_eq!(buffer[3], 48.0 / 32768.0);
+
+        stream.seek(998).unwrap();
+        assert_eq!(stream.read(&mut buffer).unwrap(), 2);
+        assert_eq!(
+            buffer[..2],
+            [998.0 * 16.0 / 32768.0, 999.0 * 16.0 / 32768.0]
+        );
+        assert_eq!(stream.read(&mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn invalid_file() {
+        let result = decode(Box::new(Cursor::new(vec![0u8; 64])), None);
+        assert!(matches!(result, Err(NeoAudioError::Decode(_))));
+    }
+}
@@ -1,4 +1,6 @@
-use super::device_name::Device;
+use realtime_tools::channel_layout::ChannelLayout;
+
+use super::{device_name::Device, sample::SampleFormat};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -8,6 +10,37 @@ pub struct DeviceConfig {
     pub input_device: Device,
     pub num_output_ch: u16,
     pub num_input_ch: u16,
+    /// the speakers of the output channels, backends derive it from the number of channels
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub output_layout: ChannelLayout,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub input_layout: ChannelLayout,
     pub sample_rate: u32,
     pub num_frames: u32,
+    /// the format the devices are opened with, processors always get `f32` or `f64`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sample_format: SampleFormat,
+}
+
+impl DeviceConfig {
+    /// a config for the default devices, with the layouts derived from the numbers of channels,
+    /// e.g. to prepare processors for offline rendering and in tests
+    pub fn new(sample_rate: u32, num_frames: u32, num_output_ch: u16, num_input_ch: u16) -> Self {
+        let device = |num_ch| match num_ch {
+            0 => Device::None,
+            _ => Device::Default,
+        };
+        Self {
+            api: String::new(),
+            output_device: device(num_output_ch),
+            input_device: device(num_input_ch),
+            num_output_ch,
+            num_input_ch,
+            output_layout: ChannelLayout::from_num_channels(num_output_ch as usize),
+            input_layout: ChannelLayout::from_num_channels(num_input_ch as usize),
+            sample_rate,
+            num_frames,
+            sample_format: SampleFormat::F32,
+        }
+    }
 }
@@ -0,0 +1,666 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, OnceLock},
+};
+
+use realtime_tools::interleaved_audio::{InterleavedAudio, InterleavedAudioMut};
+
+use crate::{
+    audio_processor::AudioProcessor,
+    device_config::DeviceConfig,
+    message_queue::{MessageReceiver, Timed},
+    sample::{Converter, Sample},
+};
+
+/// Decides how many frames the `AudioProcessor` gets in one `process` call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockSize {
+    /// The processor gets whatever number of frames the backend delivers.
+    #[default]
+    Variable,
+    /// Buffers of the backend are split, so the processor never gets more than this number of
+    /// frames. This does not add any latency.
+    Max(usize),
+    /// Input and output are buffered, so the processor always gets exactly this number of frames.
+    /// This adds the block size as latency. Timestamped messages are delivered at the start of
+    /// the block they fall into, because the blocks can not be split.
+    Fixed(usize),
+}
+
+impl BlockSize {
+    /// the number of frames the processor will get at most, if it is known upfront
+    pub fn max_frames(&self) -> Option<usize> {
+        match self {
+            BlockSize::Variable => None,
+            BlockSize::Max(n) | BlockSize::Fixed(n) => Some(*n),
+        }
+    }
+
+    /// the latency in frames that is added by buffering the audio
+    pub fn latency(&self) -> usize {
+        match self {
+            BlockSize::Variable | BlockSize::Max(_) => 0,
+            BlockSize::Fixed(n) => *n,
+        }
+    }
+}
+
+/// Remembers the panic message of a processor, shared between the audio callback and `NeoAudio`.
+#[derive(Clone, Default)]
+pub(crate) struct StreamFault {
+    message: Arc<OnceLock<String>>,
+}
+
+impl StreamFault {
+    pub fn is_faulted(&self) -> bool {
+        self.message.get().is_some()
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.get().map(|m| m.as_str())
+    }
+
+    fn set(&self, payload: Box<dyn Any + Send>) {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic payload".to_string()
+        };
+        let _ = self.message.set(message);
+    }
+}
+
+/// Runs an `AudioProcessor` in the audio callback. It receives the messages, keeps the ones that
+/// are scheduled for later and splits the processing at the frames they should be delivered at.
+/// Processors with another sample type than `f32` get converted buffers.
+pub(crate) struct Engine<P, T = f32>
+where
+    P: AudioProcessor<T>,
+    T: Sample,
+{
+    processor: P,
+    queue: MessageReceiver<P::Message>,
+    // sorted from the latest to the earliest frame, so due messages can be popped from the back
+    scheduled: Vec<(u64, P::Message)>,
+    frame: u64,
+    block_size: BlockSize,
+    fixed_block: FixedBlock,
+    converter: Converter<T>,
+    // offline the processor runs outside of the audio thread and can allocate
+    #[cfg(feature = "rt-check")]
+    rt_check: bool,
+}
+
+impl<P, T> Engine<P, T>
+where
+    P: AudioProcessor<T>,
+    T: Sample,
+{
+    /// has to be called outside of the audio thread, because all buffers get allocated here.
+    /// `max_frames` is the number of frames the conversion buffers are allocated for.
+    pub fn new(
+        processor: P,
+        queue: MessageReceiver<P::Message>,
+        block_size: BlockSize,
+        num_output_ch: usize,
+        num_input_ch: usize,
+        max_frames: usize,
+    ) -> Self {
+        let capacity = queue.receiver.capacity().unwrap_or(0);
+        queue.clock.set_frame(0);
+        let fixed_block = match block_size {
+            BlockSize::Fixed(num_frames) => {
+                FixedBlock::new(num_frames.max(1), num_output_ch, num_input_ch)
+            }
+            _ => FixedBlock::new(0, 0, 0),
+        };
+        Self {
+            processor,
+            queue,
+            scheduled: Vec::with_capacity(capacity),
+            frame: 0,
+            block_size,
+            fixed_block,
+            converter: Converter::new(max_frames, num_output_ch, num_input_ch),
+            #[cfg(feature = "rt-check")]
+            rt_check: true,
+        }
+    }
+
+    /// flags the processing for the `rt-check` feature, on by default
+    #[cfg(feature = "rt-check")]
+    pub fn set_rt_check(&mut self, rt_check: bool) {
+        self.rt_check = rt_check;
+    }
+
+    /// prepares the processor with the config, like it is done before a stream is started.
+    /// the number of frames in the config is limited by the block size.
+    pub fn prepared(
+        mut processor: P,
+        mut config: DeviceConfig,
+        queue: MessageReceiver<P::Message>,
+        block_size: BlockSize,
+    ) -> Self {
+        if let Some(max_frames) = block_size.max_frames() {
+            config.num_frames = max_frames as u32;
+        }
+        let num_output_ch = config.num_output_ch as usize;
+        let num_input_ch = config.num_input_ch as usize;
+        let max_frames = config.num_frames as usize;
+        processor.prepare(config);
+        Self::new(
+            processor,
+            queue,
+            block_size,
+            num_output_ch,
+            num_input_ch,
+            max_frames,
+        )
+    }
+
+    /// Processes like `process`, but catches a panic of the processor, so it does not unwind
+    /// into the audio backend. After a panic the processor is not called anymore, the output
+    /// stays silent and the panic message is stored in the fault.
+    pub fn process_catching(
+        &mut self,
+        mut output: InterleavedAudioMut<'_, f32>,
+        input: InterleavedAudio<'_, f32>,
+        fault: &StreamFault,
+    ) {
+        let num_output_ch = output.num_channels();
+        let output = output.data_mut();
+        if fault.is_faulted() {
+            output.fill(0.0);
+            return;
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.process(
+                InterleavedAudioMut::from_slice(&mut *output, num_output_ch),
+                input,
+            )
+        }));
+        if let Err(payload) = result {
+            output.fill(0.0);
+            fault.set(payload);
+        }
+    }
+
+    pub fn process(
+        &mut self,
+        output: InterleavedAudioMut<'_, f32>,
+        input: InterleavedAudio<'_, f32>,
+    ) {
+        match self.block_size {
+            BlockSize::Variable => self.process_block(output, input),
+            BlockSize::Max(max_frames) => self.process_max(output, input, max_frames.max(1)),
+            BlockSize::Fixed(_) => self.process_fixed(output, input),
+        }
+    }
+
+    fn process_max(
+        &mut self,
+        mut output: InterleavedAudioMut<'_, f32>,
+        input: InterleavedAudio<'_, f32>,
+        max_frames: usize,
+    ) {
+        let num_frames = num_frames(&output, &input);
+        let mut start = 0;
+        while start < num_frames {
+            let end = (start + max_frames).min(num_frames);
+            self.process_block(
+                output.slice_frames_mut(start..end),
+                input.slice_frames(start..end),
+            );
+            start = end;
+        }
+    }
+
+    fn process_fixed(
+        &mut self,
+        mut output: InterleavedAudioMut<'_, f32>,
+        input: InterleavedAudio<'_, f32>,
+    ) {
+        let num_frames = num_frames(&output, &input);
+        let mut start = 0;
+        while start < num_frames {
+            let block = &mut self.fixed_block;
+            let num = (block.num_frames - block.fill).min(num_frames - start);
+            let block_range = block.fill..block.fill + num;
+
+            // the output of the last block is played while the input of the next is collected
+            copy_frames(
+                &mut output.slice_frames_mut(start..start + num),
+                &InterleavedAudio::from_slice(&block.output, block.num_output_ch)
+                    .slice_frames(block_range.clone()),
+            );
+            copy_frames(
+                &mut InterleavedAudioMut::from_slice(&mut block.input, block.num_input_ch)
+                    .slice_frames_mut(block_range),
+                &input.slice_frames(start..start + num),
+            );
+            block.fill += num;
+            start += num;
+
+            if block.fill == block.num_frames {
+                block.fill = 0;
+                let mut block_output = std::mem::take(&mut block.output);
+                let block_input = std::mem::take(&mut block.input);
+                self.process_block(
+                    InterleavedAudioMut::from_slice(
+                        &mut block_output,
+                        self.fixed_block.num_output_ch,
+                    ),
+                    InterleavedAudio::from_slice(&block_input, self.fixed_block.num_input_ch),
+                );
+                self.fixed_block.output = block_output;
+                self.fixed_block.input = block_input;
+            }
+        }
+    }
+
+    fn process_block(
+        &mut self,
+        mut output: InterleavedAudioMut<'_, f32>,
+        input: InterleavedAudio<'_, f32>,
+    ) {
+        self.receive_messages();
+
+        let num_frames = num_frames(&output, &input);
+
+        if let BlockSize::Fixed(_) = self.block_size {
+            // fixed blocks can not be split
+            self.deliver_due_messages(num_frames.saturating_sub(1));
+            self.processor_process(output, input);
+        } else if self.next_scheduled_offset(num_frames).is_none() {
+            // nothing to split, this is the common case
+            self.processor_process(output, input);
+        } else {
+            let mut start = 0;
+            while start < num_frames {
+                self.deliver_due_messages(start);
+                let end = self.next_scheduled_offset(num_frames).unwrap_or(num_frames);
+                self.processor_process(
+                    output.slice_frames_mut(start..end),
+                    input.slice_frames(start..end),
+                );
+                start = end;
+            }
+        }
+
+        self.frame += num_frames as u64;
+        self.queue.clock.set_frame(self.frame);
+    }
+
+    fn processor_process(
+        &mut self,
+        output: InterleavedAudioMut<'_, f32>,
+        input: InterleavedAudio<'_, f32>,
+    ) {
+        #[cfg(feature = "rt-check")]
+        let _scope = self
+            .rt_check
+            .then(|| crate::rt_check::RtScope::enter("process"));
+        let processor = &mut self.processor;
+        self.converter.process(output, input, |output, input| {
+            processor.process(output, input)
+        });
+    }
+
+    fn processor_message_process(&mut self, message: P::Message) {
+        #[cfg(feature = "rt-check")]
+        let _scope = self
+            .rt_check
+            .then(|| crate::rt_check::RtScope::enter("message_process"));
+        self.processor.message_process(message);
+    }
+
+    fn receive_messages(&mut self) {
+        let num_messages = self.queue.receiver.len();
+        self.queue.stats.record_depth(num_messages);
+
+        // receive all messages
+        for _ in 0..num_messages {
+            match self.queue.receiver.try_recv() {
+                Ok(Timed {
+                    frame: Some(frame),
+                    message,
+                }) if frame > self.frame => self.schedule(frame, message),
+                Ok(Timed { message, .. }) => self.processor_message_process(message),
+                _ => break,
+            }
+        }
+    }
+
+    fn schedule(&mut self, frame: u64, message: P::Message) {
+        if self.scheduled.len() == self.scheduled.capacity() {
+            // never allocate in the audio thread, deliver too early instead
+            self.queue.stats.add_delivered_early();
+            self.processor_message_process(message);
+            return;
+        }
+        // messages for the same frame keep the order they were sent in
+        let index = self.scheduled.partition_point(|(f, _)| *f > frame);
+        self.scheduled.insert(index, (frame, message));
+    }
+
+    /// delivers all scheduled messages up to the given offset in the current block
+    fn deliver_due_messages(&mut self, offset: usize) {
+        let frame = self.frame + offset as u64;
+        while self.scheduled.last().is_some_and(|(f, _)| *f <= frame) {
+            if let Some((_, message)) = self.scheduled.pop() {
+                self.processor_message_process(message);
+            }
+        }
+    }
+
+    /// offset of the next scheduled message, if it lies within the current block
+    fn next_scheduled_offset(&self, num_frames: usize) -> Option<usize> {
+        self.scheduled
+            .last()
+            .map(|(frame, _)| frame.saturating_sub(self.frame) as usize)
+            .filter(|offset| *offset < num_frames)
+    }
+}
+
+/// buffers to collect the input for a fixed block and to play back its output
+struct FixedBlock {
+    num_frames: usize,
+    num_output_ch: usize,
+    num_input_ch: usize,
+    output: Vec<f32>,
+    input: Vec<f32>,
+    fill: usize,
+}
+
+impl FixedBlock {
+    fn new(num_frames: usize, num_output_ch: usize, num_input_ch: usize) -> Self {
+        Self {
+            num_frames,
+            num_output_ch,
+            num_input_ch,
+            output: vec![0.0; num_frames * num_output_ch],
+            input: vec![0.0; num_frames * num_input_ch],
+            fill: 0,
+        }
+    }
+}
+
+/// Splits the buffers into chunks of at most `max_frames`, for processors with scratch buffers
+/// that are allocated in `prepare`. Backends can deliver more frames than the config says.
+pub(crate) fn process_chunked(
+    mut output: InterleavedAudioMut<'_, f32>,
+    input: InterleavedAudio<'_, f32>,
+    max_frames: usize,
+    mut process: impl FnMut(InterleavedAudioMut<'_, f32>, InterleavedAudio<'_, f32>),
+) {
+    if max_frames == 0 {
+        output.data_mut().fill(0.0);
+        return;
+    }
+
+    let num_frames = num_frames(&output, &input);
+    let mut start = 0;
+    while start < num_frames {
+        let end = (start + max_frames).min(num_frames);
+        process(
+            output.slice_frames_mut(start..end),
+            input.slice_frames(start..end),
+        );
+        start = end;
+    }
+}
+
+/// the number of frames with this number of channels that fit into a buffer, unlimited without
+/// channels. backends can deliver more channels than a processor was prepared for, so buffers
+/// are split further instead of overrunning the memory allocated in `prepare`.
+pub(crate) fn fitting_frames<T>(buffer: &[T], num_channels: usize) -> usize {
+    buffer.len().checked_div(num_channels).unwrap_or(usize::MAX)
+}
+
+/// input buffers can be shorter than the output, e.g. when there is no input device
+pub(crate) fn num_frames(
+    output: &InterleavedAudioMut<'_, f32>,
+    input: &InterleavedAudio<'_, f32>,
+) -> usize {
+    if output.num_channels() > 0 {
+        output.num_frames()
+    } else {
+        input.num_frames()
+    }
+}
+
+/// copies interleaved frames, channels and frames that are not present in the source are filled
+/// with zeros
+pub(crate) fn copy_frames(
+    dest: &mut InterleavedAudioMut<'_, f32>,
+    source: &InterleavedAudio<'_, f32>,
+) {
+    if dest.num_channels() == source.num_channels() && dest.num_frames() == source.num_frames() {
+        dest.data_mut().copy_from_slice(source.data());
+        return;
+    }
+    dest.data_mut().fill(0.0);
+    let num_ch = dest.num_channels().min(source.num_channels());
+    if num_ch == 0 {
+        return;
+    }
+    for (d, s) in dest.frames_iter_mut().zip(source.frames_iter()) {
+        d[..num_ch].copy_from_slice(&s[..num_ch]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_queue::{channel, MessageSender, OverflowPolicy};
+
+    /// writes the current value into every output sample
+    struct Recorder {
+        value: f32,
+        block_sizes: Vec<usize>,
+    }
+
+    impl AudioProcessor for Recorder {
+        type Message = f32;
+
+        fn prepare(&mut self, _config: DeviceConfig) {}
+
+        fn message_process(&mut self, message: Self::Message) {
+            self.value = message;
+        }
+
+        fn process(
+            &mut self,
+            mut output: InterleavedAudioMut<'_, f32>,
+            _input: InterleavedAudio<'_, f32>,
+        ) {
+            self.block_sizes.push(output.num_frames());
+            output.data_mut().fill(self.value);
+        }
+    }
+
+    fn new_engine(block_size: BlockSize) -> (Engine<Recorder>, MessageSender<f32>) {
+        let (sender, receiver) = channel(16, OverflowPolicy::Block, |_| None);
+        let recorder = Recorder {
+            value: 0.0,
+            block_sizes: Vec::new(),
+        };
+        (
+            Engine::new(recorder, receiver, block_size, 2, 0, 16),
+            sender,
+        )
+    }
+
+    fn process(engine: &mut Engine<Recorder>, output: &mut [f32], num_channels: usize) {
+        engine.process(
+            InterleavedAudioMut::from_slice(output, num_channels),
+            InterleavedAudio::from_slice(&[], 0),
+        );
+    }
+
+    #[test]
+    fn sample_accurate_messages() {
+        let (mut engine, sender) = new_engine(BlockSize::Variable);
+        for (frame, value) in [(6, 2.0), (3, 1.0), (13, 3.0)] {
+            sender.send_at(frame, value).unwrap();
+        }
+
+        let mut output = vec![0.0; 2 * 8];
+        process(&mut engine, &mut output, 2);
+        assert_eq!(
+            output,
+            [0., 0., 0., 0., 0., 0., 1., 1., 1., 1., 1., 1., 2., 2., 2., 2.]
+        );
+
+        process(&mut engine, &mut output, 2);
+        assert_eq!(
+            output,
+            [2., 2., 2., 2., 2., 2., 2., 2., 2., 2., 3., 3., 3., 3., 3., 3.]
+        );
+        assert_eq!(engine.processor.block_sizes, [3, 3, 2, 5, 3]);
+        assert_eq!(sender.clock().frame(), 16);
+    }
+
+    #[test]
+    fn late_and_immediate_messages() {
+        let (mut engine, sender) = new_engine(BlockSize::Variable);
+        let mut output = vec![0.0; 4];
+        process(&mut engine, &mut output, 1);
+
+        // a frame in the past is delivered right away, like an immediate message
+        sender.send_at(1, 1.0).unwrap();
+        process(&mut engine, &mut output, 1);
+        assert_eq!(output, [1.0; 4]);
+
+        sender.send(2.0).unwrap();
+        process(&mut engine, &mut output, 1);
+        assert_eq!(output, [2.0; 4]);
+        assert_eq!(engine.processor.block_sizes, [4, 4, 4]);
+    }
+
+    #[test]
+    fn too_many_scheduled_messages() {
+        let (mut engine, sender) = new_engine(BlockSize::Variable);
+        let mut output = vec![0.0; 4];
+        for _ in 0..16 {
+            sender.send_at(100, 1.0).unwrap();
+        }
+        process(&mut engine, &mut output, 1);
+        assert_eq!(output, [0.0; 4]);
+
+        // the scheduled messages fill the buffer, so the next one can't wait for its frame
+        sender.send_at(100, 2.0).unwrap();
+        process(&mut engine, &mut output, 1);
+        assert_eq!(output, [2.0; 4]);
+        assert_eq!(sender.stats().delivered_early(), 1);
+    }
+
+    #[test]
+    fn max_block_size() {
+        let (mut engine, sender) = new_engine(BlockSize::Max(4));
+        sender.send_at(5, 1.0).unwrap();
+        let mut output = vec![0.0; 2 * 10];
+        process(&mut engine, &mut output, 2);
+        assert_eq!(engine.processor.block_sizes, [4, 1, 3, 2]);
+        assert_eq!(&output[8..12], [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn fixed_block_size() {
+        let (mut engine, sender) = new_engine(BlockSize::Fixed(4));
+        sender.send(1.0).unwrap();
+        let mut output = vec![0.0; 2 * 3];
+        for _ in 0..4 {
+            process(&mut engine, &mut output, 2);
+        }
+        assert_eq!(engine.processor.block_sizes, [4, 4, 4]);
+
+        // the first block is delayed by the block size
+        let (mut engine, sender) = new_engine(BlockSize::Fixed(4));
+        sender.send(1.0).unwrap();
+        let mut output = vec![0.0; 2 * 6];
+        process(&mut engine, &mut output, 2);
+        assert_eq!(output, [0., 0., 0., 0., 0., 0., 0., 0., 1., 1., 1., 1.]);
+    }
+
+    #[test]
+    fn double_precision_processor() {
+        struct Double;
+
+        impl AudioProcessor<f64> for Double {
+            type Message = ();
+
+            fn prepare(&mut self, _config: DeviceConfig) {}
+
+            fn message_process(&mut self, _message: Self::Message) {}
+
+            fn process(
+                &mut self,
+                mut output: InterleavedAudioMut<'_, f64>,
+                input: InterleavedAudio<'_, f64>,
+            ) {
+                for (o, i) in output.data_mut().iter_mut().zip(input.data()) {
+                    *o = *i * 2.0;
+                }
+            }
+        }
+
+        let (_sender, receiver) = channel(16, OverflowPolicy::Block, |_| None);
+        let mut engine = Engine::new(Double, receiver, BlockSize::Variable, 1, 1, 2);
+        let mut output = vec![0.0; 3];
+        engine.process(
+            InterleavedAudioMut::from_slice(&mut output, 1),
+            InterleavedAudio::from_slice(&[0.25, -0.5, 0.125], 1),
+        );
+        assert_eq!(output, [0.5, -1.0, 0.25]);
+    }
+
+    #[test]
+    fn catch_panic() {
+        struct Panicking;
+
+        impl AudioProcessor for Panicking {
+            type Message = ();
+
+            fn prepare(&mut self, _config: DeviceConfig) {}
+
+            fn message_process(&mut self, _message: Self::Message) {
+                panic!("processor failed");
+            }
+
+            fn process(
+                &mut self,
+                mut output: InterleavedAudioMut<'_, f32>,
+                _input: InterleavedAudio<'_, f32>,
+            ) {
+                output.data_mut().fill(1.0);
+            }
+        }
+
+        let (sender, receiver) = channel(16, OverflowPolicy::Block, |_| None);
+        let mut engine = Engine::new(Panicking, receiver, BlockSize::Variable, 1, 0, 4);
+        let fault = StreamFault::default();
+        let mut output = vec![0.0; 4];
+
+        engine.process_catching(
+            InterleavedAudioMut::from_slice(&mut output, 1),
+            InterleavedAudio::from_slice(&[], 0),
+            &fault,
+        );
+        assert_eq!(output, [1.0; 4]);
+        assert!(!fault.is_faulted());
+
+        sender.send(()).unwrap();
+        engine.process_catching(
+            InterleavedAudioMut::from_slice(&mut output, 1),
+            InterleavedAudio::from_slice(&[], 0),
+            &fault,
+        );
+        assert_eq!(output, [0.0; 4]);
+        assert_eq!(fault.message(), Some("processor failed"));
+    }
+}
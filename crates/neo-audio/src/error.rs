@@ -19,6 +19,10 @@ pub enum NeoAudioError {
     NumInputChannels,
     #[error("Number of output channels not supported")]
     NumOutputChannels,
+    #[error("Sample format not supported")]
+    SampleFormat,
+    #[error("Channel layout doesn't match the number of channels")]
+    ChannelLayout,
     #[error("Unspecified")]
     Unspecified,
     #[error("Backend Error {0}")]
@@ -29,6 +33,12 @@ pub enum NeoAudioError {
     OpenStream(String),
     #[error("Failed to send message containing {0}")]
     Send(String),
+    #[error("Audio processor panicked: {0}")]
+    ProcessorPanicked(String),
+    #[error("Invalid audio graph: {0}")]
+    InvalidGraph(String),
+    #[error("Failed to decode audio file: {0}")]
+    Decode(String),
 }
 
 impl<T> From<SendError<T>> for NeoAudioError {
@@ -0,0 +1,846 @@
+//! A graph of processors that runs as one `AudioProcessor`.
+//!
+//! The `AudioGraph` is started like any other processor, while the `GraphEditor` stays in a
+//! non real-time thread. Nodes and edges are changed in the editor and `GraphEditor::commit`
+//! compiles them into a new graph with all buffers allocated. The returned message swaps the graph
+//! in the audio thread, the processors of nodes that are still in use are moved over, so they keep
+//! their state. The replaced graph is sent back to the editor to be dropped there.
+
+use std::{
+    any::Any,
+    sync::{Arc, Mutex},
+};
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+use realtime_tools::{
+    channel_layout::ChannelLayout,
+    interleaved_audio::{InterleavedAudio, InterleavedAudioMut},
+};
+
+use crate::{
+    audio_processor::AudioProcessor,
+    device_config::DeviceConfig,
+    engine::{copy_frames, num_frames, process_chunked},
+    error::NeoAudioError,
+};
+
+/// number of replaced graphs and messages that can wait until the editor drops them
+const GARBAGE_CAPACITY: usize = 64;
+/// number of them the audio thread holds back while the channel to the editor is full
+const OVERFLOW_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// the input of the graph, its channels are the input channels of the device
+    pub const INPUT: NodeId = NodeId(0);
+    /// the output of the graph, its channels are the output channels of the device
+    pub const OUTPUT: NodeId = NodeId(1);
+}
+
+/// Connects one output channel of a node with one input channel of another node.
+/// All edges that end in the same channel are summed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edge {
+    pub from: NodeId,
+    pub from_ch: usize,
+    pub to: NodeId,
+    pub to_ch: usize,
+}
+
+pub enum GraphMessage {
+    /// Swap replaces the running graph with the edit returned by `GraphEditor::commit`.
+    Swap(Box<CompiledGraph>),
+    /// Node delivers a message to the processor of a node, create it with `GraphEditor::message`.
+    Node(NodeId, Box<dyn Any + Send>),
+}
+
+/// object safe version of `AudioProcessor`, so processors with different messages can be stored
+/// in the same graph
+trait Node: Send {
+    fn prepare(&mut self, config: DeviceConfig);
+
+    fn message_process(&mut self, message: &mut (dyn Any + Send));
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>);
+
+    fn latency(&self) -> usize;
+}
+
+/// wraps a processor, so it can be stored as node
+struct ProcessorNode<P>(P);
+
+impl<P> Node for ProcessorNode<P>
+where
+    P: AudioProcessor + Send,
+    P::Message: 'static,
+{
+    fn prepare(&mut self, config: DeviceConfig) {
+        self.0.prepare(config);
+    }
+
+    fn message_process(&mut self, message: &mut (dyn Any + Send)) {
+        // the message is taken out, so its box can be dropped outside of the audio thread
+        if let Some(message) = message
+            .downcast_mut::<Option<P::Message>>()
+            .and_then(Option::take)
+        {
+            self.0.message_process(message);
+        }
+    }
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>) {
+        self.0.process(output, input);
+    }
+
+    fn latency(&self) -> usize {
+        self.0.latency()
+    }
+}
+
+/// replaced graphs and message boxes, the audio thread never deallocates them itself
+type Garbage = Box<dyn Any + Send>;
+
+/// state that `AudioGraph::prepare` hands to the editor
+#[derive(Default)]
+struct Shared {
+    config: Option<DeviceConfig>,
+    latencies: Vec<(NodeId, usize)>,
+}
+
+/// Runs processors in topologically sorted order, routes their channels along the edges and
+/// delays the edges, so that all paths to a node have the same latency.
+pub struct AudioGraph {
+    graph: Box<CompiledGraph>,
+    shared: Arc<Mutex<Shared>>,
+    garbage: Sender<Garbage>,
+    // garbage that didn't fit into the channel, sent again in `process`
+    overflow: Vec<Garbage>,
+}
+
+impl AudioGraph {
+    /// creates an empty graph and the editor to change it
+    pub fn new() -> (Self, GraphEditor) {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let (garbage_sender, garbage_receiver) = bounded(GARBAGE_CAPACITY);
+        let graph = Self {
+            graph: Box::default(),
+            shared: shared.clone(),
+            garbage: garbage_sender,
+            overflow: Vec::with_capacity(OVERFLOW_CAPACITY),
+        };
+        let editor = GraphEditor {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            next_id: 2,
+            shared,
+            garbage: garbage_receiver,
+        };
+        (graph, editor)
+    }
+
+    fn dispose(&mut self, garbage: Garbage) {
+        self.retry_garbage();
+        if let Err(error) = self.garbage.try_send(garbage) {
+            // if the editor is gone or never collects, it is dropped here as a last resort
+            if self.overflow.len() < self.overflow.capacity() {
+                self.overflow.push(error.into_inner());
+            }
+        }
+    }
+
+    /// sends the garbage that was held back again, once the editor made room
+    fn retry_garbage(&mut self) {
+        while let Some(garbage) = self.overflow.pop() {
+            if let Err(error) = self.garbage.try_send(garbage) {
+                // the capacity is kept, so this doesn't allocate
+                self.overflow.push(error.into_inner());
+                break;
+            }
+        }
+    }
+}
+
+impl AudioProcessor for AudioGraph {
+    type Message = GraphMessage;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        self.graph.prepare(&config);
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        shared.latencies = self
+            .graph
+            .slots
+            .iter()
+            .map(|slot| (slot.id, slot.latency))
+            .collect();
+        shared.config = Some(config);
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        match message {
+            GraphMessage::Swap(mut graph) => {
+                graph.take_nodes(&mut self.graph);
+                let old = std::mem::replace(&mut self.graph, graph);
+                self.dispose(old);
+            }
+            GraphMessage::Node(id, mut message) => {
+                if let Some(node) = self.graph.node_mut(id) {
+                    node.message_process(message.as_mut());
+                }
+                self.dispose(message);
+            }
+        }
+    }
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>) {
+        self.retry_garbage();
+        self.graph.process(output, input);
+    }
+
+    fn latency(&self) -> usize {
+        self.graph.latency
+    }
+}
+
+struct NodeInfo {
+    id: NodeId,
+    num_inputs: usize,
+    num_outputs: usize,
+    latency: usize,
+    // the processor until it is committed, afterwards it lives in the audio thread
+    pending: Option<Box<dyn Node>>,
+}
+
+/// Changes the nodes and edges of an `AudioGraph` from a non real-time thread.
+pub struct GraphEditor {
+    nodes: Vec<NodeInfo>,
+    edges: Vec<Edge>,
+    next_id: usize,
+    shared: Arc<Mutex<Shared>>,
+    garbage: Receiver<Garbage>,
+}
+
+impl GraphEditor {
+    /// adds a processor, it gets prepared with a config that has the given number of channels
+    pub fn add_node<P>(&mut self, processor: P, num_inputs: usize, num_outputs: usize) -> NodeId
+    where
+        P: AudioProcessor + Send + 'static,
+        P::Message: Send,
+    {
+        let id = NodeId(self.next_id);
+        self.next_id += 1;
+        self.nodes.push(NodeInfo {
+            id,
+            num_inputs,
+            num_outputs,
+            latency: processor.latency(),
+            pending: Some(Box::new(ProcessorNode(processor))),
+        });
+        id
+    }
+
+    /// removes the node and all of its edges
+    pub fn remove_node(&mut self, id: NodeId) -> Result<(), NeoAudioError> {
+        let index = self
+            .nodes
+            .iter()
+            .position(|node| node.id == id)
+            .ok_or_else(|| NeoAudioError::InvalidGraph(format!("unknown node {id:?}")))?;
+        self.nodes.remove(index);
+        self.edges.retain(|edge| edge.from != id && edge.to != id);
+        Ok(())
+    }
+
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes.iter().map(|node| node.id)
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// connects an output channel of `from` with an input channel of `to`
+    pub fn connect(
+        &mut self,
+        from: NodeId,
+        from_ch: usize,
+        to: NodeId,
+        to_ch: usize,
+    ) -> Result<(), NeoAudioError> {
+        if from == NodeId::OUTPUT || to == NodeId::INPUT {
+            return Err(NeoAudioError::InvalidGraph(
+                "edges have to go from the input towards the output".to_string(),
+            ));
+        }
+        if let Some(num_outputs) = self.num_channels(from, false)? {
+            if from_ch >= num_outputs {
+                return Err(NeoAudioError::InvalidGraph(format!(
+                    "{from:?} has no output channel {from_ch}"
+                )));
+            }
+        }
+        if let Some(num_inputs) = self.num_channels(to, true)? {
+            if to_ch >= num_inputs {
+                return Err(NeoAudioError::InvalidGraph(format!(
+                    "{to:?} has no input channel {to_ch}"
+                )));
+            }
+        }
+        let edge = Edge {
+            from,
+            from_ch,
+            to,
+            to_ch,
+        };
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+        Ok(())
+    }
+
+    /// connects every output channel of `from` with the input channel of `to` with the same index
+    pub fn connect_all(&mut self, from: NodeId, to: NodeId) -> Result<(), NeoAudioError> {
+        let num_outputs = self.num_channels(from, false)?;
+        let num_inputs = self.num_channels(to, true)?;
+        let num_ch = match (num_outputs, num_inputs) {
+            (Some(outputs), Some(inputs)) => outputs.min(inputs),
+            (Some(num_ch), None) | (None, Some(num_ch)) => num_ch,
+            (None, None) => 0,
+        };
+        for ch in 0..num_ch {
+            self.connect(from, ch, to, ch)?;
+        }
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self, from: NodeId, from_ch: usize, to: NodeId, to_ch: usize) {
+        self.edges.retain(|edge| {
+            *edge
+                != Edge {
+                    from,
+                    from_ch,
+                    to,
+                    to_ch,
+                }
+        });
+    }
+
+    /// creates a message for the processor of a node, messages of the wrong type are ignored
+    pub fn message<M>(&self, node: NodeId, message: M) -> GraphMessage
+    where
+        M: Send + 'static,
+    {
+        GraphMessage::Node(node, Box::new(Some(message)))
+    }
+
+    /// compiles the current nodes and edges, send the returned message to the `AudioGraph` to
+    /// swap them in. before the audio is started it can be passed to `message_process` directly.
+    /// new processors are prepared here, if the graph was already prepared.
+    pub fn commit(&mut self) -> Result<GraphMessage, NeoAudioError> {
+        self.collect_garbage();
+        let order = self.sorted()?;
+
+        let config = {
+            let shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+            for (id, latency) in shared.latencies.iter() {
+                if let Some(node) = self.nodes.iter_mut().find(|node| node.id == *id) {
+                    node.latency = *latency;
+                }
+            }
+            shared.config.clone()
+        };
+
+        let mut graph = CompiledGraph::default();
+        graph.outputs.push(NodeOutput {
+            data: Vec::new(),
+            num_channels: 0,
+        });
+        for index in order.iter() {
+            let info = &mut self.nodes[*index];
+            let mut node = info.pending.take();
+            if let (Some(node), Some(config)) = (node.as_mut(), config.as_ref()) {
+                node.prepare(node_config(config, info.num_inputs, info.num_outputs));
+                info.latency = node.latency();
+            }
+            graph.slots.push(Slot {
+                id: info.id,
+                node,
+                num_inputs: info.num_inputs,
+                num_outputs: info.num_outputs,
+                latency: info.latency,
+                input: Vec::new(),
+                edges: Vec::new(),
+            });
+            graph.outputs.push(NodeOutput {
+                data: Vec::new(),
+                num_channels: info.num_outputs,
+            });
+        }
+
+        // output 0 is the graph input, output n + 1 belongs to slot n
+        let output_index = |id: NodeId| {
+            graph
+                .slots
+                .iter()
+                .position(|slot| slot.id == id)
+                .map_or(0, |index| index + 1)
+        };
+        let mut slot_edges = vec![Vec::new(); graph.slots.len()];
+        let mut output_edges = Vec::new();
+        for edge in self.edges.iter() {
+            let compiled = CompiledEdge {
+                from: output_index(edge.from),
+                from_ch: edge.from_ch,
+                to_ch: edge.to_ch,
+                delay: None,
+            };
+            if edge.to == NodeId::OUTPUT {
+                output_edges.push(compiled);
+            } else {
+                slot_edges[output_index(edge.to) - 1].push(compiled);
+            }
+        }
+        for (slot, edges) in graph.slots.iter_mut().zip(slot_edges) {
+            slot.edges = edges;
+        }
+        graph.output_edges = output_edges;
+
+        if let Some(config) = config.as_ref() {
+            graph.allocate(config);
+        }
+        graph.compensate();
+        Ok(GraphMessage::Swap(Box::new(graph)))
+    }
+
+    /// drops replaced graphs and delivered messages the audio thread sent back.
+    /// this is done on every commit, call it regularly if there are many node messages.
+    pub fn collect_garbage(&mut self) {
+        self.garbage.try_iter().for_each(drop);
+    }
+
+    fn num_channels(&self, id: NodeId, input: bool) -> Result<Option<usize>, NeoAudioError> {
+        if id == NodeId::INPUT || id == NodeId::OUTPUT {
+            let shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+            return Ok(shared.config.as_ref().map(|config| {
+                if id == NodeId::INPUT {
+                    config.num_input_ch as usize
+                } else {
+                    config.num_output_ch as usize
+                }
+            }));
+        }
+        self.nodes
+            .iter()
+            .find(|node| node.id == id)
+            .map(|node| {
+                Some(if input {
+                    node.num_inputs
+                } else {
+                    node.num_outputs
+                })
+            })
+            .ok_or_else(|| NeoAudioError::InvalidGraph(format!("unknown node {id:?}")))
+    }
+
+    /// indices of the nodes in topological order
+    fn sorted(&self) -> Result<Vec<usize>, NeoAudioError> {
+        let index_of = |id: NodeId| self.nodes.iter().position(|node| node.id == id);
+        let mut num_inputs = vec![0; self.nodes.len()];
+        for edge in self.edges.iter() {
+            if let (Some(_), Some(to)) = (index_of(edge.from), index_of(edge.to)) {
+                num_inputs[to] += 1;
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut ready = (0..self.nodes.len())
+            .filter(|index| num_inputs[*index] == 0)
+            .collect::<Vec<_>>();
+        while let Some(index) = ready.pop() {
+            order.push(index);
+            let id = self.nodes[index].id;
+            for edge in self.edges.iter().filter(|edge| edge.from == id) {
+                if let Some(to) = index_of(edge.to) {
+                    num_inputs[to] -= 1;
+                    if num_inputs[to] == 0 {
+                        ready.push(to);
+                    }
+                }
+            }
+        }
+
+        if order.len() < self.nodes.len() {
+            return Err(NeoAudioError::InvalidGraph(
+                "the edges contain a cycle".to_string(),
+            ));
+        }
+        Ok(order)
+    }
+}
+
+fn node_config(config: &DeviceConfig, num_inputs: usize, num_outputs: usize) -> DeviceConfig {
+    DeviceConfig {
+        num_output_ch: num_outputs as u16,
+        num_input_ch: num_inputs as u16,
+        output_layout: ChannelLayout::from_num_channels(num_outputs),
+        input_layout: ChannelLayout::from_num_channels(num_inputs),
+        ..config.clone()
+    }
+}
+
+/// A graph compiled by `GraphEditor::commit`, with all buffers allocated.
+#[derive(Default)]
+pub struct CompiledGraph {
+    // in topological order
+    slots: Vec<Slot>,
+    // output 0 is the graph input, output n + 1 belongs to slot n
+    outputs: Vec<NodeOutput>,
+    output_edges: Vec<CompiledEdge>,
+    max_frames: usize,
+    latency: usize,
+}
+
+struct Slot {
+    id: NodeId,
+    node: Option<Box<dyn Node>>,
+    num_inputs: usize,
+    num_outputs: usize,
+    latency: usize,
+    input: Vec<f32>,
+    // the edges that end in this node
+    edges: Vec<CompiledEdge>,
+}
+
+struct NodeOutput {
+    data: Vec<f32>,
+    num_channels: usize,
+}
+
+#[derive(Clone)]
+struct CompiledEdge {
+    from: usize,
+    from_ch: usize,
+    to_ch: usize,
+    delay: Option<DelayLine>,
+}
+
+impl CompiledGraph {
+    fn prepare(&mut self, config: &DeviceConfig) {
+        for slot in self.slots.iter_mut() {
+            if let Some(node) = slot.node.as_mut() {
+                node.prepare(node_config(config, slot.num_inputs, slot.num_outputs));
+                slot.latency = node.latency();
+            }
+        }
+        self.allocate(config);
+        self.compensate();
+    }
+
+    fn allocate(&mut self, config: &DeviceConfig) {
+        self.max_frames = config.num_frames as usize;
+        if self.outputs.is_empty() {
+            self.outputs.push(NodeOutput {
+                data: Vec::new(),
+                num_channels: 0,
+            });
+        }
+        self.outputs[0].num_channels = config.num_input_ch as usize;
+        for (slot, output) in self.slots.iter_mut().zip(self.outputs[1..].iter_mut()) {
+            slot.input = vec![0.0; slot.num_inputs * self.max_frames];
+            output.num_channels = slot.num_outputs;
+        }
+        for output in self.outputs.iter_mut() {
+            output.data = vec![0.0; output.num_channels * self.max_frames];
+        }
+    }
+
+    /// delays the edges, so that all edges that end in a node have the same latency
+    fn compensate(&mut self) {
+        // the latency at the output of the graph input and of every slot
+        let mut output_latency = vec![0; self.slots.len() + 1];
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let input_latency = delay_edges(&mut slot.edges, &output_latency);
+            output_latency[index + 1] = input_latency + slot.latency;
+        }
+        self.latency = delay_edges(&mut self.output_edges, &output_latency);
+    }
+
+    /// moves the processors of nodes that are still in use out of the old graph
+    fn take_nodes(&mut self, old: &mut CompiledGraph) {
+        for slot in self.slots.iter_mut().filter(|slot| slot.node.is_none()) {
+            if let Some(old_slot) = old.slots.iter_mut().find(|old| old.id == slot.id) {
+                slot.node = old_slot.node.take();
+            }
+        }
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Box<dyn Node>> {
+        self.slots
+            .iter_mut()
+            .find(|slot| slot.id == id)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>) {
+        // blocks larger than the prepared number of frames are processed in chunks
+        let max_frames = self.max_frames;
+        process_chunked(output, input, max_frames, |output, input| {
+            self.process_chunk(output, input)
+        });
+    }
+
+    fn process_chunk(
+        &mut self,
+        mut output: InterleavedAudioMut<'_, f32>,
+        input: InterleavedAudio<'_, f32>,
+    ) {
+        let num_frames = num_frames(&output, &input);
+        let graph_input = &mut self.outputs[0];
+        let num_ch = graph_input.num_channels;
+        copy_frames(
+            &mut InterleavedAudioMut::from_slice(
+                &mut graph_input.data[..num_frames * num_ch],
+                num_ch,
+            ),
+            &input,
+        );
+
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let (sources, rest) = self.outputs.split_at_mut(index + 1);
+            let node_input = &mut slot.input[..num_frames * slot.num_inputs];
+            let node_output = &mut rest[0].data[..num_frames * slot.num_outputs];
+            mix_edges(
+                node_input,
+                slot.num_inputs,
+                &mut slot.edges,
+                sources,
+                num_frames,
+            );
+            match slot.node.as_mut() {
+                Some(node) => node.process(
+                    InterleavedAudioMut::from_slice(node_output, slot.num_outputs),
+                    InterleavedAudio::from_slice(node_input, slot.num_inputs),
+                ),
+                None => node_output.fill(0.0),
+            }
+        }
+
+        let num_output_ch = output.num_channels();
+        mix_edges(
+            output.data_mut(),
+            num_output_ch,
+            &mut self.output_edges,
+            &self.outputs,
+            num_frames,
+        );
+    }
+}
+
+/// sets the delays of the edges and returns the latency they are aligned to
+fn delay_edges(edges: &mut [CompiledEdge], output_latency: &[usize]) -> usize {
+    let latency = edges
+        .iter()
+        .map(|edge| output_latency[edge.from])
+        .max()
+        .unwrap_or(0);
+    for edge in edges.iter_mut() {
+        let delay = latency - output_latency[edge.from];
+        edge.delay = (delay > 0).then(|| DelayLine::new(delay));
+    }
+    latency
+}
+
+/// sums all edges into the interleaved destination
+fn mix_edges(
+    dest: &mut [f32],
+    dest_ch: usize,
+    edges: &mut [CompiledEdge],
+    sources: &[NodeOutput],
+    num_frames: usize,
+) {
+    dest.fill(0.0);
+    for edge in edges.iter_mut() {
+        let source = &sources[edge.from];
+        // the channels of the graph input and output are only known at runtime
+        if edge.from_ch >= source.num_channels || edge.to_ch >= dest_ch {
+            continue;
+        }
+        let samples = source.data[..num_frames * source.num_channels]
+            .iter()
+            .skip(edge.from_ch)
+            .step_by(source.num_channels);
+        let dest_samples = dest.iter_mut().skip(edge.to_ch).step_by(dest_ch);
+        match edge.delay.as_mut() {
+            Some(delay) => dest_samples
+                .zip(samples)
+                .for_each(|(d, s)| *d += delay.process(*s)),
+            None => dest_samples.zip(samples).for_each(|(d, s)| *d += *s),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct DelayLine {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl DelayLine {
+    fn new(delay: usize) -> Self {
+        Self {
+            buffer: vec![0.0; delay],
+            position: 0,
+        }
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        let delayed = std::mem::replace(&mut self.buffer[self.position], sample);
+        self.position = (self.position + 1) % self.buffer.len();
+        delayed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::test_utils::{config, process_input};
+
+    struct Gain(f32);
+
+    impl AudioProcessor for Gain {
+        type Message = f32;
+
+        fn prepare(&mut self, _config: DeviceConfig) {}
+
+        fn message_process(&mut self, message: Self::Message) {
+            self.0 = message;
+        }
+
+        fn process(
+            &mut self,
+            mut output: InterleavedAudioMut<'_, f32>,
+            input: InterleavedAudio<'_, f32>,
+        ) {
+            for (o, i) in output.data_mut().iter_mut().zip(input.data()) {
+                *o = *i * self.0;
+            }
+        }
+    }
+
+    struct Delay(VecDeque<f32>);
+
+    impl AudioProcessor for Delay {
+        type Message = ();
+
+        fn prepare(&mut self, _config: DeviceConfig) {
+            self.0 = VecDeque::from(vec![0.0; 2]);
+        }
+
+        fn message_process(&mut self, _message: Self::Message) {}
+
+        fn latency(&self) -> usize {
+            2
+        }
+
+        fn process(
+            &mut self,
+            mut output: InterleavedAudioMut<'_, f32>,
+            input: InterleavedAudio<'_, f32>,
+        ) {
+            for (o, i) in output.data_mut().iter_mut().zip(input.data()) {
+                self.0.push_back(*i);
+                *o = self.0.pop_front().unwrap_or_default();
+            }
+        }
+    }
+
+    #[test]
+    fn latency_compensation() {
+        let (mut graph, mut editor) = AudioGraph::new();
+        let gain = editor.add_node(Gain(2.0), 1, 1);
+        let delay = editor.add_node(Delay(VecDeque::new()), 1, 1);
+        for node in [gain, delay] {
+            editor.connect(NodeId::INPUT, 0, node, 0).unwrap();
+            editor.connect(node, 0, NodeId::OUTPUT, 0).unwrap();
+        }
+        graph.message_process(editor.commit().unwrap());
+        graph.prepare(DeviceConfig {
+            num_frames: 4,
+            ..config(1, 1)
+        });
+        assert_eq!(graph.latency(), 2);
+
+        // the gain path is delayed like the delay path, the 6 frames are processed in 2 chunks
+        let output = process_input(&mut graph, &[1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(output, [0.0, 0.0, 3.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn swap_keeps_processors() {
+        let (mut graph, mut editor) = AudioGraph::new();
+        graph.prepare(config(1, 1));
+        let gain = editor.add_node(Gain(1.0), 1, 1);
+        editor.connect_all(NodeId::INPUT, gain).unwrap();
+        editor.connect_all(gain, NodeId::OUTPUT).unwrap();
+        graph.message_process(editor.commit().unwrap());
+        graph.message_process(editor.message(gain, 0.5_f32));
+        assert_eq!(process_input(&mut graph, &[1.0; 4]), [0.5; 4]);
+        // the replaced graph and the message box are sent back to be dropped
+        assert_eq!(editor.garbage.len(), 2);
+
+        // a second gain in series, the first one keeps its gain
+        let second = editor.add_node(Gain(4.0), 1, 1);
+        editor.disconnect(gain, 0, NodeId::OUTPUT, 0);
+        editor.connect(gain, 0, second, 0).unwrap();
+        editor.connect(second, 0, NodeId::OUTPUT, 0).unwrap();
+        graph.message_process(editor.commit().unwrap());
+        assert_eq!(process_input(&mut graph, &[1.0; 4]), [2.0; 4]);
+
+        editor.collect_garbage();
+        assert!(editor.garbage.is_empty());
+    }
+
+    #[test]
+    fn garbage_overflow() {
+        let (mut graph, mut editor) = AudioGraph::new();
+        graph.prepare(config(1, 1));
+        let gain = editor.add_node(Gain(1.0), 1, 1);
+        editor.connect_all(NodeId::INPUT, gain).unwrap();
+        editor.connect_all(gain, NodeId::OUTPUT).unwrap();
+        graph.message_process(editor.commit().unwrap());
+
+        // the boxes that don't fit into the channel are held back instead of dropped
+        for _ in 0..100 {
+            graph.message_process(editor.message(gain, 0.5_f32));
+        }
+        assert_eq!(editor.garbage.len(), GARBAGE_CAPACITY);
+        assert_eq!(graph.overflow.len(), 101 - GARBAGE_CAPACITY);
+
+        editor.collect_garbage();
+        assert_eq!(process_input(&mut graph, &[1.0]), [0.5]);
+        assert_eq!(editor.garbage.len(), 101 - GARBAGE_CAPACITY);
+        assert!(graph.overflow.is_empty());
+    }
+
+    #[test]
+    fn invalid_edges() {
+        let (_graph, mut editor) = AudioGraph::new();
+        let a = editor.add_node(Gain(1.0), 1, 1);
+        let b = editor.add_node(Gain(1.0), 1, 1);
+        assert!(editor.connect(a, 1, b, 0).is_err());
+        assert!(editor.connect(NodeId::OUTPUT, 0, a, 0).is_err());
+
+        editor.connect(a, 0, b, 0).unwrap();
+        editor.connect(b, 0, a, 0).unwrap();
+        assert!(editor.commit().is_err());
+
+        editor.remove_node(b).unwrap();
+        assert!(editor.edges().is_empty());
+        assert!(editor.commit().is_ok());
+    }
+}
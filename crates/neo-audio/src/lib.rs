@@ -1,22 +1,40 @@
 use audio_processor::AudioProcessor;
 use backends::AudioBackend;
-use crossbeam_channel::Sender;
+use engine::{BlockSize, Engine, StreamFault};
 use error::NeoAudioError;
+use message_queue::{MessageSender, OverflowPolicy, DEFAULT_MESSAGE_CAPACITY};
+use sample::Sample;
 
 pub mod audio_processor;
 pub mod backends;
+#[cfg(feature = "decode")]
+pub mod decode;
 pub mod device_config;
 pub mod device_name;
+pub mod engine;
 pub mod error;
+pub mod graph;
+pub mod message_queue;
+pub mod planar_processor;
 pub mod prelude;
 #[cfg(feature = "processors")]
 pub mod processors;
+pub mod render;
+#[cfg(feature = "rt-check")]
+pub mod rt_check;
+pub mod sample;
+#[cfg(test)]
+mod test_utils;
 
 pub struct NeoAudio<B>
 where
     B: AudioBackend,
 {
     backend: B,
+    message_capacity: usize,
+    overflow_policy: OverflowPolicy,
+    block_size: BlockSize,
+    fault: StreamFault,
 }
 
 unsafe impl<B> Sync for NeoAudio<B> where B: AudioBackend {}
@@ -29,6 +47,10 @@ where
     pub fn new() -> Result<Self, NeoAudioError> {
         Ok(Self {
             backend: B::default()?,
+            message_capacity: DEFAULT_MESSAGE_CAPACITY,
+            overflow_policy: OverflowPolicy::default(),
+            block_size: BlockSize::default(),
+            fault: StreamFault::default(),
         })
     }
 
@@ -40,34 +62,85 @@ where
         &mut self.backend
     }
 
-    pub fn start_audio<P>(&mut self, mut processor: P) -> Result<Sender<P::Message>, NeoAudioError>
+    /// the number of messages that can wait in the queue to the audio thread,
+    /// takes effect the next time the audio is started
+    pub fn set_message_capacity(&mut self, capacity: usize) {
+        self.message_capacity = capacity.max(1);
+    }
+
+    pub fn message_capacity(&self) -> usize {
+        self.message_capacity
+    }
+
+    /// decides what happens when the message queue is full,
+    /// takes effect the next time the audio is started
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.overflow_policy = policy;
+    }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    /// decides how many frames the processor gets in one `process` call,
+    /// takes effect the next time the audio is started
+    pub fn set_block_size(&mut self, block_size: BlockSize) {
+        self.block_size = block_size;
+    }
+
+    pub fn block_size(&self) -> BlockSize {
+        self.block_size
+    }
+
+    /// the latency in frames that is added by the engine on top of the latency of the backend
+    pub fn added_latency(&self) -> usize {
+        self.block_size.latency()
+    }
+
+    /// starts the stream with a processor. the sample type `T` is inferred from the
+    /// `AudioProcessor` implementation, so it only needs to be named if there is more than one.
+    pub fn start_audio<P, T>(
+        &mut self,
+        processor: P,
+    ) -> Result<MessageSender<P::Message>, NeoAudioError>
     where
-        P: AudioProcessor + Send + 'static,
-        <P as audio_processor::AudioProcessor>::Message: std::marker::Send,
+        P: AudioProcessor<T> + Send + 'static,
+        <P as audio_processor::AudioProcessor<T>>::Message: std::marker::Send,
+        T: Sample,
     {
-        let (sender, receiver) = crossbeam_channel::bounded(1024);
-        processor.prepare(self.backend.config());
-        self.backend.start_stream(move |output, input| {
-            // receive all messages
-            for _ in 0..receiver.len() {
-                match receiver.try_recv() {
-                    Ok(message) => {
-                        processor.message_process(message);
-                    }
-                    _ => break,
-                }
-            }
-
-            processor.process(output, input);
-        })?;
+        let (sender, receiver) =
+            message_queue::channel(self.message_capacity, self.overflow_policy, P::coalesce_key);
+
+        let mut engine =
+            Engine::prepared(processor, self.backend.config(), receiver, self.block_size);
+        self.fault = StreamFault::default();
+        let fault = self.fault.clone();
+        self.backend
+            .start_stream(move |output, input| engine.process_catching(output, input, &fault))?;
         Ok(sender)
     }
 
     pub fn stop_audio(&mut self) -> Result<(), NeoAudioError> {
         self.backend.stop_stream()?;
-        self.backend.stream_error()?;
+        #[cfg(feature = "rt-check")]
+        rt_check::report();
+        self.stream_error()?;
         Ok(())
     }
+
+    /// returns true if the processor panicked, the stream then only outputs silence
+    pub fn is_faulted(&self) -> bool {
+        self.fault.is_faulted()
+    }
+
+    /// if the processor panicked or an error happened in the backend during the audio stream,
+    /// it will be returned by this function
+    pub fn stream_error(&self) -> Result<(), NeoAudioError> {
+        if let Some(message) = self.fault.message() {
+            return Err(NeoAudioError::ProcessorPanicked(message.to_string()));
+        }
+        self.backend.stream_error()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,448 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use crossbeam_channel::{Receiver, SendError, Sender, TrySendError};
+
+use crate::error::NeoAudioError;
+
+pub const DEFAULT_MESSAGE_CAPACITY: usize = 1024;
+
+/// Decides what happens when a message is sent while the message queue to the audio thread is
+/// full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OverflowPolicy {
+    /// Block the sending thread until the audio thread made room in the queue.
+    #[default]
+    Block,
+    /// Discard the message that should be sent.
+    DropNewest,
+    /// Discard the oldest pending message to make room for the new one.
+    DropOldest,
+    /// Merge pending messages with the same `AudioProcessor::coalesce_key`, so only the latest
+    /// message per key is kept. Messages sent with a timestamp are never merged. If the queue is
+    /// still full afterwards, the oldest pending message is discarded. Every send takes a lock,
+    /// and while the queue is merged the audio thread gets the pending messages one callback
+    /// later.
+    Coalesce,
+}
+
+/// Counts the frames that were processed since the audio was started.
+/// Can be read from any thread to schedule messages with `MessageSender::send_at`.
+#[derive(Clone, Default)]
+pub struct StreamClock {
+    frames: Arc<AtomicU64>,
+}
+
+impl StreamClock {
+    pub fn frame(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_frame(&self, frame: u64) {
+        self.frames.store(frame, Ordering::Relaxed);
+    }
+}
+
+/// A message together with the frame on the stream clock it should be delivered at.
+/// Messages without a frame are delivered at the start of the next audio callback.
+pub(crate) struct Timed<M> {
+    pub frame: Option<u64>,
+    pub message: M,
+}
+
+#[derive(Default)]
+struct Counters {
+    dropped: AtomicUsize,
+    max_depth: AtomicUsize,
+    delivered_early: AtomicUsize,
+}
+
+/// Counters of a message queue, can be cloned and read from any thread.
+#[derive(Clone, Default)]
+pub struct MessageQueueStats {
+    counters: Arc<Counters>,
+}
+
+impl MessageQueueStats {
+    /// number of messages that got discarded because of the `OverflowPolicy`
+    pub fn dropped(&self) -> usize {
+        self.counters.dropped.load(Ordering::Relaxed)
+    }
+
+    /// the maximum number of pending messages the audio thread found in the queue
+    pub fn max_depth(&self) -> usize {
+        self.counters.max_depth.load(Ordering::Relaxed)
+    }
+
+    /// number of messages sent with `send_at` that were delivered before their frame, because
+    /// the audio thread had no room left to keep them until then
+    pub fn delivered_early(&self) -> usize {
+        self.counters.delivered_early.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.counters.dropped.store(0, Ordering::Relaxed);
+        self.counters.max_depth.store(0, Ordering::Relaxed);
+        self.counters.delivered_early.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_dropped(&self, num: usize) {
+        self.counters.dropped.fetch_add(num, Ordering::Relaxed);
+    }
+
+    /// real-time safe, called by the audio thread for a message it can't schedule
+    pub(crate) fn add_delivered_early(&self) {
+        self.counters.delivered_early.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// real-time safe, called by the audio thread before it reads the queue
+    pub(crate) fn record_depth(&self, depth: usize) {
+        self.counters.max_depth.fetch_max(depth, Ordering::Relaxed);
+    }
+}
+
+/// Creates the message queue to the audio thread.
+pub(crate) fn channel<M>(
+    capacity: usize,
+    policy: OverflowPolicy,
+    coalesce_key: fn(&M) -> Option<u64>,
+) -> (MessageSender<M>, MessageReceiver<M>) {
+    let (sender, receiver) = crossbeam_channel::bounded(capacity);
+    let receiver = MessageReceiver {
+        receiver,
+        stats: MessageQueueStats::default(),
+        clock: StreamClock::default(),
+        connected: Arc::new(AtomicBool::new(true)),
+    };
+    let sender = MessageSender::new(
+        sender,
+        &receiver.receiver,
+        policy,
+        coalesce_key,
+        receiver.stats.clone(),
+        receiver.clock.clone(),
+        receiver.connected.clone(),
+    );
+    (sender, receiver)
+}
+
+/// The receiving end of the message queue, owned by the audio engine.
+pub(crate) struct MessageReceiver<M> {
+    pub receiver: Receiver<Timed<M>>,
+    pub stats: MessageQueueStats,
+    pub clock: StreamClock,
+    // cleared when the engine is dropped, the senders of some policies keep the queue open
+    connected: Arc<AtomicBool>,
+}
+
+impl<M> Drop for MessageReceiver<M> {
+    fn drop(&mut self) {
+        self.connected.store(false, Ordering::Release);
+    }
+}
+
+/// The sending end of the message queue to the audio thread. It can be cloned as often as you
+/// like and applies the `OverflowPolicy` that was selected when the audio was started.
+pub struct MessageSender<M> {
+    sender: Sender<Timed<M>>,
+    // only present for policies that need to take pending messages out of the queue
+    receiver: Option<Receiver<Timed<M>>>,
+    policy: OverflowPolicy,
+    coalesce_key: fn(&M) -> Option<u64>,
+    stats: MessageQueueStats,
+    clock: StreamClock,
+    connected: Arc<AtomicBool>,
+    // makes sure clones don't rearrange the queue at the same time
+    overflow_lock: Arc<Mutex<CoalesceBuffers<M>>>,
+}
+
+/// the buffers to merge the queue, so a full queue doesn't allocate every time
+struct CoalesceBuffers<M> {
+    pending: Vec<Timed<M>>,
+    keys: Vec<u64>,
+}
+
+impl<M> Clone for MessageSender<M> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            receiver: self.receiver.clone(),
+            policy: self.policy,
+            coalesce_key: self.coalesce_key,
+            stats: self.stats.clone(),
+            clock: self.clock.clone(),
+            connected: self.connected.clone(),
+            overflow_lock: self.overflow_lock.clone(),
+        }
+    }
+}
+
+impl<M> MessageSender<M> {
+    fn new(
+        sender: Sender<Timed<M>>,
+        receiver: &Receiver<Timed<M>>,
+        policy: OverflowPolicy,
+        coalesce_key: fn(&M) -> Option<u64>,
+        stats: MessageQueueStats,
+        clock: StreamClock,
+        connected: Arc<AtomicBool>,
+    ) -> Self {
+        let receiver = match policy {
+            OverflowPolicy::DropOldest | OverflowPolicy::Coalesce => Some(receiver.clone()),
+            OverflowPolicy::Block | OverflowPolicy::DropNewest => None,
+        };
+        // the pending messages and the new one
+        let buffer_len = match policy {
+            OverflowPolicy::Coalesce => {
+                receiver.as_ref().and_then(|r| r.capacity()).unwrap_or(0) + 1
+            }
+            _ => 0,
+        };
+        Self {
+            sender,
+            receiver,
+            policy,
+            coalesce_key,
+            stats,
+            clock,
+            connected,
+            overflow_lock: Arc::new(Mutex::new(CoalesceBuffers {
+                pending: Vec::with_capacity(buffer_len),
+                keys: Vec::with_capacity(buffer_len),
+            })),
+        }
+    }
+
+    /// Sends a message to the audio thread. If the queue is full, the `OverflowPolicy` decides
+    /// if this call blocks or if a message gets discarded. Discarded messages are not an error,
+    /// they are counted in the `MessageQueueStats`.
+    pub fn send(&self, message: M) -> Result<(), NeoAudioError> {
+        self.send_timed(Timed {
+            frame: None,
+            message,
+        })
+    }
+
+    /// Sends a message that is delivered exactly at the given frame of the `StreamClock`.
+    /// The audio engine splits the processing at this frame, so the processor receives the
+    /// message sample-accurate. Frames that already passed are delivered at the start of the
+    /// next audio callback. The engine keeps as many messages for later as the queue holds, more
+    /// are delivered right away and counted in `MessageQueueStats::delivered_early`.
+    pub fn send_at(&self, frame: u64, message: M) -> Result<(), NeoAudioError> {
+        self.send_timed(Timed {
+            frame: Some(frame),
+            message,
+        })
+    }
+
+    fn send_timed(&self, message: Timed<M>) -> Result<(), NeoAudioError> {
+        // the same error for every policy, even if the sender keeps the queue open
+        if !self.connected.load(Ordering::Acquire) {
+            return Err(SendError(message).into());
+        }
+        if self.policy == OverflowPolicy::Coalesce {
+            // the queue is only merged correctly if no clone sends in between
+            return self.send_coalesced(message);
+        }
+        let message = match self.sender.try_send(message) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Disconnected(message)) => return Err(SendError(message).into()),
+            Err(TrySendError::Full(message)) => message,
+        };
+
+        match self.policy {
+            OverflowPolicy::Block => {
+                #[cfg(feature = "rt-check")]
+                crate::rt_check::report_blocking("MessageSender::send on a full queue");
+                self.sender.send(message)?
+            }
+            OverflowPolicy::DropNewest => self.stats.add_dropped(1),
+            OverflowPolicy::DropOldest => self.send_drop_oldest(message)?,
+            OverflowPolicy::Coalesce => unreachable!("coalesced messages are sent with the lock"),
+        }
+        Ok(())
+    }
+
+    /// the number of messages that wait to be processed by the audio thread
+    pub fn len(&self) -> usize {
+        self.sender.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sender.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.sender.capacity().unwrap_or(usize::MAX)
+    }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    pub fn stats(&self) -> &MessageQueueStats {
+        &self.stats
+    }
+
+    /// the clock of the running stream, use it to calculate frames for `send_at`
+    pub fn clock(&self) -> &StreamClock {
+        &self.clock
+    }
+
+    fn receiver(&self) -> &Receiver<Timed<M>> {
+        self.receiver
+            .as_ref()
+            .expect("receiver is always present for dropping policies")
+    }
+
+    fn send_drop_oldest(&self, mut message: Timed<M>) -> Result<(), NeoAudioError> {
+        #[cfg(feature = "rt-check")]
+        crate::rt_check::report_blocking("MessageSender::send on a full queue");
+        let _lock = self.overflow_lock.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match self.sender.try_send(message) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(message)) => return Err(SendError(message).into()),
+                Err(TrySendError::Full(m)) => {
+                    message = m;
+                    if self.receiver().try_recv().is_ok() {
+                        self.stats.add_dropped(1);
+                    }
+                }
+            }
+        }
+    }
+
+    fn send_coalesced(&self, message: Timed<M>) -> Result<(), NeoAudioError> {
+        #[cfg(feature = "rt-check")]
+        crate::rt_check::report_blocking("MessageSender::send with OverflowPolicy::Coalesce");
+        let mut buffers = self.overflow_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let message = match self.sender.try_send(message) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Disconnected(message)) => return Err(SendError(message).into()),
+            Err(TrySendError::Full(message)) => message,
+        };
+
+        let CoalesceBuffers { pending, keys } = &mut *buffers;
+        pending.extend(self.receiver().try_iter());
+        pending.push(message);
+
+        // keep only the latest message per key, walking from the newest to the oldest
+        let num_pending = pending.len();
+        keys.clear();
+        pending.reverse();
+        pending.retain(|message| {
+            let key = (self.coalesce_key)(&message.message).filter(|_| message.frame.is_none());
+            match key {
+                Some(key) if keys.contains(&key) => false,
+                Some(key) => {
+                    keys.push(key);
+                    true
+                }
+                None => true,
+            }
+        });
+        pending.reverse();
+        // the merged messages count as dropped as well
+        let mut dropped = num_pending - pending.len();
+
+        // if there are still more messages than the queue can hold, the oldest ones are dropped
+        let overflow = pending.len().saturating_sub(self.capacity());
+        dropped += overflow;
+        self.stats.add_dropped(dropped);
+        // only the audio thread takes messages out while the lock is held, so all of them fit
+        for message in pending.drain(..).skip(overflow) {
+            if let Err(TrySendError::Disconnected(message)) = self.sender.try_send(message) {
+                return Err(SendError(message).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sender(
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> (MessageSender<u32>, MessageReceiver<u32>) {
+        channel(capacity, policy, |m| Some((*m / 10) as u64))
+    }
+
+    fn received(receiver: &MessageReceiver<u32>) -> Vec<u32> {
+        receiver.receiver.try_iter().map(|m| m.message).collect()
+    }
+
+    #[test]
+    fn drop_newest() {
+        let (sender, receiver) = sender(2, OverflowPolicy::DropNewest);
+        for i in 0..4 {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(received(&receiver), [0, 1]);
+        assert_eq!(sender.stats().dropped(), 2);
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let (sender, receiver) = sender(2, OverflowPolicy::DropOldest);
+        for i in 0..4 {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(received(&receiver), [2, 3]);
+        assert_eq!(sender.stats().dropped(), 2);
+    }
+
+    #[test]
+    fn coalesce() {
+        let (sender, receiver) = sender(3, OverflowPolicy::Coalesce);
+        // keys 0, 1, 0, 1, 2
+        for i in [1, 11, 2, 12, 20] {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(received(&receiver), [2, 12, 20]);
+        assert_eq!(sender.stats().dropped(), 2);
+
+        sender.stats().reset();
+        // all keys are different, so the oldest one has to go
+        for i in [10, 20, 30, 40] {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(received(&receiver), [20, 30, 40]);
+        assert_eq!(sender.stats().dropped(), 1);
+
+        sender.stats().reset();
+        // timed messages are never merged
+        for frame in [0, 64, 128] {
+            sender.send_at(frame, 1).unwrap();
+        }
+        sender.send(2).unwrap();
+        assert_eq!(received(&receiver), [1, 1, 2]);
+        assert_eq!(sender.stats().dropped(), 1);
+
+        // the merge reuses its buffers
+        let buffers = sender.overflow_lock.lock().unwrap();
+        assert!(buffers.pending.is_empty());
+        assert_eq!(buffers.pending.capacity(), 4);
+    }
+
+    #[test]
+    fn disconnected() {
+        for policy in [
+            OverflowPolicy::Block,
+            OverflowPolicy::DropNewest,
+            OverflowPolicy::DropOldest,
+            OverflowPolicy::Coalesce,
+        ] {
+            let (sender, receiver) = sender(2, policy);
+            drop(receiver);
+            assert!(sender.send(0).is_err(), "{policy:?}");
+            assert!(sender.clone().send_at(0, 1).is_err(), "{policy:?}");
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use realtime_tools::{
+    buffer_ops::{deinterleave, interleave},
+    planar_audio::{PlanarAudio, PlanarAudioMut},
+};
+
+use crate::{
+    audio_processor::AudioProcessor,
+    device_config::DeviceConfig,
+    engine::{num_frames, process_chunked},
+    prelude::{InterleavedAudio, InterleavedAudioMut},
+};
+
+/// Like `AudioProcessor`, but `process` gets non-interleaved buffers, so every channel is one
+/// contiguous slice. Start it wrapped in `Planar`.
+pub trait PlanarAudioProcessor {
+    type Message;
+
+    /// prepare is called just before the audio engine is started, so that anything can be handled
+    /// that is not "real-time" safe, like resize arrays, make system calls etc.
+    fn prepare(&mut self, config: DeviceConfig);
+
+    /// the message process will handle all incoming messages in the audio thread,
+    /// see `AudioProcessor::message_process`
+    fn message_process(&mut self, message: Self::Message);
+
+    /// see `AudioProcessor::coalesce_key`
+    fn coalesce_key(_message: &Self::Message) -> Option<u64>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// see `AudioProcessor::latency`
+    fn latency(&self) -> usize {
+        0
+    }
+
+    /// here you can manipulate the audio streams channel by channel.
+    /// do not do anything that blocks the audio stream.
+    fn process(&mut self, output: PlanarAudioMut<'_, f32>, input: PlanarAudio<'_, f32>);
+}
+
+/// Runs a `PlanarAudioProcessor` as `AudioProcessor`. The audio is deinterleaved into buffers
+/// that are allocated in `prepare` and the output is interleaved again.
+pub struct Planar<P> {
+    inner: P,
+    num_output_ch: usize,
+    num_input_ch: usize,
+    max_frames: usize,
+    output: Vec<f32>,
+    input: Vec<f32>,
+}
+
+impl<P> Planar<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            num_output_ch: 0,
+            num_input_ch: 0,
+            max_frames: 0,
+            output: Vec::new(),
+            input: Vec::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+}
+
+impl<P> AudioProcessor for Planar<P>
+where
+    P: PlanarAudioProcessor,
+{
+    type Message = P::Message;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        self.num_output_ch = config.num_output_ch as usize;
+        self.num_input_ch = config.num_input_ch as usize;
+        self.max_frames = config.num_frames as usize;
+        self.output = vec![0.0; self.num_output_ch * self.max_frames];
+        self.input = vec![0.0; self.num_input_ch * self.max_frames];
+        self.inner.prepare(config);
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        self.inner.message_process(message);
+    }
+
+    fn coalesce_key(message: &Self::Message) -> Option<u64> {
+        P::coalesce_key(message)
+    }
+
+    fn latency(&self) -> usize {
+        self.inner.latency()
+    }
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>) {
+        process_chunked(output, input, self.max_frames, |mut output, input| {
+            let num_frames = num_frames(&output, &input);
+
+            // the channels are kept `max_frames` apart, so the buffers never have to be moved
+            let mut planar_input = PlanarAudioMut::from_slice_with_stride(
+                &mut self.input,
+                self.num_input_ch,
+                num_frames,
+                self.max_frames,
+            );
+            if input.num_channels() != self.num_input_ch {
+                planar_input.fill(0.0);
+            }
+            deinterleave(&input, &mut planar_input);
+
+            self.inner.process(
+                PlanarAudioMut::from_slice_with_stride(
+                    &mut self.output,
+                    self.num_output_ch,
+                    num_frames,
+                    self.max_frames,
+                ),
+                PlanarAudio::from_slice_with_stride(
+                    &self.input,
+                    self.num_input_ch,
+                    num_frames,
+                    self.max_frames,
+                ),
+            );
+
+            if output.num_channels() != self.num_output_ch {
+                output.data_mut().fill(0.0);
+            }
+            interleave(
+                &PlanarAudio::from_slice_with_stride(
+                    &self.output,
+                    self.num_output_ch,
+                    num_frames,
+                    self.max_frames,
+                ),
+                &mut output,
+            );
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// writes the channel index plus the input of the first channel
+    struct ChannelIndex;
+
+    impl PlanarAudioProcessor for ChannelIndex {
+        type Message = ();
+
+        fn prepare(&mut self, _config: DeviceConfig) {}
+
+        fn message_process(&mut self, _message: Self::Message) {}
+
+        fn process(&mut self, mut output: PlanarAudioMut<'_, f32>, input: PlanarAudio<'_, f32>) {
+            for (index, channel) in output.channels_iter_mut().enumerate() {
+                for (o, i) in channel.iter_mut().zip(input.channel(0)) {
+                    *o = index as f32 + *i;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn processes_planar() {
+        let mut planar = Planar::new(ChannelIndex);
+        planar.prepare(DeviceConfig::new(48000, 2, 2, 1));
+
+        let input = [10.0, 20.0, 30.0];
+        let mut output = [0.0; 6];
+        planar.process(
+            InterleavedAudioMut::from_slice(&mut output, 2),
+            InterleavedAudio::from_slice(&input, 1),
+        );
+        assert_eq!(output, [10.0, 11.0, 20.0, 21.0, 30.0, 31.0]);
+    }
+}
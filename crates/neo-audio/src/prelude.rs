@@ -4,9 +4,20 @@ pub use crate::backends::portaudio_backend::PortAudioBackend;
 #[cfg(feature = "rtaudio-backend")]
 pub use crate::backends::rtaudio_backend::RtAudioBackend;
 pub use crate::backends::AudioBackend;
+#[cfg(feature = "decode")]
+pub use crate::decode::{decode_file, DecodedAudio, FileStream};
 pub use crate::device_config::DeviceConfig;
 pub use crate::device_name::Device;
+pub use crate::engine::BlockSize;
 pub use crate::error::NeoAudioError;
+pub use crate::graph::{AudioGraph, GraphEditor, GraphMessage, NodeId};
+pub use crate::message_queue::{MessageQueueStats, MessageSender, OverflowPolicy};
+pub use crate::planar_processor::{Planar, PlanarAudioProcessor};
+pub use crate::render::{render_offline, RenderInput};
+pub use crate::sample::{Sample, SampleFormat};
 pub use crate::NeoAudio;
 pub use crossbeam_channel::{bounded, Receiver, Sender};
+pub use realtime_tools::audio_buffer::{AudioBuffer, BufferLayout};
+pub use realtime_tools::channel_layout::{ChannelLayout, MixMatrix, Speaker};
 pub use realtime_tools::interleaved_audio::{InterleavedAudio, InterleavedAudioMut};
+pub use realtime_tools::planar_audio::{PlanarAudio, PlanarAudioMut};
@@ -0,0 +1,40 @@
+/// channels a delay is allocated for at least, backends can deliver more channels than a
+/// processor was prepared for
+const MIN_CHANNELS: usize = 32;
+
+/// Delays interleaved audio by a fixed number of frames.
+#[derive(Default)]
+pub(crate) struct FrameDelay {
+    buffer: Vec<f32>,
+    num_frames: usize,
+    max_channels: usize,
+    position: usize,
+}
+
+impl FrameDelay {
+    pub fn new(num_frames: usize, num_channels: usize) -> Self {
+        let max_channels = num_channels.max(MIN_CHANNELS);
+        Self {
+            buffer: vec![0.0; num_frames * max_channels],
+            num_frames,
+            max_channels,
+            position: 0,
+        }
+    }
+
+    /// every channel is delayed on its own, so the number of channels can change between
+    /// calls. channels beyond the allocated ones are not delayed.
+    pub fn process(&mut self, data: &mut [f32], num_channels: usize) {
+        if self.num_frames == 0 || num_channels == 0 {
+            return;
+        }
+        for frame in data.chunks_exact_mut(num_channels) {
+            let start = self.position * self.max_channels;
+            let delayed = &mut self.buffer[start..start + self.max_channels];
+            for (sample, delayed) in frame.iter_mut().zip(delayed) {
+                std::mem::swap(sample, delayed);
+            }
+            self.position = (self.position + 1) % self.num_frames;
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{engine::process_chunked, prelude::*};
+
+pub enum ChainMessage<A, B> {
+    /// First is delivered to the processor that runs first
+    First(A),
+    /// Second is delivered to the processor that runs second
+    Second(B),
+}
+
+/// Runs two processors in series, the output of the first is the input of the second.
+/// Both run with the number of output channels of the device in between.
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+    num_channels: usize,
+    max_frames: usize,
+    scratch: Vec<f32>,
+}
+
+impl<A, B> Chain<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            num_channels: 0,
+            max_frames: 0,
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    pub fn first_mut(&mut self) -> &mut A {
+        &mut self.first
+    }
+
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    pub fn second_mut(&mut self) -> &mut B {
+        &mut self.second
+    }
+}
+
+impl<A, B> AudioProcessor for Chain<A, B>
+where
+    A: AudioProcessor,
+    B: AudioProcessor,
+{
+    type Message = ChainMessage<A::Message, B::Message>;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        self.num_channels = config.num_output_ch as usize;
+        self.max_frames = config.num_frames as usize;
+        self.scratch = vec![0.0; self.num_channels * self.max_frames];
+        self.first.prepare(config.clone());
+        self.second.prepare(DeviceConfig {
+            num_input_ch: config.num_output_ch,
+            input_layout: config.output_layout.clone(),
+            ..config
+        });
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        match message {
+            ChainMessage::First(message) => self.first.message_process(message),
+            ChainMessage::Second(message) => self.second.message_process(message),
+        }
+    }
+
+    fn coalesce_key(message: &Self::Message) -> Option<u64> {
+        match message {
+            ChainMessage::First(message) => A::coalesce_key(message).map(|key| key << 1),
+            ChainMessage::Second(message) => B::coalesce_key(message).map(|key| key << 1 | 1),
+        }
+    }
+
+    fn latency(&self) -> usize {
+        self.first.latency() + self.second.latency()
+    }
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>) {
+        process_chunked(output, input, self.max_frames, |output, input| {
+            let num_ch = self.num_channels;
+            let scratch = &mut self.scratch[..output.num_frames() * num_ch];
+            self.first
+                .process(InterleavedAudioMut::from_slice(scratch, num_ch), input);
+            self.second
+                .process(output, InterleavedAudio::from_slice(scratch, num_ch));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processors::feedback::{FeedbackMessage, FeedbackProcessor};
+
+    #[test]
+    fn runs_in_series() {
+        let mut chain = Chain::new(FeedbackProcessor::default(), FeedbackProcessor::default());
+        chain.prepare(DeviceConfig::new(48000, 2, 2, 2));
+        chain.message_process(ChainMessage::First(FeedbackMessage::Gain(2.0)));
+        chain.message_process(ChainMessage::Second(FeedbackMessage::Gain(3.0)));
+
+        let input = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let mut output = [0.0; 6];
+        chain.process(
+            InterleavedAudioMut::from_slice(&mut output, 2),
+            InterleavedAudio::from_slice(&input, 2),
+        );
+        assert_eq!(output, [6.0, 12.0, 18.0, 24.0, 30.0, 36.0]);
+        assert_ne!(
+            Chain::<FeedbackProcessor, FeedbackProcessor>::coalesce_key(&ChainMessage::First(
+                FeedbackMessage::Gain(1.0)
+            )),
+            Chain::<FeedbackProcessor, FeedbackProcessor>::coalesce_key(&ChainMessage::Second(
+                FeedbackMessage::Gain(1.0)
+            )),
+        );
+    }
+}
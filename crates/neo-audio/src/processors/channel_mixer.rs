@@ -0,0 +1,74 @@
+use realtime_tools::channel_layout::{ChannelLayout, MixMatrix};
+
+use crate::prelude::*;
+
+pub enum ChannelMixerMessage {
+    /// Sets the gain from one input to one output channel, to change the standard mix.
+    /// Channels that don't exist are ignored.
+    Coefficient {
+        output: usize,
+        input: usize,
+        gain: f32,
+    },
+}
+
+/// Mixes the input to the output with the standard ITU matrix, e.g. a 5.1 input to stereo
+/// speakers or a mono microphone to both speakers.
+/// The layouts of the `DeviceConfig` are used, unless they are set on the mixer.
+#[derive(Default)]
+pub struct ChannelMixer {
+    input_layout: Option<ChannelLayout>,
+    output_layout: Option<ChannelLayout>,
+    matrix: MixMatrix,
+}
+
+impl ChannelMixer {
+    /// treats the input as this layout instead of the one of the `DeviceConfig`
+    pub fn set_input_layout(&mut self, layout: ChannelLayout) {
+        self.input_layout = Some(layout);
+    }
+
+    /// treats the output as this layout instead of the one of the `DeviceConfig`
+    pub fn set_output_layout(&mut self, layout: ChannelLayout) {
+        self.output_layout = Some(layout);
+    }
+
+    /// the matrix calculated in `prepare`
+    pub fn matrix(&self) -> &MixMatrix {
+        &self.matrix
+    }
+}
+
+impl AudioProcessor for ChannelMixer {
+    type Message = ChannelMixerMessage;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        let input = self.input_layout.as_ref().unwrap_or(&config.input_layout);
+        let output = self.output_layout.as_ref().unwrap_or(&config.output_layout);
+        self.matrix = MixMatrix::new(input, output);
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        match message {
+            ChannelMixerMessage::Coefficient {
+                output,
+                input,
+                gain,
+            } => {
+                if output < self.matrix.num_outputs() && input < self.matrix.num_inputs() {
+                    self.matrix.set_coefficient(output, input, gain);
+                }
+            }
+        }
+    }
+
+    fn process(
+        &mut self,
+        mut output: InterleavedAudioMut<'_, f32>,
+        input: InterleavedAudio<'_, f32>,
+    ) {
+        // frames without input stay silent
+        output.clear();
+        self.matrix.process(&mut output, &input);
+    }
+}
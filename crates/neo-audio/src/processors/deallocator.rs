@@ -0,0 +1,67 @@
+use std::{
+    sync::{Arc, OnceLock},
+    thread,
+};
+
+use crossbeam_channel::{bounded, Sender};
+use ndarray::Array2;
+
+/// the number of buffers that can wait to be dropped
+const CAPACITY: usize = 256;
+/// the number of buffers a processor can hold back while the thread is behind, by default
+const DEFAULT_OVERFLOW: usize = 16;
+
+/// Drops the audio buffers that the audio thread replaced on a background thread, so the audio
+/// thread never frees memory. All processors share one thread, that is started with the first
+/// deallocator.
+pub(crate) struct Deallocator {
+    sender: Sender<Arc<Array2<f32>>>,
+    // buffers that didn't fit into the channel, sent again by `retry`
+    overflow: Vec<Arc<Array2<f32>>>,
+}
+
+impl Deallocator {
+    pub fn new() -> Self {
+        Self::with_overflow(DEFAULT_OVERFLOW)
+    }
+
+    /// a deallocator that holds back this number of buffers while the background thread is
+    /// behind
+    pub fn with_overflow(overflow: usize) -> Self {
+        static SENDER: OnceLock<Sender<Arc<Array2<f32>>>> = OnceLock::new();
+        let sender = SENDER.get_or_init(|| {
+            let (sender, receiver) = bounded::<Arc<Array2<f32>>>(CAPACITY);
+            thread::Builder::new()
+                .name("neo-audio-deallocator".into())
+                .spawn(move || receiver.iter().for_each(drop))
+                .expect("failed to spawn the deallocator thread");
+            sender
+        });
+        Self {
+            sender: sender.clone(),
+            overflow: Vec::with_capacity(overflow),
+        }
+    }
+
+    /// never blocks. if the background thread is behind, the buffer is held back until `retry`.
+    /// only if the overflow is full as well, the buffer is dropped on the calling thread.
+    pub fn dispose(&mut self, audio: Arc<Array2<f32>>) {
+        self.retry();
+        if let Err(error) = self.sender.try_send(audio) {
+            if self.overflow.len() < self.overflow.capacity() {
+                self.overflow.push(error.into_inner());
+            }
+        }
+    }
+
+    /// sends the buffers that were held back again, called at the start of every `process`
+    pub fn retry(&mut self) {
+        while let Some(audio) = self.overflow.pop() {
+            if let Err(error) = self.sender.try_send(audio) {
+                // the capacity is kept, so this doesn't allocate
+                self.overflow.push(error.into_inner());
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use realtime_tools::{
+    buffer_ops,
+    smooth_value::{Easing, Linear, SmoothValue},
+};
+
+use crate::{
+    engine::{copy_frames, fitting_frames, process_chunked},
+    prelude::*,
+};
+
+use super::buffers::FrameDelay;
+
+/// duration of the crossfades, short enough to feel instant but long enough to not click
+const RAMP_TIME_MS: usize = 20;
+
+pub enum DryWetMessage<M> {
+    /// Mix sets the amount of the processed signal.
+    /// - 0.0 only plays the unprocessed input
+    /// - 1.0 only plays the output of the processor
+    Mix(f32),
+    /// Inner is delivered to the wrapped processor
+    Inner(M),
+}
+
+/// Mixes the output of a processor with its input, which is delayed by the latency of the
+/// processor. Changes of the mix are smoothed.
+pub struct DryWet<P> {
+    inner: P,
+    mix: SmoothValue,
+    max_frames: usize,
+    dry: Vec<f32>,
+    dry_delay: FrameDelay,
+}
+
+impl<P> DryWet<P> {
+    pub fn new(inner: P, mix: f32) -> Self {
+        Self {
+            inner,
+            mix: SmoothValue::new(mix.clamp(0.0, 1.0), Linear::ease_in_out),
+            max_frames: 0,
+            dry: Vec::new(),
+            dry_delay: FrameDelay::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+}
+
+impl<P> AudioProcessor for DryWet<P>
+where
+    P: AudioProcessor,
+{
+    type Message = DryWetMessage<P::Message>;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        prepare_smooth_value(&mut self.mix, &config);
+        self.max_frames = config.num_frames as usize;
+        self.dry = vec![0.0; config.num_output_ch as usize * self.max_frames];
+        self.inner.prepare(config.clone());
+        self.dry_delay = FrameDelay::new(self.inner.latency(), config.num_output_ch as usize);
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        match message {
+            DryWetMessage::Mix(mix) => self.mix.set_target_value(mix.clamp(0.0, 1.0)),
+            DryWetMessage::Inner(message) => self.inner.message_process(message),
+        }
+    }
+
+    fn coalesce_key(message: &Self::Message) -> Option<u64> {
+        match message {
+            DryWetMessage::Mix(_) => Some(0),
+            DryWetMessage::Inner(message) => P::coalesce_key(message).map(|key| key << 1 | 1),
+        }
+    }
+
+    fn latency(&self) -> usize {
+        self.inner.latency()
+    }
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>) {
+        let max_frames = self
+            .max_frames
+            .min(fitting_frames(&self.dry, output.num_channels()));
+        process_chunked(output, input, max_frames, |mut output, input| {
+            let dry = delayed_dry(&mut self.dry, &mut self.dry_delay, &output, &input);
+            let num_ch = output.num_channels();
+            self.inner.process(
+                InterleavedAudioMut::from_slice(output.data_mut(), num_ch),
+                input,
+            );
+            crossfade(output, dry, &mut self.mix);
+        });
+    }
+}
+
+pub enum BypassMessage<M> {
+    /// Bypass fades over to the unprocessed input and stops calling the processor,
+    /// false fades back to the processed signal.
+    Bypass(bool),
+    /// Inner is delivered to the wrapped processor
+    Inner(M),
+}
+
+/// Lets a processor be switched off without clicks. The bypassed signal is delayed by the latency
+/// of the processor, so the latency stays the same in both states.
+pub struct Bypass<P> {
+    inner: P,
+    // 1.0 while the processor is active, 0.0 while it is bypassed
+    active: SmoothValue,
+    max_frames: usize,
+    dry: Vec<f32>,
+    dry_delay: FrameDelay,
+}
+
+impl<P> Bypass<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            active: SmoothValue::new(1.0, Linear::ease_in_out),
+            max_frames: 0,
+            dry: Vec::new(),
+            dry_delay: FrameDelay::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+
+    pub fn is_bypassed(&self) -> bool {
+        self.active.target_value() == 0.0
+    }
+}
+
+impl<P> AudioProcessor for Bypass<P>
+where
+    P: AudioProcessor,
+{
+    type Message = BypassMessage<P::Message>;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        prepare_smooth_value(&mut self.active, &config);
+        self.max_frames = config.num_frames as usize;
+        self.dry = vec![0.0; config.num_output_ch as usize * self.max_frames];
+        self.inner.prepare(config.clone());
+        self.dry_delay = FrameDelay::new(self.inner.latency(), config.num_output_ch as usize);
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        match message {
+            BypassMessage::Bypass(bypass) => {
+                self.active.set_target_value(if bypass { 0.0 } else { 1.0 })
+            }
+            BypassMessage::Inner(message) => self.inner.message_process(message),
+        }
+    }
+
+    fn coalesce_key(message: &Self::Message) -> Option<u64> {
+        match message {
+            BypassMessage::Bypass(_) => Some(0),
+            BypassMessage::Inner(message) => P::coalesce_key(message).map(|key| key << 1 | 1),
+        }
+    }
+
+    fn latency(&self) -> usize {
+        self.inner.latency()
+    }
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>) {
+        let max_frames = self
+            .max_frames
+            .min(fitting_frames(&self.dry, output.num_channels()));
+        process_chunked(output, input, max_frames, |mut output, input| {
+            let dry = delayed_dry(&mut self.dry, &mut self.dry_delay, &output, &input);
+            if self.active.target_value() == 0.0 && !self.active.is_smoothing() {
+                buffer_ops::copy(output.data_mut(), dry);
+                return;
+            }
+            let num_ch = output.num_channels();
+            self.inner.process(
+                InterleavedAudioMut::from_slice(output.data_mut(), num_ch),
+                input,
+            );
+            crossfade(output, dry, &mut self.active);
+        });
+    }
+}
+
+fn prepare_smooth_value(value: &mut SmoothValue, config: &DeviceConfig) {
+    value.prepare(config.sample_rate, RAMP_TIME_MS);
+    value.set_current_and_target_value(value.target_value());
+}
+
+/// copies the input into the dry buffer with the channels of the output and delays it
+fn delayed_dry<'a>(
+    dry: &'a mut [f32],
+    delay: &mut FrameDelay,
+    output: &InterleavedAudioMut<'_, f32>,
+    input: &InterleavedAudio<'_, f32>,
+) -> &'a [f32] {
+    let dry = &mut dry[..output.data().len()];
+    copy_frames(
+        &mut InterleavedAudioMut::from_slice(&mut *dry, output.num_channels()),
+        input,
+    );
+    delay.process(dry, output.num_channels());
+    dry
+}
+
+/// fades from the dry signal at 0.0 to the output at 1.0
+fn crossfade(mut output: InterleavedAudioMut<'_, f32>, dry: &[f32], amount: &mut SmoothValue) {
+    if !amount.is_smoothing() {
+        let wet = amount.target_value();
+        output.apply_gain(wet);
+        buffer_ops::mix_add(output.data_mut(), dry, 1.0 - wet);
+        return;
+    }
+    let num_ch = output.num_channels().max(1);
+    for (out_frame, dry_frame) in output.frames_iter_mut().zip(dry.chunks(num_ch)) {
+        let wet = amount.next_value();
+        out_frame
+            .iter_mut()
+            .zip(dry_frame.iter())
+            .for_each(|(o, d)| *o = *d + (*o - *d) * wet);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        processors::feedback::{FeedbackMessage, FeedbackProcessor},
+        test_utils::{config, process_input},
+    };
+
+    #[test]
+    fn dry_wet_mix_is_smoothed() {
+        let mut dry_wet = DryWet::new(FeedbackProcessor::default(), 0.0);
+        dry_wet.prepare(config(1, 1));
+        dry_wet.message_process(DryWetMessage::Inner(FeedbackMessage::Gain(0.0)));
+        assert_eq!(process_input(&mut dry_wet, &[1.0; 4]), [1.0; 4]);
+
+        // ramps down to the silent processor within 20 frames
+        dry_wet.message_process(DryWetMessage::Mix(1.0));
+        let output = process_input(&mut dry_wet, &[1.0; 32]);
+        assert!(output.windows(2).all(|w| w[1] <= w[0]));
+        assert!(output[0] > 0.9);
+        assert_eq!(output[31], 0.0);
+    }
+
+    #[test]
+    fn bypass_skips_processor() {
+        let mut bypass = Bypass::new(FeedbackProcessor::default());
+        bypass.prepare(config(1, 1));
+        bypass.message_process(BypassMessage::Inner(FeedbackMessage::Gain(0.5)));
+        assert_eq!(process_input(&mut bypass, &[1.0; 4]), [0.5; 4]);
+
+        bypass.message_process(BypassMessage::Bypass(true));
+        let output = process_input(&mut bypass, &[1.0; 32]);
+        assert!(output[0] < 0.6);
+        assert_eq!(output[31], 1.0);
+        assert_eq!(process_input(&mut bypass, &[1.0; 4]), [1.0; 4]);
+    }
+}
@@ -31,6 +31,12 @@ impl AudioProcessor for FeedbackProcessor {
         }
     }
 
+    fn coalesce_key(message: &Self::Message) -> Option<u64> {
+        match message {
+            FeedbackMessage::Gain(_) => Some(0),
+        }
+    }
+
     fn process(
         &mut self,
         mut output: InterleavedAudioMut<'_, f32>,
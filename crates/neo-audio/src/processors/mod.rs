@@ -1,2 +1,12 @@
+mod buffers;
+pub mod chain;
+pub mod channel_mixer;
+mod deallocator;
+pub mod dry_wet;
 pub mod feedback;
+pub mod parallel;
 pub mod player;
+pub mod queue_player;
+pub mod sampler;
+pub mod streaming_player;
+mod time_stretch;
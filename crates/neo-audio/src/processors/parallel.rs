@@ -0,0 +1,195 @@
+use realtime_tools::buffer_ops;
+
+use crate::{
+    engine::{fitting_frames, process_chunked},
+    prelude::*,
+};
+
+use super::buffers::FrameDelay;
+
+pub enum ParallelMessage<A, B> {
+    /// First is delivered to the first processor
+    First(A),
+    /// Second is delivered to the second processor
+    Second(B),
+}
+
+/// Runs two processors with the same input and sums their outputs.
+/// The processor with less latency is delayed, so both outputs stay aligned.
+pub struct Parallel<A, B> {
+    first: A,
+    second: B,
+    max_frames: usize,
+    scratch: Vec<f32>,
+    first_delay: FrameDelay,
+    second_delay: FrameDelay,
+}
+
+impl<A, B> Parallel<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            max_frames: 0,
+            scratch: Vec::new(),
+            first_delay: FrameDelay::default(),
+            second_delay: FrameDelay::default(),
+        }
+    }
+
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    pub fn first_mut(&mut self) -> &mut A {
+        &mut self.first
+    }
+
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    pub fn second_mut(&mut self) -> &mut B {
+        &mut self.second
+    }
+}
+
+impl<A, B> AudioProcessor for Parallel<A, B>
+where
+    A: AudioProcessor,
+    B: AudioProcessor,
+{
+    type Message = ParallelMessage<A::Message, B::Message>;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        let num_ch = config.num_output_ch as usize;
+        self.max_frames = config.num_frames as usize;
+        self.scratch = vec![0.0; num_ch * self.max_frames];
+        self.first.prepare(config.clone());
+        self.second.prepare(config);
+
+        let latency = self.latency();
+        self.first_delay = FrameDelay::new(latency - self.first.latency(), num_ch);
+        self.second_delay = FrameDelay::new(latency - self.second.latency(), num_ch);
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        match message {
+            ParallelMessage::First(message) => self.first.message_process(message),
+            ParallelMessage::Second(message) => self.second.message_process(message),
+        }
+    }
+
+    fn coalesce_key(message: &Self::Message) -> Option<u64> {
+        match message {
+            ParallelMessage::First(message) => A::coalesce_key(message).map(|key| key << 1),
+            ParallelMessage::Second(message) => B::coalesce_key(message).map(|key| key << 1 | 1),
+        }
+    }
+
+    fn latency(&self) -> usize {
+        self.first.latency().max(self.second.latency())
+    }
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>) {
+        let max_frames = self
+            .max_frames
+            .min(fitting_frames(&self.scratch, output.num_channels()));
+        process_chunked(output, input, max_frames, |mut output, input| {
+            let num_ch = output.num_channels();
+            let output = output.data_mut();
+            let scratch = &mut self.scratch[..output.len()];
+
+            self.first.process(
+                InterleavedAudioMut::from_slice(&mut *output, num_ch),
+                input.clone(),
+            );
+            self.second.process(
+                InterleavedAudioMut::from_slice(&mut *scratch, num_ch),
+                input,
+            );
+
+            self.first_delay.process(output, num_ch);
+            self.second_delay.process(scratch, num_ch);
+            buffer_ops::mix_add(output, scratch, 1.0);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processors::feedback::FeedbackProcessor;
+
+    /// delays the audio by one frame
+    #[derive(Default)]
+    struct OneFrameDelay(FrameDelay);
+
+    impl AudioProcessor for OneFrameDelay {
+        type Message = ();
+
+        fn prepare(&mut self, config: DeviceConfig) {
+            self.0 = FrameDelay::new(1, config.num_output_ch as usize);
+        }
+
+        fn message_process(&mut self, _message: Self::Message) {}
+
+        fn latency(&self) -> usize {
+            1
+        }
+
+        fn process(
+            &mut self,
+            mut output: InterleavedAudioMut<'_, f32>,
+            input: InterleavedAudio<'_, f32>,
+        ) {
+            let num_ch = output.num_channels();
+            buffer_ops::copy(output.data_mut(), input.data());
+            self.0.process(output.data_mut(), num_ch);
+        }
+    }
+
+    #[test]
+    fn aligns_latency() {
+        let mut parallel = Parallel::new(FeedbackProcessor::default(), OneFrameDelay::default());
+        parallel.prepare(DeviceConfig::new(48000, 2, 1, 1));
+        assert_eq!(parallel.latency(), 1);
+
+        let input = [1.0, 2.0, 0.0];
+        let mut output = [0.0; 3];
+        parallel.process(
+            InterleavedAudioMut::from_slice(&mut output, 1),
+            InterleavedAudio::from_slice(&input, 1),
+        );
+        assert_eq!(output, [0.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn more_channels_than_prepared() {
+        let mut parallel =
+            Parallel::new(FeedbackProcessor::default(), FeedbackProcessor::default());
+        parallel.prepare(DeviceConfig::new(48000, 2, 1, 1));
+
+        let input = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let mut output = [0.0; 6];
+        parallel.process(
+            InterleavedAudioMut::from_slice(&mut output, 2),
+            InterleavedAudio::from_slice(&input, 2),
+        );
+        assert_eq!(output, [2.0, 4.0, 6.0, 8.0, 10.0, 12.0]);
+    }
+
+    #[test]
+    fn delays_more_channels_than_prepared() {
+        let mut parallel = Parallel::new(FeedbackProcessor::default(), OneFrameDelay::default());
+        parallel.prepare(DeviceConfig::new(48000, 3, 1, 1));
+
+        let input = [1.0, 10.0, 2.0, 20.0, 0.0, 0.0];
+        let mut output = [0.0; 6];
+        parallel.process(
+            InterleavedAudioMut::from_slice(&mut output, 2),
+            InterleavedAudio::from_slice(&input, 2),
+        );
+        assert_eq!(output, [0.0, 0.0, 2.0, 20.0, 4.0, 40.0]);
+    }
+}
@@ -1,115 +1,961 @@
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
 pub use crossbeam_channel::{self, *};
 pub use ndarray::prelude::*;
+use realtime_tools::{
+    channel_layout::{ChannelLayout, MixMatrix},
+    resample::Resampler,
+    smooth_value::{Easing, Linear, SmoothValue},
+};
 
+use super::{deallocator::Deallocator, time_stretch::TimeStretch};
 use crate::prelude::AudioProcessor;
 
+/// the length of the crossfade at the loop seam
+const CROSSFADE_SECONDS: f64 = 0.005;
+/// the number of events that can wait to be received, newer ones are dropped
+const EVENT_CAPACITY: usize = 64;
+/// the default length of the fades of pause, stop and seek, and of gain changes
+const DEFAULT_FADE_MS: usize = 5;
+/// rate changes are ramped slower than the gain, so they sound like turning a knob
+const RATE_RAMP_MS: usize = 50;
+const MIN_RATE: f32 = 0.25;
+const MAX_RATE: f32 = 4.0;
+/// the most channels of audio that can be loaded while the stream runs, enough for 7.1
+pub(crate) const MAX_LOAD_CHANNELS: usize = 8;
+
+/// A position in the audio of the player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayPosition {
+    Frame(usize),
+    /// seconds are converted with the sample rate of the audio, after `prepare` it is the one of
+    /// the device
+    Seconds(f64),
+}
+
+/// The position of the play-head.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerPosition {
+    pub frame: usize,
+    pub seconds: f64,
+}
+
+/// Notifications of the player, received with `PlayerStatus::events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerEvent {
+    Started,
+    /// sent for pause and stop
+    Paused,
+    /// the play-head jumped back to the start of the loop
+    Looped,
+    /// the end of the audio or the play range was reached
+    Finished,
+    /// the audio of a `Load` has no or more than 8 channels and was not loaded
+    LoadRejected,
+}
+
+#[derive(Default)]
+struct Shared {
+    play_head: AtomicUsize,
+    num_frames: AtomicUsize,
+    sample_rate: AtomicU32,
+    playing: AtomicBool,
+}
+
+/// The state of a `PlayerProcessor`, can be cloned and read from any thread, e.g. to draw the
+/// play-head in a UI. The audio thread updates it once per `process` call.
+#[derive(Clone)]
+pub struct PlayerStatus {
+    shared: Arc<Shared>,
+    events: Receiver<PlayerEvent>,
+}
+
+impl PlayerStatus {
+    pub fn position(&self) -> PlayerPosition {
+        let frame = self.shared.play_head.load(Ordering::Relaxed);
+        let sample_rate = self.shared.sample_rate.load(Ordering::Relaxed);
+        PlayerPosition {
+            frame,
+            seconds: frame as f64 / sample_rate.max(1) as f64,
+        }
+    }
+
+    /// the position relative to the length of the audio, from 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        let num_frames = self.shared.num_frames.load(Ordering::Relaxed);
+        self.shared.play_head.load(Ordering::Relaxed) as f32 / num_frames.max(1) as f32
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.shared.playing.load(Ordering::Relaxed)
+    }
+
+    /// the events of the player. every event is received once, by any of the clones.
+    pub fn events(&self) -> &Receiver<PlayerEvent> {
+        &self.events
+    }
+}
+
 pub enum PlayerMessage {
     /// PLay will play the audio once until the end, or until you send pause/stop
     Play,
-    /// PlayLoop will play the audio in a never ending loop, until you send pause or stop
+    /// PlayLoop will play the audio in a never ending loop, until you send pause or stop.
+    /// If a loop region is set, only the region is looped.
     PlayLoop,
     /// Pause will pause the playback, but won't reset the play-head, so the next time you send
-    /// play it will continue to play from the last position
+    /// play it will continue to play from the last position. The playback fades out first and
+    /// fades in again when it continues.
     Pause,
     /// Stop will pause the playback and reset the play-head, so the next time you send play it
-    /// will play from the beginning
+    /// will play from the beginning. The playback fades out first.
     Stop,
     /// Gain will set the gain in linear values.
     /// - 0.0 will turn off the audio completely
     /// - 1.0 will play the audio in original volume
     /// - 2.0 will play the audio 6dB louder
+    ///
+    /// The gain is ramped over the fade time, so changes don't click.
     Gain(f32),
+    /// Seek moves the play-head, it is not limited to the play range. During the playback it
+    /// fades out before the jump and in after it.
+    Seek(PlayPosition),
+    /// SetLoopRegion sets the part of the audio that `PlayLoop` repeats, `None` loops the whole
+    /// play range. The seam is crossfaded over 5 ms with the audio that follows the region, so
+    /// it doesn't click.
+    SetLoopRegion(Option<Range<PlayPosition>>),
+    /// SetPlayRange limits the playback to a part of the audio, `None` plays all of it.
+    /// Stop and the end of the playback return to the start of the range.
+    SetPlayRange(Option<Range<PlayPosition>>),
+    /// Rate sets the speed of the playback, from 0.25 to 4.0. Changes are ramped over 50 ms.
+    Rate(f32),
+    /// RateMode sets if the pitch follows the rate. The playback fades out and in to switch.
+    RateMode(RateMode),
+    /// Load replaces the audio while the stream runs, e.g. to play the next song. The audio has
+    /// to have the sample rate of the device and 1 to 8 channels, other audio is not loaded and
+    /// `PlayerEvent::LoadRejected` is sent.
+    /// The play-head returns to the start, the play range and the loop region are removed and
+    /// the playback continues if it was playing. The previous audio is freed on a background
+    /// thread, not in the audio thread.
+    Load(Arc<Array2<f32>>),
+}
+
+/// How the playback rate changes the audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateMode {
+    /// the audio is resampled, the pitch follows the speed like on a tape machine
+    #[default]
+    Varispeed,
+    /// the pitch stays the same, with a WSOLA time-stretch. the start and the end of the
+    /// playback can be off by up to 20 ms.
+    TimeStretch,
+}
+
+/// what happens when the fade-out of a pause or stop is done
+#[derive(Clone, Copy, PartialEq)]
+enum Halt {
+    Pause,
+    Stop,
 }
 
 pub struct PlayerProcessor {
-    audio: Array2<f32>,
+    audio: Arc<Array2<f32>>,
+    sample_rate: Option<u32>,
+    // the audio of `set_audio_with_sample_rate` and its rate, it is converted from the original
+    // when the rate of the device changes
+    original: Option<(Arc<Array2<f32>>, u32)>,
+    layout: Option<ChannelLayout>,
+    matrix: MixMatrix,
+    // the matrices for the other numbers of channels that can be loaded, prepared in advance
+    load_matrices: Vec<MixMatrix>,
+    deallocator: Deallocator,
+    device_sample_rate: u32,
     play_head: usize,
+    // the position between the play-head and the next frame, at rates other than 1.0
+    phase: f64,
+    rate: SmoothValue,
+    rate_mode: RateMode,
+    stretch: TimeStretch,
+    // one frame of the audio, read by the rate mode
+    source_frame: Vec<f32>,
+    tail_frame: Vec<f32>,
     play: bool,
     looped: bool,
-    progress_sender: Option<Sender<f32>>,
-    gain: f32,
+    play_range: Option<Range<usize>>,
+    loop_region: Option<Range<usize>>,
+    crossfade_frames: usize,
+    // the play-head of the audio that is faded out after the loop seam, and the frames it has
+    // already been faded
+    crossfade: Option<(usize, usize)>,
+    shared: Arc<Shared>,
+    events: Sender<PlayerEvent>,
+    event_receiver: Receiver<PlayerEvent>,
+    gain: SmoothValue,
+    // fades the playback out and in around pause, stop and seek
+    fade: SmoothValue,
+    fade_ms: usize,
+    // applied when the fade-out is done
+    pending_seek: Option<usize>,
+    pending_halt: Option<Halt>,
+    pending_rate_mode: Option<RateMode>,
 }
 
 impl Default for PlayerProcessor {
     fn default() -> Self {
+        let (events, event_receiver) = bounded(EVENT_CAPACITY);
         Self {
-            audio: Array2::zeros((0, 0)),
+            audio: Arc::new(Array2::zeros((0, 0))),
+            sample_rate: None,
+            original: None,
+            layout: None,
+            matrix: MixMatrix::default(),
+            load_matrices: Vec::new(),
+            deallocator: Deallocator::new(),
+            device_sample_rate: 0,
             play_head: 0,
+            phase: 0.0,
+            rate: SmoothValue::new(1.0, Linear::ease_in_out),
+            rate_mode: RateMode::Varispeed,
+            stretch: TimeStretch::default(),
+            source_frame: Vec::new(),
+            tail_frame: Vec::new(),
             play: false,
             looped: false,
-            progress_sender: None,
-            gain: 1.0,
+            play_range: None,
+            loop_region: None,
+            crossfade_frames: 1,
+            crossfade: None,
+            shared: Arc::default(),
+            events,
+            event_receiver,
+            gain: SmoothValue::new(1.0, Linear::ease_in_out),
+            fade: SmoothValue::new(0.0, Linear::ease_in_out),
+            fade_ms: DEFAULT_FADE_MS,
+            pending_seek: None,
+            pending_halt: None,
+            pending_rate_mode: None,
         }
     }
 }
 
 impl PlayerProcessor {
+    /// the audio is played with the sample rate of the device
     pub fn set_audio(&mut self, audio: impl Into<Array2<f32>>) {
-        self.audio = audio.into();
+        self.audio = Arc::new(audio.into());
+        self.sample_rate = None;
+        self.original = None;
+        self.update_status();
+    }
+
+    /// the audio is converted to the sample rate of the device right away if the player was
+    /// prepared before, else in `prepare`. the original is kept, so it is only converted again
+    /// if the rate of the device changes.
+    pub fn set_audio_with_sample_rate(&mut self, audio: impl Into<Array2<f32>>, sample_rate: u32) {
+        let audio = Arc::new(audio.into());
+        self.original = Some((audio.clone(), sample_rate));
+        if self.device_sample_rate > 0 {
+            self.audio = convert_rate(&audio, sample_rate, self.device_sample_rate);
+            self.sample_rate = Some(self.device_sample_rate);
+        } else {
+            self.audio = audio;
+            self.sample_rate = Some(sample_rate);
+        }
+        self.update_status();
+    }
+
+    /// the sample rate of the audio, after `prepare` it is the one of the device
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    /// the speakers of the rows of the audio, by default the common layout for the number of
+    /// rows is used. the audio is mixed to the output layout in `prepare`.
+    pub fn set_channel_layout(&mut self, layout: ChannelLayout) {
+        self.layout = Some(layout);
+    }
+
+    /// the length of the fades when pausing, stopping or seeking during the playback, and of
+    /// gain changes. 5 ms by default, 0 switches instantly. applied in `prepare`.
+    pub fn set_fade_time(&mut self, milliseconds: usize) {
+        self.fade_ms = milliseconds;
+    }
+
+    /// the position and the events of the player, for other threads
+    pub fn status(&self) -> PlayerStatus {
+        PlayerStatus {
+            shared: self.shared.clone(),
+            events: self.event_receiver.clone(),
+        }
+    }
+
+    pub fn position(&self) -> PlayerPosition {
+        let sample_rate = self.sample_rate.unwrap_or(self.device_sample_rate);
+        PlayerPosition {
+            frame: self.play_head,
+            seconds: self.play_head as f64 / sample_rate.max(1) as f64,
+        }
+    }
+}
+
+impl PlayerProcessor {
+    /// converts the audio to the sample rate, if it has another one
+    fn resample(&mut self, to: u32) {
+        let Some(from) = self.sample_rate.filter(|from| *from != to && to > 0) else {
+            return;
+        };
+        let audio = match &self.original {
+            Some((original, rate)) => convert_rate(original, *rate, to),
+            None => convert_rate(&self.audio, from, to),
+        };
+        let convert = |frame: usize| (frame as u64 * to as u64 / from as u64) as usize;
+        self.play_head = convert(self.play_head);
+        for range in [&mut self.play_range, &mut self.loop_region]
+            .into_iter()
+            .flatten()
+        {
+            *range = convert(range.start)..convert(range.end);
+        }
+        self.audio = audio;
+        self.sample_rate = Some(to);
     }
+}
 
-    pub fn set_progress_sender(&mut self, sender: Sender<f32>) {
-        self.progress_sender = Some(sender);
+/// sends an event of a player to its status. never blocks, the event is dropped if nobody
+/// receives the events.
+pub(crate) fn notify<E>(events: &Sender<E>, event: E) {
+    let _ = events.try_send(event);
+}
+
+/// the audio in another sample rate, the same audio if the rates are the same
+pub(crate) fn convert_rate(audio: &Arc<Array2<f32>>, from: u32, to: u32) -> Arc<Array2<f32>> {
+    if from == to {
+        audio.clone()
+    } else {
+        Arc::new(resample_audio(audio, from, to))
+    }
+}
+
+/// converts every row of the audio to another sample rate
+pub(crate) fn resample_audio(audio: &Array2<f32>, from: u32, to: u32) -> Array2<f32> {
+    let resampler = Resampler::new(from, to);
+    let num_frames = resampler.output_len(audio.ncols());
+    let mut resampled = Array2::zeros((audio.nrows(), num_frames));
+    for (input, mut output) in audio.rows().into_iter().zip(resampled.rows_mut()) {
+        let input = input.to_vec();
+        if let Some(output) = output.as_slice_mut() {
+            resampler.process(output, &input);
+        }
     }
+    resampled
 }
 
 impl AudioProcessor for PlayerProcessor {
     type Message = PlayerMessage;
 
-    fn prepare(&mut self, _config: crate::prelude::DeviceConfig) {}
+    fn prepare(&mut self, config: crate::prelude::DeviceConfig) {
+        let layout = self
+            .layout
+            .clone()
+            .unwrap_or_else(|| ChannelLayout::from_num_channels(self.audio.nrows()));
+        self.matrix = MixMatrix::new(&layout, &config.output_layout);
+        self.load_matrices = (1..=MAX_LOAD_CHANNELS)
+            .filter(|num_channels| *num_channels != self.matrix.num_inputs())
+            .map(|num_channels| {
+                let layout = ChannelLayout::from_num_channels(num_channels);
+                MixMatrix::new(&layout, &config.output_layout)
+            })
+            .collect();
+
+        self.resample(config.sample_rate);
+        self.device_sample_rate = config.sample_rate;
+        self.crossfade_frames = ((config.sample_rate as f64 * CROSSFADE_SECONDS) as usize).max(1);
+        for value in [&mut self.gain, &mut self.fade] {
+            value.prepare(config.sample_rate, self.fade_ms);
+            value.set_current_and_target_value(value.target_value());
+        }
+        self.rate.prepare(config.sample_rate, RATE_RAMP_MS);
+        self.rate
+            .set_current_and_target_value(self.rate.target_value());
+        // loaded audio can have more channels
+        let max_channels = self.audio.nrows().max(MAX_LOAD_CHANNELS);
+        self.stretch = TimeStretch::new(config.sample_rate, max_channels);
+        self.source_frame = vec![0.0; max_channels];
+        self.tail_frame = vec![0.0; max_channels];
+        self.update_status();
+    }
 
     fn message_process(&mut self, message: Self::Message) {
         match message {
             PlayerMessage::Play => {
-                self.play = true;
+                self.start(false);
             }
             PlayerMessage::PlayLoop => {
-                self.play = true;
-                self.looped = true;
+                self.start(true);
             }
             PlayerMessage::Pause => {
-                self.play = false;
+                self.halt(Halt::Pause);
             }
             PlayerMessage::Stop => {
-                self.play = false;
-                self.play_head = 0;
+                self.halt(Halt::Stop);
             }
             PlayerMessage::Gain(gain) => {
-                self.gain = gain;
+                self.gain.set_target_value(gain);
+            }
+            PlayerMessage::Seek(position) => {
+                let frame = self.to_frame(position);
+                if self.play && self.fade_ms > 0 {
+                    self.pending_seek = Some(frame);
+                    self.fade.set_target_value(0.0);
+                } else {
+                    self.seek(frame);
+                }
+            }
+            PlayerMessage::SetLoopRegion(region) => {
+                self.loop_region = region.map(|r| self.to_frame(r.start)..self.to_frame(r.end));
+            }
+            PlayerMessage::SetPlayRange(range) => {
+                self.play_range = range.map(|r| self.to_frame(r.start)..self.to_frame(r.end));
+            }
+            PlayerMessage::Rate(rate) => {
+                self.rate.set_target_value(rate.clamp(MIN_RATE, MAX_RATE));
+            }
+            PlayerMessage::RateMode(mode) => {
+                if self.play && self.fade_ms > 0 {
+                    self.pending_rate_mode = Some(mode);
+                    self.fade.set_target_value(0.0);
+                } else {
+                    self.set_rate_mode(mode);
+                }
+            }
+            PlayerMessage::Load(audio) => {
+                self.load(audio);
             }
         }
     }
 
+    fn coalesce_key(message: &Self::Message) -> Option<u64> {
+        match message {
+            PlayerMessage::Gain(_) => Some(0),
+            PlayerMessage::Seek(_) => Some(1),
+            PlayerMessage::SetLoopRegion(_) => Some(2),
+            PlayerMessage::SetPlayRange(_) => Some(3),
+            PlayerMessage::Rate(_) => Some(4),
+            PlayerMessage::RateMode(_) => Some(5),
+            _ => None,
+        }
+    }
+
     fn process(
         &mut self,
         mut output: realtime_tools::interleaved_audio::InterleavedAudioMut<'_, f32>,
         _input: realtime_tools::interleaved_audio::InterleavedAudio<'_, f32>,
     ) {
-        let play_ch = output.num_channels().min(self.audio.nrows());
+        self.deallocator.retry();
+        output.clear();
         for frame in output.frames_iter_mut() {
-            if self.play && self.play_head < self.audio.ncols() {
-                #[allow(clippy::needless_range_loop)]
-                for ch in 0..play_ch {
-                    frame[ch] = self.audio[[ch, self.play_head]] * self.gain;
+            if !self.play {
+                continue;
+            }
+            let pending = self.pending_seek.is_some()
+                || self.pending_halt.is_some()
+                || self.pending_rate_mode.is_some();
+            if pending && !self.fade.is_smoothing() {
+                self.apply_pending();
+                if !self.play {
+                    continue;
                 }
-                self.play_head += 1;
+            }
 
-                // send progress non-blocking if sender is set
-                if let Some(sender) = self.progress_sender.as_ref() {
-                    sender
-                        .try_send(self.play_head as f32 / self.audio.ncols() as f32)
-                        .unwrap_or_default(); // ignore send error
+            let range = self.active_range();
+            if self.play_head >= range.end {
+                if self.looped && !range.is_empty() {
+                    // the grains of the time-stretch overlap at the seam anyway
+                    if self.rate_mode == RateMode::Varispeed {
+                        self.crossfade = Some((self.play_head, 0));
+                    }
+                    self.play_head = range.start;
+                    notify(&self.events, PlayerEvent::Looped);
+                } else {
+                    // the audio was completely played, the next play starts from the beginning
+                    self.set_playing(false);
+                    notify(&self.events, PlayerEvent::Finished);
+                    self.play_head = self.play_range().start;
+                    self.crossfade = None;
+                    self.fade.set_current_and_target_value(0.0);
+                    continue;
+                }
+            }
+
+            let gain = self.gain.next_value() * self.fade.next_value();
+            let num_channels = self.audio.nrows();
+            let source = &mut self.source_frame[..num_channels];
+            match (self.rate_mode, self.crossfade) {
+                (RateMode::TimeStretch, _) => {
+                    self.stretch.next_frame(&self.audio, self.play_head, source);
+                    mix(&self.matrix, frame, source, gain);
+                }
+                (RateMode::Varispeed, Some((tail, faded))) => {
+                    // equal power, because the audio before and after the seam is not correlated
+                    let position = (faded + 1) as f32 / (self.crossfade_frames + 1) as f32;
+                    let angle = position * std::f32::consts::FRAC_PI_2;
+                    read_frame(&self.audio, self.play_head, self.phase, source);
+                    mix(&self.matrix, frame, source, gain * angle.sin());
+                    let source = &mut self.tail_frame[..num_channels];
+                    read_frame(&self.audio, tail, self.phase, source);
+                    mix(&self.matrix, frame, source, gain * angle.cos());
+                    self.crossfade =
+                        (faded + 1 < self.crossfade_frames).then_some((tail, faded + 1));
+                }
+                (RateMode::Varispeed, None) => {
+                    read_frame(&self.audio, self.play_head, self.phase, source);
+                    mix(&self.matrix, frame, source, gain);
                 }
+            }
+
+            self.phase += self.rate.next_value() as f64;
+            let step = self.phase.floor();
+            self.phase -= step;
+            self.play_head += step as usize;
+            if let Some((tail, _)) = &mut self.crossfade {
+                *tail += step as usize;
+            }
+        }
+        self.shared
+            .play_head
+            .store(self.play_head, Ordering::Relaxed);
+    }
+}
+
+impl PlayerProcessor {
+    /// the frames until the end of the play range, while playing
+    pub(crate) fn remaining_frames(&self) -> usize {
+        if !self.play {
+            return 0;
+        }
+        self.active_range().end.saturating_sub(self.play_head)
+    }
+
+    /// the length of the fades of pause, stop and seek in frames
+    pub(crate) fn fade_frames(&self) -> usize {
+        self.fade_ms * self.device_sample_rate as usize / 1000
+    }
+
+    fn load(&mut self, audio: Arc<Array2<f32>>) {
+        let num_channels = audio.nrows();
+        if num_channels != self.matrix.num_inputs() {
+            // the matrices are swapped, so there is still one for every number of channels
+            match self
+                .load_matrices
+                .iter_mut()
+                .find(|matrix| matrix.num_inputs() == num_channels)
+            {
+                Some(matrix) => std::mem::swap(&mut self.matrix, matrix),
+                None => {
+                    self.deallocator.dispose(audio);
+                    notify(&self.events, PlayerEvent::LoadRejected);
+                    return;
+                }
+            }
+        }
+
+        // the audio that was fading out is gone
+        if let Some(halt) = self.pending_halt.take() {
+            self.apply_halt(halt);
+        }
+        let previous = std::mem::replace(&mut self.audio, audio);
+        self.deallocator.dispose(previous);
+        if let Some((original, _)) = self.original.take() {
+            self.deallocator.dispose(original);
+        }
+        self.sample_rate = None;
+        self.pending_seek = None;
+        self.play_head = 0;
+        self.phase = 0.0;
+        self.stretch.reset();
+        self.play_range = None;
+        self.loop_region = None;
+        self.crossfade = None;
+        self.update_status();
+    }
+
+    fn start(&mut self, looped: bool) {
+        if !self.play {
+            notify(&self.events, PlayerEvent::Started);
+            self.stretch.reset();
+        }
+        self.set_playing(true);
+        self.looped = looped;
+        // a stop that is still fading out returns to the start before playing again
+        if self.pending_halt.take() == Some(Halt::Stop) {
+            self.pending_seek = Some(self.play_range().start);
+        }
+        if !self.play_range().contains(&self.play_head) {
+            self.play_head = self.play_range().start;
+        }
+        // a pending seek fades in when it is done
+        if self.pending_seek.is_none() {
+            if self.play_head == self.play_range().start || self.fade_ms == 0 {
+                self.fade.set_current_and_target_value(1.0);
             } else {
-                frame.fill(0.0);
+                // starting in the middle of a waveform would click
+                self.fade.set_target_value(1.0);
+            }
+        }
+    }
+
+    /// pauses or stops after a fade-out, or instantly if nothing is played
+    fn halt(&mut self, halt: Halt) {
+        if self.play && self.fade_ms > 0 {
+            if self.pending_halt != Some(Halt::Stop) {
+                self.pending_halt = Some(halt);
+            }
+            self.fade.set_target_value(0.0);
+        } else {
+            self.apply_halt(halt);
+        }
+    }
+
+    fn apply_halt(&mut self, halt: Halt) {
+        if self.play {
+            notify(&self.events, PlayerEvent::Paused);
+        }
+        self.set_playing(false);
+        self.fade.set_current_and_target_value(0.0);
+        if halt == Halt::Stop {
+            self.play_head = self.play_range().start;
+            self.crossfade = None;
+        }
+    }
+
+    /// called when the fade-out is done
+    fn apply_pending(&mut self) {
+        if let Some(frame) = self.pending_seek.take() {
+            self.seek(frame);
+        }
+        if let Some(mode) = self.pending_rate_mode.take() {
+            self.set_rate_mode(mode);
+        }
+        match self.pending_halt.take() {
+            Some(halt) => self.apply_halt(halt),
+            None => self.fade.set_target_value(1.0),
+        }
+    }
+
+    fn seek(&mut self, frame: usize) {
+        self.play_head = frame.min(self.audio.ncols());
+        self.phase = 0.0;
+        self.crossfade = None;
+        self.stretch.reset();
+        self.shared
+            .play_head
+            .store(self.play_head, Ordering::Relaxed);
+    }
+
+    fn set_rate_mode(&mut self, mode: RateMode) {
+        self.rate_mode = mode;
+        self.crossfade = None;
+        self.stretch.reset();
+    }
+
+    fn set_playing(&mut self, play: bool) {
+        self.play = play;
+        self.shared.playing.store(play, Ordering::Relaxed);
+    }
+
+    /// publishes what changes outside of `process`
+    fn update_status(&self) {
+        let sample_rate = self.sample_rate.unwrap_or(self.device_sample_rate);
+        self.shared
+            .sample_rate
+            .store(sample_rate, Ordering::Relaxed);
+        self.shared
+            .num_frames
+            .store(self.audio.ncols(), Ordering::Relaxed);
+        self.shared
+            .play_head
+            .store(self.play_head, Ordering::Relaxed);
+    }
+
+    fn to_frame(&self, position: PlayPosition) -> usize {
+        match position {
+            PlayPosition::Frame(frame) => frame,
+            PlayPosition::Seconds(seconds) => {
+                let sample_rate = self.sample_rate.unwrap_or(self.device_sample_rate);
+                (seconds.max(0.0) * sample_rate as f64).round() as usize
             }
         }
+    }
 
-        // if audio was completely played, reset playhead
-        // and stop audio if audio should not be looped
-        if self.play_head >= self.audio.ncols() {
-            self.play_head = 0;
-            self.play = self.looped;
+    /// the part of the audio that is played, limited to the length of the audio
+    fn play_range(&self) -> Range<usize> {
+        let len = self.audio.ncols();
+        match &self.play_range {
+            Some(range) => range.start.min(len)..range.end.min(len),
+            None => 0..len,
         }
     }
+
+    /// the part of the audio that is played or looped
+    fn active_range(&self) -> Range<usize> {
+        match (&self.loop_region, self.looped) {
+            (Some(region), true) => {
+                let len = self.audio.ncols();
+                region.start.min(len)..region.end.min(len)
+            }
+            _ => self.play_range(),
+        }
+    }
+}
+
+/// adds one frame of the audio, mixed to the output layout
+fn mix(matrix: &MixMatrix, frame: &mut [f32], source: &[f32], gain: f32) {
+    for (sample, row) in frame.iter_mut().zip(matrix.rows()) {
+        let mixed = row.iter().zip(source).map(|(g, s)| g * s).sum::<f32>();
+        *sample += mixed * gain;
+    }
+}
+
+/// reads the audio between two frames with a cubic hermite interpolation, silent behind the end.
+/// a phase of 0.0 reads the frame exactly.
+fn read_frame(audio: &Array2<f32>, index: usize, phase: f64, frame: &mut [f32]) {
+    let t = phase as f32;
+    for (sample, row) in frame.iter_mut().zip(audio.rows()) {
+        let at = |offset: usize| {
+            (index + offset)
+                .checked_sub(1)
+                .and_then(|i| row.get(i))
+                .copied()
+                .unwrap_or(0.0)
+        };
+        let (y0, y1, y2, y3) = (at(0), at(1), at(2), at(3));
+        let c1 = 0.5 * (y2 - y0);
+        let c2 = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+        let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+        *sample = ((c3 * t + c2) * t + c1) * t + y1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::*,
+        test_utils::{config, process},
+    };
+
+    /// a mono clip where every sample is its frame index
+    fn ramp_player(len: usize) -> PlayerProcessor {
+        let mut player = PlayerProcessor::default();
+        player.set_audio(Array2::from_shape_fn((1, len), |(_, n)| n as f32));
+        // the exact samples are checked, without fades
+        player.set_fade_time(0);
+        player.prepare(config(1, 0));
+        player
+    }
+
+    #[test]
+    fn resample_to_device_rate() {
+        let mut player = PlayerProcessor::default();
+        player.set_audio_with_sample_rate(Array2::ones((1, 441)), 44100);
+        player.prepare(DeviceConfig {
+            sample_rate: 48000,
+            ..config(1, 0)
+        });
+        assert_eq!(player.sample_rate(), Some(48000));
+
+        player.message_process(PlayerMessage::Play);
+        let output = process(&mut player, 512);
+        // the clip plays for 10 ms at the new rate
+        assert!((output[240] - 1.0).abs() < 1e-3);
+        assert!(output[480..].iter().all(|sample| sample.abs() < 1e-3));
+
+        // after `prepare` the audio is converted right away, and not again for the same rate
+        player.set_audio_with_sample_rate(Array2::ones((1, 441)), 44100);
+        assert_eq!(player.sample_rate(), Some(48000));
+        let converted = player.audio.clone();
+        player.prepare(DeviceConfig {
+            sample_rate: 48000,
+            ..config(1, 0)
+        });
+        assert!(Arc::ptr_eq(&player.audio, &converted));
+    }
+
+    #[test]
+    fn seek_and_play_range() {
+        let mut player = ramp_player(100);
+        player.message_process(PlayerMessage::SetPlayRange(Some(
+            PlayPosition::Frame(10)..PlayPosition::Seconds(0.02),
+        )));
+        player.message_process(PlayerMessage::Play);
+        let output = process(&mut player, 12);
+        assert_eq!(output[..3], [10.0, 11.0, 12.0]);
+        assert_eq!(output[9..], [19.0, 0.0, 0.0]);
+        assert_eq!(player.position().frame, 10);
+
+        player.message_process(PlayerMessage::Seek(PlayPosition::Seconds(0.015)));
+        assert_eq!(
+            player.position(),
+            PlayerPosition {
+                frame: 15,
+                seconds: 0.015
+            }
+        );
+        player.message_process(PlayerMessage::Play);
+        assert_eq!(process(&mut player, 2), [15.0, 16.0]);
+    }
+
+    #[test]
+    fn loop_region() {
+        let mut player = ramp_player(100);
+        player.message_process(PlayerMessage::SetLoopRegion(Some(
+            PlayPosition::Frame(10)..PlayPosition::Frame(20),
+        )));
+        player.message_process(PlayerMessage::Seek(PlayPosition::Frame(15)));
+        player.message_process(PlayerMessage::PlayLoop);
+        let output = process(&mut player, 40);
+        assert_eq!(output[..5], [15.0, 16.0, 17.0, 18.0, 19.0]);
+
+        // the seam is crossfaded over 5 frames with the audio after the region
+        let seam = &output[5..10];
+        assert!(
+            seam.windows(2).all(|w| (w[1] - w[0]).abs() < 6.0),
+            "{seam:?}"
+        );
+        assert_eq!(output[10..15], [15.0, 16.0, 17.0, 18.0, 19.0]);
+
+        // play turns the loop back into a one-shot playback until the end of the audio
+        player.message_process(PlayerMessage::Play);
+        let output = process(&mut player, 100);
+        assert_eq!(output[..2], [15.0, 16.0]);
+        assert_eq!(output[84], 99.0);
+        assert_eq!(output[85], 0.0);
+        assert!(!player.play);
+    }
+
+    #[test]
+    fn status_and_events() {
+        let mut player = ramp_player(10);
+        let status = player.status();
+        player.message_process(PlayerMessage::PlayLoop);
+        process(&mut player, 15);
+        assert!(status.is_playing());
+        assert_eq!(status.position().frame, 5);
+        assert_eq!(status.progress(), 0.5);
+
+        player.message_process(PlayerMessage::Play);
+        process(&mut player, 10);
+        assert!(!status.is_playing());
+        assert_eq!(status.position().frame, 0);
+
+        let events = status.events().try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                PlayerEvent::Started,
+                PlayerEvent::Looped,
+                PlayerEvent::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn load_while_playing() {
+        let mut player = ramp_player(10);
+        let previous = Arc::downgrade(&player.audio);
+        player.message_process(PlayerMessage::Play);
+        process(&mut player, 4);
+
+        let stereo = Arc::new(Array2::from_elem((2, 3), 0.5));
+        player.message_process(PlayerMessage::Load(stereo));
+        // the stereo audio is mixed to the mono output at -3 dB
+        let mixed = std::f32::consts::FRAC_1_SQRT_2;
+        assert_eq!(process(&mut player, 4), [mixed, mixed, mixed, 0.0]);
+
+        // the previous audio is freed by the deallocator thread
+        let start = std::time::Instant::now();
+        while previous.upgrade().is_some() {
+            assert!(start.elapsed().as_secs() < 1);
+            std::thread::yield_now();
+        }
+
+        // too many channels are not loaded
+        let status = player.status();
+        player.message_process(PlayerMessage::Load(Arc::new(Array2::ones((9, 3)))));
+        assert_eq!(player.audio.nrows(), 2);
+        assert_eq!(
+            status.events().try_iter().last(),
+            Some(PlayerEvent::LoadRejected)
+        );
+    }
+
+    #[test]
+    fn fades() {
+        let mut player = PlayerProcessor::default();
+        player.set_audio(Array2::ones((1, 100)));
+        player.prepare(config(1, 0));
+        let status = player.status();
+
+        // the start of the audio is not faded in
+        player.message_process(PlayerMessage::Play);
+        assert_eq!(process(&mut player, 2), [1.0, 1.0]);
+
+        // pause fades out over 5 ms and pauses when it is silent
+        player.message_process(PlayerMessage::Pause);
+        let output = process(&mut player, 8);
+        assert!(output.windows(2).all(|w| w[1] < w[0] || w[1] == 0.0));
+        assert_eq!(output[6..], [0.0, 0.0]);
+        assert!(!status.is_playing());
+        assert_eq!(player.position().frame, 8);
+
+        // play continues in the middle of the audio and fades in
+        player.message_process(PlayerMessage::Play);
+        let output = process(&mut player, 8);
+        assert_eq!(output[0], 0.0);
+        assert!(output.windows(2).all(|w| w[1] > w[0] || w[1] == 1.0));
+        assert_eq!(output[7], 1.0);
+
+        // seek fades out, jumps and fades back in
+        player.message_process(PlayerMessage::Seek(PlayPosition::Frame(50)));
+        let output = process(&mut player, 14);
+        assert_eq!(output[6], 0.0);
+        assert_eq!(output[13], 1.0);
+        assert_eq!(player.position().frame, 58);
+
+        // gain changes are ramped
+        player.message_process(PlayerMessage::Gain(0.0));
+        let output = process(&mut player, 8);
+        assert!(output[1] > 0.0 && output[1] < 1.0);
+        assert_eq!(output[7], 0.0);
+    }
+
+    #[test]
+    fn playback_rate() {
+        let mut player = ramp_player(1000);
+        player.message_process(PlayerMessage::Rate(2.0));
+        player.message_process(PlayerMessage::Play);
+
+        // the rate is ramped, then the ramp is read at every second frame
+        let output = process(&mut player, 100);
+        assert!(output[10] > 10.0 && output[10] < 20.0);
+        assert_eq!(output[99] - output[98], 2.0);
+
+        // a phase between frames is interpolated
+        player.message_process(PlayerMessage::Rate(0.5));
+        let output = process(&mut player, 100);
+        assert!((output[99] - output[98] - 0.5).abs() < 1e-3);
+
+        // the time-stretch moves through the audio at the same rate
+        player.message_process(PlayerMessage::RateMode(RateMode::TimeStretch));
+        let start = player.position().frame;
+        let output = process(&mut player, 200);
+        assert_eq!(player.position().frame - start, 100);
+        // the audio that is heard follows, up to half a grain apart
+        let advanced = output[199] - output[0];
+        assert!((90.0..130.0).contains(&advanced), "{advanced}");
+    }
 }
@@ -0,0 +1,723 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+use ndarray::Array2;
+
+use super::{
+    deallocator::Deallocator,
+    player::{convert_rate, notify, PlayerMessage, PlayerProcessor, MAX_LOAD_CHANNELS},
+};
+use crate::{
+    engine::{fitting_frames, process_chunked},
+    prelude::*,
+};
+
+/// the number of clips the queue can hold without allocating in the audio thread
+const DEFAULT_CAPACITY: usize = 256;
+/// the number of events that can wait to be received, newer ones are dropped
+const EVENT_CAPACITY: usize = 64;
+const NO_TRACK: usize = usize::MAX;
+
+/// What happens at the end of the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Repeat {
+    /// the playback stops after the last track
+    #[default]
+    Off,
+    /// the queue starts again with the first track
+    All,
+    /// the current track is repeated, `Next` and `Previous` still change the track
+    One,
+}
+
+pub enum QueuePlayerMessage {
+    /// Play starts the current track, or the first one if the queue was finished
+    Play,
+    Pause,
+    /// Stop pauses and returns to the start of the current track
+    Stop,
+    Next,
+    /// Previous goes to the track before the current one in the play order
+    Previous,
+    /// PlayTrack jumps to the track with this index in the queue
+    PlayTrack(usize),
+    /// Enqueue adds a clip to the end of the queue, or at a random position after the current
+    /// track while shuffling. The clip has to have the sample rate of the device and 1 to 8
+    /// channels. Other clips and clips beyond the capacity of the queue are not added and
+    /// `QueuePlayerEvent::EnqueueRejected` is sent.
+    Enqueue(Arc<Array2<f32>>),
+    /// Clear stops the playback and removes all clips
+    Clear,
+    /// Shuffle plays the tracks after the current one in a random order, false returns to the
+    /// order of the queue
+    Shuffle(bool),
+    Repeat(Repeat),
+    /// Crossfade sets the length of the crossfade between tracks in seconds, 0.0 plays them
+    /// gapless one after the other
+    Crossfade(f64),
+    /// Gain sets the gain in linear values
+    Gain(f32),
+}
+
+/// Notifications of the queue player, received with `QueuePlayerStatus::events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuePlayerEvent {
+    /// a track with this index in the queue started, sent at the start of a crossfade
+    TrackChanged(usize),
+    /// the last track was played and nothing is repeated
+    Finished,
+    /// a clip was not added, because it has no or more than 8 channels or the queue is full
+    EnqueueRejected,
+}
+
+#[derive(Default)]
+struct Shared {
+    track: AtomicUsize,
+    num_tracks: AtomicUsize,
+    playing: AtomicBool,
+}
+
+/// The state of a `QueuePlayerProcessor`, can be cloned and read from any thread.
+#[derive(Clone)]
+pub struct QueuePlayerStatus {
+    shared: Arc<Shared>,
+    events: Receiver<QueuePlayerEvent>,
+}
+
+impl QueuePlayerStatus {
+    /// the index of the current track in the queue
+    pub fn current_track(&self) -> Option<usize> {
+        let track = self.shared.track.load(Ordering::Relaxed);
+        (track != NO_TRACK).then_some(track)
+    }
+
+    pub fn num_tracks(&self) -> usize {
+        self.shared.num_tracks.load(Ordering::Relaxed)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.shared.playing.load(Ordering::Relaxed)
+    }
+
+    /// the events of the player. every event is received once, by any of the clones.
+    pub fn events(&self) -> &Receiver<QueuePlayerEvent> {
+        &self.events
+    }
+}
+
+struct Clip {
+    audio: Arc<Array2<f32>>,
+    // the pushed audio and its sample rate, if it is converted to the rate of the device
+    original: Option<(Arc<Array2<f32>>, u32)>,
+}
+
+/// Plays a queue of clips one after the other, without gaps or with a crossfade. The tracks
+/// are played by two `PlayerProcessor`s, the next one starts while the current one ends.
+pub struct QueuePlayerProcessor {
+    clips: Vec<Clip>,
+    // indices of the clips in the order they are played
+    order: Vec<usize>,
+    // the position of the current track in the order
+    position: Option<usize>,
+    shuffle: bool,
+    repeat: Repeat,
+    voices: [PlayerProcessor; 2],
+    // the voice of the current track
+    current: usize,
+    // the frames of the crossfade to the other voice that are done, and all of them
+    fade: Option<(usize, usize)>,
+    crossfade_seconds: f64,
+    crossfade_frames: usize,
+    sample_rate: u32,
+    max_frames: usize,
+    scratch: Vec<f32>,
+    play: bool,
+    // stopped during a crossfade, the next track becomes the current one on the next play
+    stopped: bool,
+    random_state: u64,
+    deallocator: Deallocator,
+    shared: Arc<Shared>,
+    events: Sender<QueuePlayerEvent>,
+    event_receiver: Receiver<QueuePlayerEvent>,
+}
+
+impl Default for QueuePlayerProcessor {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl QueuePlayerProcessor {
+    /// a queue that can hold this number of clips, `Enqueue` doesn't add more
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (events, event_receiver) = bounded(EVENT_CAPACITY);
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        let shared = Arc::new(Shared::default());
+        shared.track.store(NO_TRACK, Ordering::Relaxed);
+        Self {
+            clips: Vec::with_capacity(capacity),
+            order: Vec::with_capacity(capacity),
+            position: None,
+            shuffle: false,
+            repeat: Repeat::Off,
+            voices: Default::default(),
+            current: 0,
+            fade: None,
+            crossfade_seconds: 0.0,
+            crossfade_frames: 0,
+            sample_rate: 0,
+            max_frames: 0,
+            scratch: Vec::new(),
+            play: false,
+            stopped: false,
+            // xorshift needs a state that is not zero
+            random_state: seed | 1,
+            // `Clear` can dispose all clips and their originals at once
+            deallocator: Deallocator::with_overflow(capacity * 2),
+            shared,
+            events,
+            event_receiver,
+        }
+    }
+
+    /// adds a clip with the sample rate of the device
+    pub fn push(&mut self, audio: impl Into<Array2<f32>>) {
+        self.add_clip(Arc::new(audio.into()), None);
+    }
+
+    /// adds a clip, that is converted to the sample rate of the device right away if the queue
+    /// was prepared before, else in `prepare`. the original is kept, so it is only converted
+    /// again if the rate of the device changes.
+    pub fn push_with_sample_rate(&mut self, audio: impl Into<Array2<f32>>, sample_rate: u32) {
+        self.add_clip(Arc::new(audio.into()), Some(sample_rate));
+    }
+
+    /// the current track, the queue and the events, for other threads
+    pub fn status(&self) -> QueuePlayerStatus {
+        QueuePlayerStatus {
+            shared: self.shared.clone(),
+            events: self.event_receiver.clone(),
+        }
+    }
+}
+
+impl AudioProcessor for QueuePlayerProcessor {
+    type Message = QueuePlayerMessage;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        if config.sample_rate != self.sample_rate && config.sample_rate > 0 {
+            for clip in &mut self.clips {
+                if let Some((original, sample_rate)) = &clip.original {
+                    clip.audio = convert_rate(original, *sample_rate, config.sample_rate);
+                }
+            }
+        }
+        self.sample_rate = config.sample_rate;
+        self.set_crossfade(self.crossfade_seconds);
+        self.max_frames = config.num_frames as usize;
+        self.scratch = vec![0.0; self.max_frames * config.num_output_ch as usize];
+        for voice in &mut self.voices {
+            voice.prepare(config.clone());
+        }
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        match message {
+            QueuePlayerMessage::Play => {
+                if self.position.is_none() && !self.order.is_empty() {
+                    self.select(0);
+                }
+                if std::mem::take(&mut self.stopped) && self.fade.is_some() {
+                    self.current = 1 - self.current;
+                    self.fade = None;
+                }
+                if self.position.is_some() {
+                    self.set_playing(true);
+                    self.voices[self.current].message_process(PlayerMessage::Play);
+                    if self.fade.is_some() {
+                        self.voices[1 - self.current].message_process(PlayerMessage::Play);
+                    }
+                }
+            }
+            QueuePlayerMessage::Pause => {
+                self.set_playing(false);
+                for voice in &mut self.voices {
+                    voice.message_process(PlayerMessage::Pause);
+                }
+            }
+            QueuePlayerMessage::Stop => {
+                self.set_playing(false);
+                self.stopped = true;
+                for voice in &mut self.voices {
+                    voice.message_process(PlayerMessage::Stop);
+                }
+            }
+            QueuePlayerMessage::Next => match self.next_position(true) {
+                Some(position) => self.select(position),
+                None => self.finish(),
+            },
+            QueuePlayerMessage::Previous => {
+                if let Some(position) = self.position {
+                    let previous = match position {
+                        0 if self.repeat == Repeat::All => self.order.len() - 1,
+                        0 => 0,
+                        position => position - 1,
+                    };
+                    self.select(previous);
+                }
+            }
+            QueuePlayerMessage::PlayTrack(track) => {
+                if let Some(position) = self.order.iter().position(|t| *t == track) {
+                    self.select(position);
+                }
+            }
+            QueuePlayerMessage::Enqueue(audio) => {
+                if self.clips.len() < self.clips.capacity() {
+                    self.add_clip(audio, None);
+                } else {
+                    self.deallocator.dispose(audio);
+                    notify(&self.events, QueuePlayerEvent::EnqueueRejected);
+                }
+            }
+            QueuePlayerMessage::Clear => {
+                self.finish();
+                for clip in self.clips.drain(..) {
+                    self.deallocator.dispose(clip.audio);
+                    if let Some((original, _)) = clip.original {
+                        self.deallocator.dispose(original);
+                    }
+                }
+                self.order.clear();
+                self.shared.num_tracks.store(0, Ordering::Relaxed);
+            }
+            QueuePlayerMessage::Shuffle(shuffle) => {
+                self.shuffle = shuffle;
+                if shuffle {
+                    let start = self.position.map_or(0, |position| position + 1);
+                    for i in (start + 1..self.order.len()).rev() {
+                        let j = start + self.random(i + 1 - start);
+                        self.order.swap(i, j);
+                    }
+                } else {
+                    let track = self.position.map(|position| self.order[position]);
+                    for (i, t) in self.order.iter_mut().enumerate() {
+                        *t = i;
+                    }
+                    self.position = track;
+                }
+            }
+            QueuePlayerMessage::Repeat(repeat) => {
+                self.repeat = repeat;
+            }
+            QueuePlayerMessage::Crossfade(seconds) => {
+                self.set_crossfade(seconds);
+            }
+            QueuePlayerMessage::Gain(gain) => {
+                for voice in &mut self.voices {
+                    voice.message_process(PlayerMessage::Gain(gain));
+                }
+            }
+        }
+    }
+
+    fn coalesce_key(message: &Self::Message) -> Option<u64> {
+        match message {
+            QueuePlayerMessage::Shuffle(_) => Some(0),
+            QueuePlayerMessage::Repeat(_) => Some(1),
+            QueuePlayerMessage::Crossfade(_) => Some(2),
+            QueuePlayerMessage::Gain(_) => Some(3),
+            _ => None,
+        }
+    }
+
+    fn process(&mut self, output: InterleavedAudioMut<'_, f32>, input: InterleavedAudio<'_, f32>) {
+        self.deallocator.retry();
+        let max_frames = self
+            .max_frames
+            .min(fitting_frames(&self.scratch, output.num_channels()));
+        process_chunked(output, input, max_frames, |output, _| {
+            self.process_block(output);
+        });
+    }
+}
+
+impl QueuePlayerProcessor {
+    fn process_block(&mut self, mut output: InterleavedAudioMut<'_, f32>) {
+        output.clear();
+        let num_frames = output.num_frames();
+        // tracks without frames are skipped, but only once per queue
+        let mut num_skipped = 0;
+        let mut start = 0;
+        while start < num_frames && self.play {
+            let end = match self.fade {
+                Some((done, total)) => (start + total - done).min(num_frames),
+                None => {
+                    let remaining = self.voices[self.current].remaining_frames();
+                    let next = self.next_position(false);
+                    match next {
+                        Some(next) if remaining <= self.crossfade_frames => {
+                            num_skipped += usize::from(remaining == 0);
+                            if num_skipped > self.order.len() {
+                                self.finish();
+                            } else {
+                                self.begin_transition(next, remaining);
+                            }
+                            continue;
+                        }
+                        Some(_) => (start + remaining - self.crossfade_frames).min(num_frames),
+                        None if remaining == 0 => {
+                            self.finish();
+                            continue;
+                        }
+                        None => (start + remaining).min(num_frames),
+                    }
+                }
+            };
+
+            self.render(output.slice_frames_mut(start..end), true);
+            if let Some((done, total)) = self.fade {
+                let done = done + end - start;
+                if done < total {
+                    self.fade = Some((done, total));
+                } else {
+                    self.voices[self.current].message_process(PlayerMessage::Stop);
+                    self.current = 1 - self.current;
+                    self.fade = None;
+                }
+            }
+            start = end;
+        }
+        if start < num_frames {
+            // the voices fade out after a pause or stop, the crossfade holds still
+            self.render(output.slice_frames_mut(start..num_frames), false);
+        }
+    }
+
+    /// the current voice, mixed with the next one during a crossfade
+    fn render(&mut self, mut block: InterleavedAudioMut<'_, f32>, advance: bool) {
+        let num_ch = block.num_channels();
+        self.voices[self.current].process(
+            InterleavedAudioMut::from_slice(block.data_mut(), num_ch),
+            InterleavedAudio::from_slice(&[], 0),
+        );
+        let Some((done, total)) = self.fade else {
+            return;
+        };
+        let next = &mut self.scratch[..block.data().len()];
+        self.voices[1 - self.current].process(
+            InterleavedAudioMut::from_slice(next, num_ch),
+            InterleavedAudio::from_slice(&[], 0),
+        );
+        for (n, (out_frame, next_frame)) in block
+            .frames_iter_mut()
+            .zip(next.chunks_exact(num_ch))
+            .enumerate()
+        {
+            // equal power, because the tracks are not correlated
+            let done = if advance { done + n + 1 } else { done };
+            let angle = done as f32 / (total + 1) as f32 * std::f32::consts::FRAC_PI_2;
+            for (sample, next_sample) in out_frame.iter_mut().zip(next_frame) {
+                *sample = *sample * angle.cos() + next_sample * angle.sin();
+            }
+        }
+    }
+
+    /// starts the next track in the other voice, the current one is faded out over its
+    /// remaining frames
+    fn begin_transition(&mut self, position: usize, remaining: usize) {
+        let other = 1 - self.current;
+        self.load(other, position);
+        if remaining == 0 {
+            self.voices[self.current].message_process(PlayerMessage::Stop);
+            self.current = other;
+        } else {
+            self.fade = Some((0, remaining));
+        }
+    }
+
+    /// jumps to the track at this position of the order, a playing track is faded out over
+    /// the fade time of the player
+    fn select(&mut self, position: usize) {
+        self.stopped = false;
+        let fade_frames = self.voices[self.current].fade_frames();
+        if !self.play || fade_frames == 0 {
+            self.fade = None;
+            self.voices[1 - self.current].message_process(PlayerMessage::Stop);
+            self.load(self.current, position);
+            return;
+        }
+        // during a crossfade the quieter voice is replaced
+        if let Some((done, total)) = self.fade.take() {
+            if done * 2 > total {
+                self.current = 1 - self.current;
+            }
+        }
+        self.begin_transition(position, fade_frames);
+    }
+
+    fn load(&mut self, voice: usize, position: usize) {
+        let track = self.order[position];
+        let voice = &mut self.voices[voice];
+        voice.message_process(PlayerMessage::Stop);
+        voice.message_process(PlayerMessage::Load(self.clips[track].audio.clone()));
+        if self.play {
+            voice.message_process(PlayerMessage::Play);
+        }
+        self.position = Some(position);
+        self.shared.track.store(track, Ordering::Relaxed);
+        notify(&self.events, QueuePlayerEvent::TrackChanged(track));
+    }
+
+    /// the position of the track after the current one, `manual` is true for `Next`
+    fn next_position(&self, manual: bool) -> Option<usize> {
+        let position = self.position?;
+        if self.repeat == Repeat::One && !manual {
+            Some(position)
+        } else if position + 1 < self.order.len() {
+            Some(position + 1)
+        } else if self.repeat != Repeat::Off {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// the next play starts with the first track
+    fn finish(&mut self) {
+        if self.play {
+            notify(&self.events, QueuePlayerEvent::Finished);
+        }
+        self.set_playing(false);
+        self.stopped = false;
+        self.fade = None;
+        self.position = None;
+        self.shared.track.store(NO_TRACK, Ordering::Relaxed);
+        for voice in &mut self.voices {
+            voice.message_process(PlayerMessage::Stop);
+        }
+    }
+
+    fn add_clip(&mut self, audio: Arc<Array2<f32>>, sample_rate: Option<u32>) {
+        if !(1..=MAX_LOAD_CHANNELS).contains(&audio.nrows()) {
+            self.deallocator.dispose(audio);
+            notify(&self.events, QueuePlayerEvent::EnqueueRejected);
+            return;
+        }
+        let track = self.clips.len();
+        let clip = match sample_rate {
+            Some(sample_rate) => Clip {
+                audio: match self.sample_rate {
+                    0 => audio.clone(),
+                    device_rate => convert_rate(&audio, sample_rate, device_rate),
+                },
+                original: Some((audio, sample_rate)),
+            },
+            None => Clip {
+                audio,
+                original: None,
+            },
+        };
+        self.clips.push(clip);
+        let first_shuffled = self.position.map_or(0, |position| position + 1);
+        if self.shuffle && first_shuffled < self.order.len() {
+            let index = first_shuffled + self.random(self.order.len() + 1 - first_shuffled);
+            self.order.insert(index, track);
+        } else {
+            self.order.push(track);
+        }
+        self.shared
+            .num_tracks
+            .store(self.clips.len(), Ordering::Relaxed);
+    }
+
+    fn set_crossfade(&mut self, seconds: f64) {
+        self.crossfade_seconds = seconds.max(0.0);
+        self.crossfade_frames = (self.crossfade_seconds * self.sample_rate as f64) as usize;
+    }
+
+    fn set_playing(&mut self, play: bool) {
+        self.play = play;
+        self.shared.playing.store(play, Ordering::Relaxed);
+    }
+
+    /// a random number below the bound, with xorshift
+    fn random(&mut self, bound: usize) -> usize {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+        (self.random_state % bound.max(1) as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{config, process};
+
+    fn clip(value: f32, len: usize) -> Array2<f32> {
+        Array2::from_elem((1, len), value)
+    }
+
+    #[test]
+    fn gapless() {
+        let mut player = QueuePlayerProcessor::default();
+        player.push(clip(1.0, 3));
+        player.push(clip(2.0, 2));
+        player.prepare(config(1, 0));
+        let status = player.status();
+
+        player.message_process(QueuePlayerMessage::Play);
+        assert_eq!(process(&mut player, 7), [1., 1., 1., 2., 2., 0., 0.]);
+        assert!(!status.is_playing());
+        assert_eq!(status.current_track(), None);
+        let events = status.events().try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                QueuePlayerEvent::TrackChanged(0),
+                QueuePlayerEvent::TrackChanged(1),
+                QueuePlayerEvent::Finished
+            ]
+        );
+
+        // a clip that arrives while playing is played after the others
+        player.message_process(QueuePlayerMessage::Repeat(Repeat::All));
+        player.message_process(QueuePlayerMessage::Enqueue(Arc::new(clip(3.0, 1))));
+        player.message_process(QueuePlayerMessage::Play);
+        assert_eq!(process(&mut player, 8), [1., 1., 1., 2., 2., 3., 1., 1.]);
+
+        // clips without channels are not added
+        player.message_process(QueuePlayerMessage::Enqueue(Arc::new(Array2::zeros((0, 4)))));
+        assert_eq!(status.num_tracks(), 3);
+        assert_eq!(
+            status.events().try_iter().last(),
+            Some(QueuePlayerEvent::EnqueueRejected)
+        );
+    }
+
+    #[test]
+    fn resample_once_per_device_rate() {
+        let mut player = QueuePlayerProcessor::default();
+        player.push_with_sample_rate(clip(1.0, 10), 500);
+        player.prepare(config(1, 0));
+        assert_eq!(player.clips[0].audio.ncols(), 20);
+        let converted = player.clips[0].audio.clone();
+        player.prepare(config(1, 0));
+        assert!(Arc::ptr_eq(&player.clips[0].audio, &converted));
+
+        // a clip pushed after `prepare` is converted right away
+        player.push_with_sample_rate(clip(1.0, 20), 2000);
+        assert_eq!(player.clips[1].audio.ncols(), 10);
+
+        // another rate converts the originals
+        player.prepare(DeviceConfig::new(2000, 16, 1, 0));
+        assert_eq!(player.clips[0].audio.ncols(), 40);
+        assert_eq!(player.clips[1].audio.ncols(), 20);
+    }
+
+    #[test]
+    fn crossfade() {
+        let mut player = QueuePlayerProcessor::default();
+        player.push(clip(1.0, 4));
+        player.push(clip(0.0, 4));
+        player.prepare(config(1, 0));
+        player.message_process(QueuePlayerMessage::Crossfade(0.002));
+        player.message_process(QueuePlayerMessage::Play);
+
+        let output = process(&mut player, 7);
+        let expected = [1., 1., 0.8660254, 0.5, 0., 0., 0.];
+        for (sample, expected) in output.iter().zip(expected) {
+            assert!((sample - expected).abs() < 1e-6, "{output:?}");
+        }
+    }
+
+    #[test]
+    fn stop_and_play() {
+        let mut player = QueuePlayerProcessor::default();
+        player.push(Array2::from_shape_fn((1, 100), |(_, n)| n as f32 + 1.0));
+        player.prepare(config(1, 0));
+        player.message_process(QueuePlayerMessage::Play);
+        process(&mut player, 20);
+
+        // the track fades out over 5 ms instead of clicking
+        player.message_process(QueuePlayerMessage::Stop);
+        let output = process(&mut player, 10);
+        assert!(output[0] > 20.0, "{output:?}");
+        assert_eq!(output[6..], [0.0; 4]);
+
+        player.message_process(QueuePlayerMessage::Play);
+        assert_eq!(process(&mut player, 2), [1.0, 2.0]);
+
+        // a pause keeps the position
+        player.message_process(QueuePlayerMessage::Pause);
+        process(&mut player, 10);
+        player.message_process(QueuePlayerMessage::Play);
+        let output = process(&mut player, 10);
+        assert!(output[9] > 10.0, "{output:?}");
+    }
+
+    #[test]
+    fn next_while_playing() {
+        let mut player = QueuePlayerProcessor::default();
+        player.push(clip(1.0, 100));
+        player.push(clip(2.0, 100));
+        player.prepare(config(1, 0));
+        player.message_process(QueuePlayerMessage::Play);
+        process(&mut player, 10);
+
+        // the tracks are crossfaded over the 5 ms of the player fades instead of clicking
+        player.message_process(QueuePlayerMessage::Next);
+        let output = process(&mut player, 7);
+        assert!(output[0] > 1.0 && output[0] < 2.0, "{output:?}");
+        assert_eq!(output[5..], [2.0, 2.0]);
+        assert_eq!(player.status().current_track(), Some(1));
+    }
+
+    #[test]
+    fn next_previous_and_shuffle() {
+        // the sample after the fade to the selected track
+        fn selected(player: &mut QueuePlayerProcessor) -> f32 {
+            process(player, 6)[5]
+        }
+
+        let mut player = QueuePlayerProcessor::default();
+        for value in 0..5 {
+            player.push(clip(value as f32, 10));
+        }
+        player.prepare(config(1, 0));
+        let status = player.status();
+
+        player.message_process(QueuePlayerMessage::Play);
+        player.message_process(QueuePlayerMessage::Next);
+        assert_eq!(selected(&mut player), 1.0);
+        player.message_process(QueuePlayerMessage::Previous);
+        player.message_process(QueuePlayerMessage::Previous);
+        assert_eq!(status.current_track(), Some(0));
+        player.message_process(QueuePlayerMessage::PlayTrack(3));
+        assert_eq!(selected(&mut player), 3.0);
+
+        // the tracks after the current one are shuffled, every one is still played once
+        player.message_process(QueuePlayerMessage::PlayTrack(0));
+        player.message_process(QueuePlayerMessage::Shuffle(true));
+        let mut played = vec![0];
+        for _ in 0..4 {
+            player.message_process(QueuePlayerMessage::Next);
+            played.push(selected(&mut player) as usize);
+        }
+        played.sort();
+        assert_eq!(played, [0, 1, 2, 3, 4]);
+
+        player.message_process(QueuePlayerMessage::Shuffle(false));
+        player.message_process(QueuePlayerMessage::PlayTrack(1));
+        player.message_process(QueuePlayerMessage::Next);
+        assert_eq!(status.current_track(), Some(2));
+    }
+}
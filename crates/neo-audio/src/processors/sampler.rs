@@ -0,0 +1,361 @@
+use std::sync::Arc;
+
+use ndarray::Array2;
+use realtime_tools::channel_layout::{ChannelLayout, MixMatrix, Speaker};
+
+use super::player::convert_rate;
+use crate::prelude::*;
+
+/// the number of voices of `SamplerProcessor::default`
+const DEFAULT_MAX_VOICES: usize = 16;
+/// the default length of the fade-out of stopped and stolen voices
+const DEFAULT_FADE_MS: usize = 5;
+
+/// Starts a new voice of a clip.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trigger {
+    /// the index that `SamplerProcessor::add_clip` returned
+    pub clip: usize,
+    /// the gain in linear values
+    pub gain: f32,
+    /// the position between the left (-1.0) and the right speakers (1.0). in the center the
+    /// clip plays like without panning.
+    pub pan: f32,
+}
+
+impl Trigger {
+    /// the clip with unity gain in the center
+    pub fn new(clip: usize) -> Self {
+        Self {
+            clip,
+            gain: 1.0,
+            pan: 0.0,
+        }
+    }
+}
+
+pub enum SamplerMessage {
+    /// Trigger plays the clip from the start, on top of the voices that are already playing.
+    /// When all voices are used, the oldest one is faded out to make room. While the fading
+    /// voices use up the room too, the trigger is dropped.
+    Trigger(Trigger),
+    /// Stop fades out all voices of the clip, e.g. an open hi-hat when the closed one is hit
+    Stop(usize),
+    /// StopAll fades out all voices
+    StopAll,
+}
+
+struct Clip {
+    audio: Arc<Array2<f32>>,
+    // the added audio and its sample rate, if it is converted to the rate of the device
+    original: Option<(Arc<Array2<f32>>, u32)>,
+    matrix: MixMatrix,
+}
+
+#[derive(Default)]
+struct Voice {
+    active: bool,
+    clip: usize,
+    position: usize,
+    gain: f32,
+    // the gain of each output channel for the pan
+    pan_gains: Vec<f32>,
+    // the frames of the fade-out that are left
+    fade: Option<usize>,
+    // voices that started earlier have a lower number, the oldest voice is stolen
+    started: u64,
+}
+
+/// Plays one-shot clips polyphonically, e.g. for drum pads and the sound effects of a UI.
+/// Every trigger starts a new voice, so the same clip can overlap itself.
+pub struct SamplerProcessor {
+    clips: Vec<Clip>,
+    max_voices: usize,
+    // twice the number of voices, so stolen voices can fade out while the new ones play
+    voices: Vec<Voice>,
+    num_started: u64,
+    output_speakers: Vec<Speaker>,
+    fade_ms: usize,
+    fade_frames: usize,
+    sample_rate: u32,
+}
+
+impl Default for SamplerProcessor {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_VOICES)
+    }
+}
+
+impl SamplerProcessor {
+    /// a sampler that plays at most this number of voices at once
+    pub fn new(max_voices: usize) -> Self {
+        let max_voices = max_voices.max(1);
+        Self {
+            clips: Vec::new(),
+            max_voices,
+            voices: (0..max_voices * 2).map(|_| Voice::default()).collect(),
+            num_started: 0,
+            output_speakers: Vec::new(),
+            fade_ms: DEFAULT_FADE_MS,
+            fade_frames: 1,
+            sample_rate: 0,
+        }
+    }
+
+    /// adds a clip with the sample rate of the device and returns its index for `Trigger`.
+    /// clips are added before the audio is started.
+    pub fn add_clip(&mut self, audio: impl Into<Array2<f32>>) -> usize {
+        self.push_clip(audio.into(), None)
+    }
+
+    /// adds a clip, that is converted to the sample rate of the device right away if the
+    /// sampler was prepared before, else in `prepare`. the original is kept, so it is only
+    /// converted again if the rate of the device changes.
+    pub fn add_clip_with_sample_rate(
+        &mut self,
+        audio: impl Into<Array2<f32>>,
+        sample_rate: u32,
+    ) -> usize {
+        self.push_clip(audio.into(), Some(sample_rate))
+    }
+
+    pub fn num_clips(&self) -> usize {
+        self.clips.len()
+    }
+
+    /// the length of the fade-out of stopped and stolen voices, 5 ms by default.
+    /// applied in `prepare`.
+    pub fn set_fade_time(&mut self, milliseconds: usize) {
+        self.fade_ms = milliseconds;
+    }
+
+    fn push_clip(&mut self, audio: Array2<f32>, sample_rate: Option<u32>) -> usize {
+        let audio = Arc::new(audio);
+        let (audio, original) = match sample_rate {
+            Some(sample_rate) if self.sample_rate > 0 => (
+                convert_rate(&audio, sample_rate, self.sample_rate),
+                Some((audio, sample_rate)),
+            ),
+            Some(sample_rate) => (audio.clone(), Some((audio, sample_rate))),
+            None => (audio, None),
+        };
+        self.clips.push(Clip {
+            audio,
+            original,
+            matrix: MixMatrix::default(),
+        });
+        self.clips.len() - 1
+    }
+}
+
+impl AudioProcessor for SamplerProcessor {
+    type Message = SamplerMessage;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        let rate_changed = config.sample_rate != self.sample_rate && config.sample_rate > 0;
+        for clip in &mut self.clips {
+            if let (true, Some((original, sample_rate))) = (rate_changed, &clip.original) {
+                clip.audio = convert_rate(original, *sample_rate, config.sample_rate);
+            }
+            let layout = ChannelLayout::from_num_channels(clip.audio.nrows());
+            clip.matrix = MixMatrix::new(&layout, &config.output_layout);
+        }
+        self.output_speakers = config.output_layout.speakers().to_vec();
+        for voice in &mut self.voices {
+            voice.active = false;
+            voice.pan_gains = vec![1.0; self.output_speakers.len()];
+        }
+        self.sample_rate = config.sample_rate;
+        self.fade_frames =
+            ((config.sample_rate as usize * self.fade_ms) as f64 / 1000.0).round() as usize;
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        match message {
+            SamplerMessage::Trigger(trigger) => self.trigger(trigger),
+            SamplerMessage::Stop(clip) => {
+                for voice in self.voices.iter_mut().filter(|voice| voice.clip == clip) {
+                    voice.fade_out(self.fade_frames);
+                }
+            }
+            SamplerMessage::StopAll => {
+                for voice in &mut self.voices {
+                    voice.fade_out(self.fade_frames);
+                }
+            }
+        }
+    }
+
+    fn process(
+        &mut self,
+        mut output: InterleavedAudioMut<'_, f32>,
+        _input: InterleavedAudio<'_, f32>,
+    ) {
+        output.clear();
+        for voice in self.voices.iter_mut().filter(|voice| voice.active) {
+            let clip = &self.clips[voice.clip];
+            for frame in output.frames_iter_mut() {
+                if voice.position >= clip.audio.ncols() || voice.fade == Some(0) {
+                    voice.active = false;
+                    break;
+                }
+                let mut gain = voice.gain;
+                if let Some(fade) = &mut voice.fade {
+                    gain *= *fade as f32 / (self.fade_frames + 1) as f32;
+                    *fade -= 1;
+                }
+                let column = clip.audio.column(voice.position);
+                for ((sample, row), pan_gain) in frame
+                    .iter_mut()
+                    .zip(clip.matrix.rows())
+                    .zip(&voice.pan_gains)
+                {
+                    let mixed = row
+                        .iter()
+                        .zip(column.iter())
+                        .map(|(g, s)| g * s)
+                        .sum::<f32>();
+                    *sample += mixed * gain * pan_gain;
+                }
+                voice.position += 1;
+            }
+        }
+    }
+}
+
+impl SamplerProcessor {
+    fn trigger(&mut self, trigger: Trigger) {
+        if trigger.clip >= self.clips.len() {
+            return;
+        }
+
+        // fading voices keep playing until they are silent, restarting one would click
+        let Some(index) = self.voices.iter().position(|voice| !voice.active) else {
+            return;
+        };
+        let playing = self.voices.iter().filter(|voice| voice.is_playing());
+        if playing.count() >= self.max_voices {
+            if let Some(oldest) = self
+                .voices
+                .iter_mut()
+                .filter(|voice| voice.is_playing())
+                .min_by_key(|voice| voice.started)
+            {
+                oldest.fade_out(self.fade_frames);
+            }
+        }
+        let voice = &mut self.voices[index];
+        voice.active = true;
+        voice.clip = trigger.clip;
+        voice.position = 0;
+        voice.gain = trigger.gain;
+        voice.fade = None;
+        voice.started = self.num_started;
+        self.num_started += 1;
+
+        // equal power, scaled so the center keeps the level of the clip
+        let angle = (trigger.pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+        let left = angle.cos() * std::f32::consts::SQRT_2;
+        let right = angle.sin() * std::f32::consts::SQRT_2;
+        for (gain, speaker) in voice.pan_gains.iter_mut().zip(&self.output_speakers) {
+            *gain = match speaker {
+                Speaker::FrontLeft | Speaker::BackLeft | Speaker::SideLeft => left,
+                Speaker::FrontRight | Speaker::BackRight | Speaker::SideRight => right,
+                _ => 1.0,
+            };
+        }
+    }
+}
+
+impl Voice {
+    /// playing and not fading out
+    fn is_playing(&self) -> bool {
+        self.active && self.fade.is_none()
+    }
+
+    fn fade_out(&mut self, fade_frames: usize) {
+        if self.is_playing() {
+            self.fade = Some(fade_frames);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{config, process_channels};
+
+    fn assert_near(output: &[f32], expected: &[f32]) {
+        assert_eq!(output.len(), expected.len());
+        for (sample, expected) in output.iter().zip(expected) {
+            assert!((sample - expected).abs() < 1e-5, "{output:?}");
+        }
+    }
+
+    #[test]
+    fn overlapping_voices() {
+        let mut sampler = SamplerProcessor::default();
+        let kick = sampler.add_clip(Array2::from_elem((2, 4), 1.0));
+        let snare = sampler.add_clip(Array2::from_elem((2, 2), 0.5));
+        sampler.prepare(config(2, 0));
+
+        sampler.message_process(SamplerMessage::Trigger(Trigger::new(kick)));
+        sampler.message_process(SamplerMessage::Trigger(Trigger::new(snare)));
+        sampler.message_process(SamplerMessage::Trigger(Trigger {
+            clip: snare,
+            gain: 2.0,
+            pan: -1.0,
+        }));
+        // the panned snare is 3 dB louder on the left and silent on the right
+        let left = 1.5 + std::f32::consts::SQRT_2;
+        assert_near(
+            &process_channels(&mut sampler, 2, 5),
+            &[left, 1.5, left, 1.5, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn steal_oldest_voice() {
+        let mut sampler = SamplerProcessor::new(2);
+        let clip = sampler.add_clip(Array2::ones((2, 100)));
+        sampler.prepare(config(2, 0));
+
+        for gain in [1.0, 2.0, 4.0] {
+            sampler.message_process(SamplerMessage::Trigger(Trigger {
+                clip,
+                gain,
+                pan: 0.0,
+            }));
+        }
+        // the first voice fades out over 5 ms
+        let output = process_channels(&mut sampler, 2, 8);
+        let left = output.iter().step_by(2).copied().collect::<Vec<_>>();
+        assert!(left[..6].windows(2).all(|w| w[1] < w[0]));
+        assert_near(&left[5..], &[6.0, 6.0, 6.0]);
+
+        sampler.message_process(SamplerMessage::StopAll);
+        let output = process_channels(&mut sampler, 2, 8);
+        assert_near(&output[12..], &[0.0; 4]);
+        assert!(sampler.voices.iter().all(|voice| !voice.active));
+    }
+
+    #[test]
+    fn drop_trigger_while_voices_fade() {
+        let mut sampler = SamplerProcessor::new(1);
+        let clip = sampler.add_clip(Array2::ones((2, 100)));
+        sampler.prepare(config(2, 0));
+
+        // the second trigger fades out the first voice, the third finds no free voice
+        for gain in [1.0, 2.0, 4.0] {
+            sampler.message_process(SamplerMessage::Trigger(Trigger {
+                clip,
+                gain,
+                pan: 0.0,
+            }));
+        }
+        let output = process_channels(&mut sampler, 2, 8);
+        let left = output.iter().step_by(2).copied().collect::<Vec<_>>();
+        assert!(left[..6].windows(2).all(|w| w[1] < w[0] && w[0] <= 3.0));
+        assert_near(&left[5..], &[2.0, 2.0, 2.0]);
+    }
+}
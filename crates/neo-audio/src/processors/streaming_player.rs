@@ -0,0 +1,462 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use realtime_tools::channel_layout::{ChannelLayout, MixMatrix};
+use ringbuf::{traits::*, HeapCons, HeapProd, HeapRb};
+
+use crate::prelude::*;
+
+/// the number of frames the reader thread decodes at once
+const READ_FRAMES: usize = 4096;
+/// how long the reader thread waits, when the buffer is full
+const IDLE: Duration = Duration::from_millis(5);
+
+/// Audio that is read while it is played, e.g. a file that is decoded from disk.
+/// The source is moved to the reader thread of the `StreamingPlayerProcessor`, so it doesn't
+/// need to be real-time safe.
+pub trait StreamSource: Send + 'static {
+    fn num_channels(&self) -> usize;
+
+    fn sample_rate(&self) -> u32;
+
+    /// reads interleaved frames into the buffer, its length is a multiple of the channels.
+    /// returns the number of frames read, 0 at the end of the stream.
+    fn read(&mut self, buffer: &mut [f32]) -> Result<usize, NeoAudioError>;
+
+    /// the next `read` starts at this frame
+    fn seek(&mut self, frame: u64) -> Result<(), NeoAudioError>;
+}
+
+pub enum StreamingPlayerMessage {
+    /// Play will play the stream until the end, or until you send pause/stop
+    Play,
+    /// Pause will pause the playback, the next time you send play it will continue from the
+    /// same position
+    Pause,
+    /// Stop will pause the playback and seek to the beginning
+    Stop,
+    /// Seek will continue the playback at this frame of the stream. Until the reader thread has
+    /// caught up, the output is silent.
+    Seek(u64),
+    /// Gain will set the gain in linear values.
+    Gain(f32),
+}
+
+#[derive(Default)]
+struct Shared {
+    // written by the audio thread
+    seek_generation: AtomicU64,
+    seek_frame: AtomicU64,
+    position: AtomicU64,
+    playing: AtomicBool,
+    underruns: AtomicUsize,
+    // written by the reader thread
+    reader_generation: AtomicU64,
+    end_of_stream: AtomicBool,
+    error: Mutex<Option<String>>,
+    // written on drop of the processor
+    stop: AtomicBool,
+}
+
+/// The state of a `StreamingPlayerProcessor`, can be cloned and read from any thread.
+#[derive(Clone)]
+pub struct StreamingPlayerStatus {
+    shared: Arc<Shared>,
+}
+
+impl StreamingPlayerStatus {
+    /// the frame of the stream that is played next
+    pub fn position(&self) -> u64 {
+        self.shared.position.load(Ordering::Relaxed)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.shared.playing.load(Ordering::Relaxed)
+    }
+
+    /// number of audio callbacks that didn't get enough audio from the reader thread
+    pub fn underruns(&self) -> usize {
+        self.shared.underruns.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_underruns(&self) {
+        self.shared.underruns.store(0, Ordering::Relaxed);
+    }
+
+    /// the last error of the source, the stream ends when it happens
+    pub fn error(&self) -> Option<String> {
+        self.shared.error.lock().ok()?.clone()
+    }
+}
+
+/// Plays long files without loading them into memory. A reader thread decodes ahead into a
+/// lock-free ring buffer, the audio thread only reads from it.
+/// The stream is not resampled, if its sample rate doesn't match the one of the device, the
+/// player stays silent and `StreamingPlayerStatus::error` reports it.
+pub struct StreamingPlayerProcessor {
+    consumer: HeapCons<f32>,
+    shared: Arc<Shared>,
+    num_channels: usize,
+    sample_rate: u32,
+    rate_mismatch: bool,
+    layout: Option<ChannelLayout>,
+    matrix: MixMatrix,
+    max_frames: usize,
+    scratch: Vec<f32>,
+    seek_generation: u64,
+    position: u64,
+    play: bool,
+    gain: f32,
+}
+
+impl StreamingPlayerProcessor {
+    /// starts the reader thread with a buffer of two seconds of the source
+    pub fn new(source: impl StreamSource) -> Self {
+        let buffer_frames = source.sample_rate() as usize * 2;
+        Self::with_buffer_frames(source, buffer_frames)
+    }
+
+    /// starts the reader thread with a buffer of this number of frames. larger buffers survive
+    /// longer stalls of the disk.
+    pub fn with_buffer_frames(source: impl StreamSource, buffer_frames: usize) -> Self {
+        let num_channels = source.num_channels().max(1);
+        let sample_rate = source.sample_rate();
+        let (producer, consumer) = HeapRb::new(buffer_frames.max(1) * num_channels).split();
+        let shared = Arc::new(Shared::default());
+        let reader_shared = shared.clone();
+        thread::spawn(move || read_ahead(source, producer, reader_shared, num_channels));
+        Self {
+            consumer,
+            shared,
+            num_channels,
+            sample_rate,
+            rate_mismatch: false,
+            layout: None,
+            matrix: MixMatrix::default(),
+            max_frames: 0,
+            scratch: Vec::new(),
+            seek_generation: 0,
+            position: 0,
+            play: false,
+            gain: 1.0,
+        }
+    }
+
+    /// the speakers of the channels of the source, by default the common layout for the number
+    /// of channels is used. the stream is mixed to the output layout in `prepare`.
+    pub fn set_channel_layout(&mut self, layout: ChannelLayout) {
+        self.layout = Some(layout);
+    }
+
+    pub fn status(&self) -> StreamingPlayerStatus {
+        StreamingPlayerStatus {
+            shared: self.shared.clone(),
+        }
+    }
+
+    fn seek(&mut self, frame: u64) {
+        self.position = frame;
+        self.seek_generation += 1;
+        self.shared.seek_frame.store(frame, Ordering::Relaxed);
+        self.shared
+            .seek_generation
+            .store(self.seek_generation, Ordering::Release);
+        self.shared.position.store(frame, Ordering::Relaxed);
+    }
+
+    fn set_playing(&mut self, play: bool) {
+        self.play = play;
+        self.shared.playing.store(play, Ordering::Relaxed);
+    }
+}
+
+impl Drop for StreamingPlayerProcessor {
+    fn drop(&mut self) {
+        // the reader thread ends on its own, joining it could block the audio thread
+        self.shared.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl AudioProcessor for StreamingPlayerProcessor {
+    type Message = StreamingPlayerMessage;
+
+    fn prepare(&mut self, config: DeviceConfig) {
+        let layout = self
+            .layout
+            .clone()
+            .unwrap_or_else(|| ChannelLayout::from_num_channels(self.num_channels));
+        self.matrix = MixMatrix::new(&layout, &config.output_layout);
+        self.max_frames = config.num_frames as usize;
+        self.scratch = vec![0.0; self.max_frames * self.num_channels];
+
+        self.rate_mismatch = self.sample_rate != config.sample_rate;
+        if self.rate_mismatch {
+            if let Ok(mut error) = self.shared.error.lock() {
+                *error = Some(format!(
+                    "{}: the stream has {} Hz, the device {} Hz",
+                    NeoAudioError::SampleRate,
+                    self.sample_rate,
+                    config.sample_rate
+                ));
+            }
+        }
+    }
+
+    fn message_process(&mut self, message: Self::Message) {
+        match message {
+            StreamingPlayerMessage::Play => self.set_playing(true),
+            StreamingPlayerMessage::Pause => self.set_playing(false),
+            StreamingPlayerMessage::Stop => {
+                self.set_playing(false);
+                self.seek(0);
+            }
+            StreamingPlayerMessage::Seek(frame) => self.seek(frame),
+            StreamingPlayerMessage::Gain(gain) => self.gain = gain,
+        }
+    }
+
+    fn coalesce_key(message: &Self::Message) -> Option<u64> {
+        match message {
+            StreamingPlayerMessage::Seek(_) => Some(0),
+            StreamingPlayerMessage::Gain(_) => Some(1),
+            _ => None,
+        }
+    }
+
+    fn process(
+        &mut self,
+        mut output: InterleavedAudioMut<'_, f32>,
+        _input: InterleavedAudio<'_, f32>,
+    ) {
+        output.clear();
+
+        // the audio from before the last seek is discarded, until the reader thread caught up
+        if self.shared.reader_generation.load(Ordering::Acquire) != self.seek_generation {
+            self.consumer.clear();
+            return;
+        }
+        if !self.play || self.max_frames == 0 || self.rate_mismatch {
+            return;
+        }
+
+        let num_frames = output.num_frames();
+        let mut start = 0;
+        while start < num_frames {
+            let end = (start + self.max_frames).min(num_frames);
+            let wanted = (end - start) * self.num_channels;
+            // loaded before reading, so everything the reader pushed is in the buffer
+            let end_of_stream = self.shared.end_of_stream.load(Ordering::Acquire);
+            let read = self.consumer.pop_slice(&mut self.scratch[..wanted]);
+
+            let source = self.scratch[..read].chunks_exact(self.num_channels);
+            let mut chunk = output.slice_frames_mut(start..end);
+            for (out_frame, in_frame) in chunk.frames_iter_mut().zip(source) {
+                self.matrix.mix_frame(out_frame, in_frame);
+            }
+            chunk.apply_gain(self.gain);
+            self.position += (read / self.num_channels) as u64;
+
+            if read < wanted {
+                if end_of_stream {
+                    self.set_playing(false);
+                    self.seek(0);
+                } else {
+                    self.shared.underruns.fetch_add(1, Ordering::Relaxed);
+                }
+                break;
+            }
+            start = end;
+        }
+        self.shared.position.store(self.position, Ordering::Relaxed);
+    }
+}
+
+/// runs in the reader thread, until the processor is dropped
+fn read_ahead(
+    mut source: impl StreamSource,
+    mut producer: HeapProd<f32>,
+    shared: Arc<Shared>,
+    num_channels: usize,
+) {
+    let mut chunk = vec![0.0; READ_FRAMES * num_channels];
+    let mut generation = 0;
+    while !shared.stop.load(Ordering::Relaxed) {
+        let wanted = shared.seek_generation.load(Ordering::Acquire);
+        if wanted != generation {
+            // the audio thread empties the buffer, the old audio must not be played after the seek
+            if !producer.is_empty() {
+                thread::sleep(IDLE);
+                continue;
+            }
+            let result = source.seek(shared.seek_frame.load(Ordering::Relaxed));
+            shared.end_of_stream.store(false, Ordering::Relaxed);
+            if let Err(error) = result {
+                fail(&shared, error);
+            }
+            generation = wanted;
+            shared
+                .reader_generation
+                .store(generation, Ordering::Release);
+            continue;
+        }
+
+        let free_frames = producer.vacant_len() / num_channels;
+        if free_frames == 0 || shared.end_of_stream.load(Ordering::Relaxed) {
+            thread::sleep(IDLE);
+            continue;
+        }
+        let len = free_frames.min(READ_FRAMES) * num_channels;
+        match source.read(&mut chunk[..len]) {
+            Ok(0) => shared.end_of_stream.store(true, Ordering::Release),
+            Ok(num_frames) => {
+                producer.push_slice(&chunk[..num_frames.min(free_frames) * num_channels]);
+            }
+            Err(error) => fail(&shared, error),
+        }
+    }
+}
+
+/// ends the stream after an error of the source
+fn fail(shared: &Shared, error: NeoAudioError) {
+    if let Ok(mut last_error) = shared.error.lock() {
+        *last_error = Some(error.to_string());
+    }
+    shared.end_of_stream.store(true, Ordering::Release);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// a mono stream that counts the frames
+    struct Counter {
+        len: u64,
+        position: u64,
+        delay: Duration,
+    }
+
+    impl StreamSource for Counter {
+        fn num_channels(&self) -> usize {
+            1
+        }
+
+        fn sample_rate(&self) -> u32 {
+            48000
+        }
+
+        fn read(&mut self, buffer: &mut [f32]) -> Result<usize, NeoAudioError> {
+            thread::sleep(self.delay);
+            let num_frames = buffer.len().min((self.len - self.position) as usize);
+            for sample in &mut buffer[..num_frames] {
+                *sample = self.position as f32;
+                self.position += 1;
+            }
+            Ok(num_frames)
+        }
+
+        fn seek(&mut self, frame: u64) -> Result<(), NeoAudioError> {
+            self.position = frame.min(self.len);
+            Ok(())
+        }
+    }
+
+    fn player(len: u64, delay: Duration) -> StreamingPlayerProcessor {
+        let source = Counter {
+            len,
+            position: 0,
+            delay,
+        };
+        let mut player = StreamingPlayerProcessor::with_buffer_frames(source, 64);
+        player.prepare(DeviceConfig::new(48000, 8, 1, 0));
+        player
+    }
+
+    fn process(player: &mut StreamingPlayerProcessor, output: &mut [f32]) {
+        player.process(
+            InterleavedAudioMut::from_slice(output, 1),
+            InterleavedAudio::from_slice(&[], 0),
+        );
+    }
+
+    /// seeks while paused and waits until the reader thread filled the buffer
+    fn seek(player: &mut StreamingPlayerProcessor, frame: u64) {
+        player.message_process(StreamingPlayerMessage::Pause);
+        player.message_process(StreamingPlayerMessage::Seek(frame));
+        let timeout = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < timeout {
+            process(player, &mut [0.0; 8]);
+            let shared = &player.shared;
+            if shared.reader_generation.load(Ordering::Acquire) == player.seek_generation
+                && (player.consumer.is_full() || shared.end_of_stream.load(Ordering::Acquire))
+            {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        player.message_process(StreamingPlayerMessage::Play);
+    }
+
+    #[test]
+    fn stream_and_seek() {
+        let mut player = player(1000, Duration::ZERO);
+        let status = player.status();
+        let mut output = [0.0; 12];
+
+        seek(&mut player, 0);
+        process(&mut player, &mut output);
+        assert_eq!(output[..4], [0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(output[11], 11.0);
+        assert_eq!(status.position(), 12);
+
+        // the audio before the seek is never played
+        seek(&mut player, 500);
+        process(&mut player, &mut output);
+        assert_eq!(output[0], 500.0);
+        assert_eq!(status.position(), 512);
+        assert_eq!(status.underruns(), 0);
+
+        // the player stops at the end and returns to the start
+        seek(&mut player, 995);
+        process(&mut player, &mut output);
+        assert_eq!(output[..6], [995.0, 996.0, 997.0, 998.0, 999.0, 0.0]);
+        assert!(!status.is_playing());
+        assert_eq!(status.position(), 0);
+        assert_eq!(status.underruns(), 0);
+    }
+
+    #[test]
+    fn sample_rate_mismatch() {
+        let mut player = player(1000, Duration::ZERO);
+        let status = player.status();
+        assert_eq!(status.error(), None);
+
+        player.prepare(DeviceConfig::new(44100, 8, 1, 0));
+        assert!(status.error().unwrap().contains("48000 Hz"));
+        seek(&mut player, 0);
+        let mut output = [1.0; 8];
+        process(&mut player, &mut output);
+        assert_eq!(output, [0.0; 8]);
+        assert_eq!(status.position(), 0);
+    }
+
+    #[test]
+    fn underruns() {
+        let mut player = player(1000, Duration::from_millis(50));
+        let status = player.status();
+        player.message_process(StreamingPlayerMessage::Play);
+        let mut output = [0.0; 8];
+        for _ in 0..3 {
+            process(&mut player, &mut output);
+        }
+        assert!(status.underruns() > 0);
+        assert!(status.is_playing());
+    }
+}
@@ -0,0 +1,198 @@
+use ndarray::Array2;
+
+/// the length of the grains, long enough for low notes and short enough to not smear speech
+const WINDOW_MS: usize = 40;
+
+/// Changes the speed of audio without changing its pitch, with WSOLA (waveform similarity
+/// overlap-add). Grains of the audio are overlapped by half of their length. Each grain is taken
+/// from around the position of the player, where it is most similar to the audio that followed
+/// the previous grain, so the waveforms line up and don't cancel out.
+pub(crate) struct TimeStretch {
+    // a hann window, overlapping halves add up to 1
+    window: Vec<f32>,
+    hop: usize,
+    // how far a grain can be moved away from the position to line it up
+    tolerance: usize,
+    // the overlap-added grains, interleaved. the first hop is complete and is read.
+    output: Vec<f32>,
+    num_channels: usize,
+    read: usize,
+    // the start of the previous grain in the audio
+    previous: Option<isize>,
+}
+
+impl Default for TimeStretch {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl TimeStretch {
+    pub fn new(sample_rate: u32, max_channels: usize) -> Self {
+        let hop = (sample_rate as usize * WINDOW_MS / 2000).max(1);
+        let len = hop * 2;
+        let window = (0..len)
+            .map(|n| {
+                let phase = n as f32 / len as f32 * std::f32::consts::TAU;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+        Self {
+            window,
+            hop,
+            tolerance: hop / 2,
+            output: vec![0.0; len * max_channels],
+            num_channels: 0,
+            read: hop,
+            previous: None,
+        }
+    }
+
+    /// starts over without overlapping the previous grain, e.g. after a seek
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.read = self.hop;
+    }
+
+    /// writes the next frame into `frame`, which has one sample per row of the audio.
+    /// `position` is the frame of the audio the player is at, the next grain starts around it.
+    pub fn next_frame(&mut self, audio: &Array2<f32>, position: usize, frame: &mut [f32]) {
+        let num_channels = audio.nrows();
+        if num_channels * self.window.len() > self.output.len() {
+            frame.fill(0.0);
+            return;
+        }
+        if num_channels != self.num_channels {
+            self.num_channels = num_channels;
+            self.reset();
+        }
+        if self.read == self.hop {
+            self.add_grain(audio, position as isize);
+            self.read = 0;
+        }
+        let start = self.read * num_channels;
+        frame.copy_from_slice(&self.output[start..start + num_channels]);
+        self.read += 1;
+    }
+
+    fn add_grain(&mut self, audio: &Array2<f32>, target: isize) {
+        let hop = self.hop as isize;
+        let previous = match self.previous {
+            Some(previous) => previous,
+            None => {
+                // the grain before the target, so the first hop isn't faded in
+                self.output.fill(0.0);
+                self.overlap_add(audio, target - hop);
+                target - hop
+            }
+        };
+        let start = self.best_match(audio, previous + hop, target);
+
+        let num_channels = self.num_channels;
+        let len = self.window.len() * num_channels;
+        self.output.copy_within(self.hop * num_channels..len, 0);
+        self.output[len - self.hop * num_channels..len].fill(0.0);
+        self.overlap_add(audio, start);
+        self.previous = Some(start);
+    }
+
+    fn overlap_add(&mut self, audio: &Array2<f32>, start: isize) {
+        let num_channels = self.num_channels;
+        for (n, gain) in self.window.iter().enumerate() {
+            let Some(column) = column(audio, start + n as isize) else {
+                continue;
+            };
+            let frame = &mut self.output[n * num_channels..(n + 1) * num_channels];
+            for (sample, value) in frame.iter_mut().zip(column) {
+                *sample += value * gain;
+            }
+        }
+    }
+
+    /// the start around the target, where the audio is most similar to the natural continuation
+    /// of the previous grain
+    fn best_match(&self, audio: &Array2<f32>, natural: isize, target: isize) -> isize {
+        let tolerance = self.tolerance as isize;
+        // a coarse search, refined around the best candidate
+        let coarse = (-tolerance..=tolerance)
+            .step_by(4)
+            .map(|offset| target + offset);
+        let best = self.most_similar(audio, natural, coarse, 2);
+        self.most_similar(audio, natural, best - 3..=best + 3, 1)
+    }
+
+    fn most_similar(
+        &self,
+        audio: &Array2<f32>,
+        natural: isize,
+        candidates: impl Iterator<Item = isize>,
+        stride: usize,
+    ) -> isize {
+        let mut best = (natural, f32::MIN);
+        for candidate in candidates {
+            let mut correlation = 0.0;
+            let mut energy = 0.0;
+            for n in (0..self.hop as isize).step_by(stride) {
+                let x = mono(audio, candidate + n);
+                correlation += x * mono(audio, natural + n);
+                energy += x * x;
+            }
+            let similarity = correlation / (energy + 1e-9).sqrt();
+            if similarity > best.1 {
+                best = (candidate, similarity);
+            }
+        }
+        best.0
+    }
+}
+
+/// the samples of a frame, `None` outside of the audio
+fn column(audio: &Array2<f32>, index: isize) -> Option<ndarray::ArrayView1<'_, f32>> {
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index < audio.ncols())
+        .map(|index| audio.column(index))
+}
+
+fn mono(audio: &Array2<f32>, index: isize) -> f32 {
+    column(audio, index).map_or(0.0, |column| column.sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the number of times the signal goes from negative to positive
+    fn crossings(signal: &[f32]) -> usize {
+        signal
+            .windows(2)
+            .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+            .count()
+    }
+
+    #[test]
+    fn keep_pitch() {
+        // 50 Hz at 1000 Hz, the window is 40 frames long
+        let audio = Array2::from_shape_fn((1, 1000), |(_, n)| {
+            (n as f32 / 20.0 * std::f32::consts::TAU).sin()
+        });
+        let mut stretch = TimeStretch::new(1000, 1);
+        let mut output = Vec::new();
+        let mut position = 0.0;
+        let mut frame = [0.0];
+        while position < 1000.0 {
+            stretch.next_frame(&audio, position as usize, &mut frame);
+            output.push(frame[0]);
+            position += 0.5;
+        }
+
+        // twice as long with the same frequency and level
+        assert_eq!(output.len(), 2000);
+        assert!((crossings(&output) as i32 - 100).abs() <= 2);
+        // the grains line up, so no period is cancelled out
+        for period in output[100..1900].chunks(20) {
+            let peak = period.iter().fold(0.0f32, |a, b| a.max(b.abs()));
+            assert!(peak > 0.9 && peak < 1.1, "{peak}");
+        }
+    }
+}
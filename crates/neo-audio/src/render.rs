@@ -0,0 +1,228 @@
+//! Runs a processor without a backend, e.g. for tests with golden outputs or to bounce the
+//! output to a file. The processor is prepared and gets its messages and buffers exactly like
+//! in a stream started with `NeoAudio::start_audio`, but on the calling thread.
+
+use realtime_tools::{
+    audio_buffer::AudioBuffer,
+    interleaved_audio::{InterleavedAudio, InterleavedAudioMut},
+};
+
+use crate::{
+    audio_processor::AudioProcessor,
+    device_config::DeviceConfig,
+    engine::{copy_frames, BlockSize, Engine},
+    message_queue::{self, OverflowPolicy},
+    sample::Sample,
+};
+
+/// What the processor gets as input while rendering.
+pub enum RenderInput<'a> {
+    /// interleaved audio, channels that don't exist in the config are dropped or left silent
+    Audio(InterleavedAudio<'a, f32>),
+    /// silence of this number of frames
+    Silence(usize),
+}
+
+impl RenderInput<'_> {
+    fn num_frames(&self) -> usize {
+        match self {
+            RenderInput::Audio(audio) => audio.num_frames(),
+            RenderInput::Silence(num_frames) => *num_frames,
+        }
+    }
+}
+
+/// Renders the processor into an interleaved buffer with the output channels of the config and
+/// as many frames as the input has, plus the latency of the block size.
+/// The audio is processed in callbacks of `config.num_frames`, like a backend would, and split
+/// further by the block size. The messages of the schedule are delivered at their frame, frames
+/// beyond the end are never delivered. With `BlockSize::Fixed` the output is delayed by the
+/// block size, like in a stream, so silence is rendered after the input until all of it is out.
+pub fn render_offline<P, T>(
+    processor: P,
+    config: DeviceConfig,
+    block_size: BlockSize,
+    input: RenderInput<'_>,
+    schedule: impl IntoIterator<Item = (u64, P::Message)>,
+) -> AudioBuffer<f32>
+where
+    P: AudioProcessor<T>,
+    T: Sample,
+{
+    let schedule = schedule.into_iter().collect::<Vec<_>>();
+    // the whole schedule fits into the queue, so the engine keeps every message until its frame
+    let (sender, receiver) =
+        message_queue::channel(schedule.len().max(1), OverflowPolicy::Block, |_| None);
+    for (frame, message) in schedule {
+        // can not fail, the receiver is alive and the queue is large enough
+        let _ = sender.send_at(frame, message);
+    }
+
+    let num_output_ch = config.num_output_ch as usize;
+    let num_input_ch = config.num_input_ch as usize;
+    let callback_frames = (config.num_frames as usize).max(1);
+    let num_frames = input.num_frames() + block_size.latency();
+
+    let mut input_buffer = AudioBuffer::interleaved(num_input_ch, num_frames);
+    if let (RenderInput::Audio(audio), Some(mut buffer)) =
+        (&input, input_buffer.as_interleaved_mut())
+    {
+        copy_frames(&mut buffer, audio);
+    }
+    let mut output_buffer = AudioBuffer::interleaved(num_output_ch, num_frames);
+
+    let mut engine = Engine::prepared(processor, config, receiver, block_size);
+    // nothing is rendered in the audio thread, so allocations are fine
+    #[cfg(feature = "rt-check")]
+    engine.set_rt_check(false);
+    let mut output = InterleavedAudioMut::from_slice(output_buffer.data_mut(), num_output_ch);
+    let input = InterleavedAudio::from_slice(input_buffer.data(), num_input_ch);
+    let mut start = 0;
+    while start < num_frames {
+        let end = (start + callback_frames).min(num_frames);
+        engine.process(
+            output.slice_frames_mut(start..end),
+            input.slice_frames(start..end),
+        );
+        start = end;
+    }
+    output_buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// multiplies the mono input with a gain that changes by the ramp every frame
+    struct Gain {
+        gain: f32,
+        ramp: f32,
+        prepared_frames: u32,
+    }
+
+    impl AudioProcessor for Gain {
+        type Message = f32;
+
+        fn prepare(&mut self, config: DeviceConfig) {
+            self.prepared_frames = config.num_frames;
+        }
+
+        fn message_process(&mut self, message: Self::Message) {
+            self.ramp = message;
+        }
+
+        fn process(
+            &mut self,
+            mut output: InterleavedAudioMut<'_, f32>,
+            input: InterleavedAudio<'_, f32>,
+        ) {
+            assert!(output.num_frames() as u32 <= self.prepared_frames);
+            for (out_frame, in_frame) in output.frames_iter_mut().zip(input.frames_iter()) {
+                self.gain += self.ramp;
+                out_frame.fill(in_frame[0] * self.gain);
+            }
+        }
+    }
+
+    fn config(num_frames: u32) -> DeviceConfig {
+        DeviceConfig::new(48000, num_frames, 2, 1)
+    }
+
+    fn gain() -> Gain {
+        Gain {
+            gain: 0.0,
+            ramp: 0.0,
+            prepared_frames: 0,
+        }
+    }
+
+    #[test]
+    fn golden_output() {
+        let input = [1.0f32; 8];
+        let output = render_offline(
+            gain(),
+            config(3),
+            BlockSize::Variable,
+            RenderInput::Audio(InterleavedAudio::from_slice(&input, 1)),
+            [(5, -1.0), (2, 1.0)],
+        );
+        assert_eq!(output.num_channels(), 2);
+        assert_eq!(
+            output.data(),
+            [0., 0., 0., 0., 1., 1., 2., 2., 3., 3., 2., 2., 1., 1., 0., 0.]
+        );
+
+        // the block size doesn't change a sample-accurate result
+        let split = render_offline(
+            gain(),
+            config(3),
+            BlockSize::Max(2),
+            RenderInput::Audio(InterleavedAudio::from_slice(&input, 1)),
+            [(5, -1.0), (2, 1.0)],
+        );
+        assert_eq!(split, output);
+    }
+
+    #[test]
+    fn fixed_block_latency() {
+        let input = [1.0f32; 6];
+        let output = render_offline(
+            gain(),
+            config(4),
+            BlockSize::Fixed(2),
+            RenderInput::Audio(InterleavedAudio::from_slice(&input, 1)),
+            [(0, 1.0)],
+        );
+        // the last frames of the input come out in the tail
+        assert_eq!(
+            output.data(),
+            [0., 0., 0., 0., 1., 1., 2., 2., 3., 3., 4., 4., 5., 5., 6., 6.]
+        );
+
+        let silence = render_offline(
+            gain(),
+            config(4),
+            BlockSize::Variable,
+            RenderInput::Silence(5),
+            [],
+        );
+        assert_eq!(silence.num_frames(), 5);
+        assert!(silence.data().iter().all(|sample| *sample == 0.0));
+    }
+
+    #[cfg(feature = "rt-check")]
+    #[test]
+    fn allocations_are_not_violations() {
+        /// allocates in every callback, which is fine offline
+        struct Allocate;
+
+        impl AudioProcessor for Allocate {
+            type Message = ();
+
+            fn prepare(&mut self, _config: DeviceConfig) {}
+
+            fn message_process(&mut self, _message: Self::Message) {}
+
+            fn process(
+                &mut self,
+                _output: InterleavedAudioMut<'_, f32>,
+                _input: InterleavedAudio<'_, f32>,
+            ) {
+                drop(vec![0u8; 12345]);
+            }
+        }
+
+        render_offline(
+            Allocate,
+            config(4),
+            BlockSize::Variable,
+            RenderInput::Silence(8),
+            [],
+        );
+        let violations = crate::rt_check::take_violations();
+        assert!(!violations.iter().any(|violation| matches!(
+            violation.kind,
+            crate::rt_check::RtViolationKind::Allocation { size: 12345 }
+        )));
+    }
+}
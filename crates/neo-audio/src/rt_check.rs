@@ -0,0 +1,284 @@
+//! Debug tool that finds code which is not real-time safe.
+//!
+//! With the `rt-check` feature enabled, the audio thread is flagged while it runs
+//! `AudioProcessor::process` and `AudioProcessor::message_process`. If the binary installs the
+//! `RtCheckAllocator` as global allocator, every allocation and deallocation made there is
+//! recorded with a backtrace and reported when the audio is stopped. This slows down every
+//! allocation, so only use it while debugging.
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static GLOBAL: RtCheckAllocator = RtCheckAllocator::new(std::alloc::System);
+//! ```
+//!
+//! Blocking calls can't be detected from the outside. They are recorded for `MessageSender::send`
+//! on a full queue, for the `Mutex` and `RwLock` of this module, which can replace the ones of
+//! `std::sync` in a processor, and for everything that calls `report_blocking` before it blocks.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    backtrace::Backtrace,
+    cell::Cell,
+    fmt,
+    sync::{self, LockResult, MutexGuard, RwLockReadGuard, RwLockWriteGuard, TryLockResult},
+};
+
+/// violations that are recorded at most, to not run out of memory with a leaking processor
+const MAX_VIOLATIONS: usize = 256;
+
+static VIOLATIONS: sync::Mutex<Vec<RtViolation>> = sync::Mutex::new(Vec::new());
+
+thread_local! {
+    // the function of the processor that is currently running in this thread
+    static SCOPE: Cell<Option<&'static str>> = const { Cell::new(None) };
+    // set while a violation is recorded, so the recording does not record itself
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtViolationKind {
+    Allocation { size: usize },
+    Deallocation { size: usize },
+    Reallocation { old_size: usize, new_size: usize },
+    Blocking(&'static str),
+}
+
+/// Something that happened in the audio thread that is not real-time safe.
+pub struct RtViolation {
+    pub kind: RtViolationKind,
+    /// the function of the processor it happened in, e.g. `process`
+    pub scope: &'static str,
+    pub backtrace: Backtrace,
+}
+
+impl fmt::Display for RtViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            RtViolationKind::Allocation { size } => write!(f, "allocation of {size} bytes")?,
+            RtViolationKind::Deallocation { size } => write!(f, "deallocation of {size} bytes")?,
+            RtViolationKind::Reallocation { old_size, new_size } => {
+                write!(f, "reallocation from {old_size} to {new_size} bytes")?
+            }
+            RtViolationKind::Blocking(what) => write!(f, "blocking call: {what}")?,
+        }
+        write!(f, " in {}\n{}", self.scope, self.backtrace)
+    }
+}
+
+/// Flags the current thread as real-time thread until it is dropped.
+pub(crate) struct RtScope {
+    previous: Option<&'static str>,
+}
+
+impl RtScope {
+    pub fn enter(scope: &'static str) -> Self {
+        Self {
+            previous: SCOPE.with(|s| s.replace(Some(scope))),
+        }
+    }
+}
+
+impl Drop for RtScope {
+    fn drop(&mut self) {
+        SCOPE.with(|s| s.set(self.previous));
+    }
+}
+
+/// Call this before anything that can block, like locking a mutex. If it happens inside of a
+/// processor in the audio thread it is reported as violation.
+pub fn report_blocking(what: &'static str) {
+    record(RtViolationKind::Blocking(what));
+}
+
+/// A `std::sync::Mutex` that reports `lock` as blocking call. `try_lock` never blocks and is
+/// not reported.
+#[derive(Debug, Default)]
+pub struct Mutex<T: ?Sized>(sync::Mutex<T>);
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self(sync::Mutex::new(value))
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        self.0.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        report_blocking("Mutex::lock");
+        self.0.lock()
+    }
+
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        self.0.try_lock()
+    }
+
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.0.get_mut()
+    }
+}
+
+/// A `std::sync::RwLock` that reports `read` and `write` as blocking calls. `try_read` and
+/// `try_write` never block and are not reported.
+#[derive(Debug, Default)]
+pub struct RwLock<T: ?Sized>(sync::RwLock<T>);
+
+impl<T> RwLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self(sync::RwLock::new(value))
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        self.0.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        report_blocking("RwLock::read");
+        self.0.read()
+    }
+
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        report_blocking("RwLock::write");
+        self.0.write()
+    }
+
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        self.0.try_read()
+    }
+
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        self.0.try_write()
+    }
+
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.0.get_mut()
+    }
+}
+
+/// Returns all violations recorded so far and clears them.
+pub fn take_violations() -> Vec<RtViolation> {
+    let mut violations = VIOLATIONS.lock().unwrap_or_else(|e| e.into_inner());
+    std::mem::take(&mut *violations)
+}
+
+/// Prints all violations recorded so far to stderr and clears them.
+pub(crate) fn report() {
+    let violations = take_violations();
+    if violations.is_empty() {
+        return;
+    }
+    eprintln!(
+        "rt-check: {} real-time violations in the audio thread",
+        violations.len()
+    );
+    for violation in violations {
+        eprintln!("rt-check: {violation}");
+    }
+}
+
+fn record(kind: RtViolationKind) {
+    let Some(scope) = SCOPE.try_with(|s| s.get()).ok().flatten() else {
+        return;
+    };
+    // the panic hook holds the backtrace lock, capturing another one would deadlock
+    if std::thread::panicking() {
+        return;
+    }
+    if RECORDING.try_with(|r| r.replace(true)).unwrap_or(true) {
+        return;
+    }
+    let mut violations = VIOLATIONS.lock().unwrap_or_else(|e| e.into_inner());
+    if violations.len() < MAX_VIOLATIONS {
+        violations.push(RtViolation {
+            kind,
+            scope,
+            backtrace: Backtrace::force_capture(),
+        });
+    }
+    drop(violations);
+    let _ = RECORDING.try_with(|r| r.set(false));
+}
+
+/// Wraps another allocator and records all calls made in a flagged thread.
+pub struct RtCheckAllocator<A = System>(A);
+
+impl<A> RtCheckAllocator<A> {
+    pub const fn new(allocator: A) -> Self {
+        Self(allocator)
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for RtCheckAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(RtViolationKind::Allocation {
+            size: layout.size(),
+        });
+        self.0.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(RtViolationKind::Allocation {
+            size: layout.size(),
+        });
+        self.0.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        record(RtViolationKind::Deallocation {
+            size: layout.size(),
+        });
+        self.0.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(RtViolationKind::Reallocation {
+            old_size: layout.size(),
+            new_size,
+        });
+        self.0.realloc(ptr, layout, new_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static GLOBAL: RtCheckAllocator = RtCheckAllocator::new(System);
+
+    #[test]
+    fn records_only_in_scope() {
+        let _ = take_violations();
+
+        let outside = vec![0u8; 16];
+        {
+            let _scope = RtScope::enter("test");
+            let inside = vec![0u8; 32];
+            drop(inside);
+            report_blocking("mutex");
+            let lock = RwLock::new(0);
+            drop(lock.try_write());
+            drop(lock.read());
+        }
+        drop(outside);
+
+        let kinds = take_violations()
+            .into_iter()
+            .filter(|v| v.scope == "test")
+            .map(|v| v.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                RtViolationKind::Allocation { size: 32 },
+                RtViolationKind::Deallocation { size: 32 },
+                RtViolationKind::Blocking("mutex"),
+                RtViolationKind::Blocking("RwLock::read"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use std::any::TypeId;
+
+use realtime_tools::{
+    interleaved_audio::{InterleavedAudio, InterleavedAudioMut},
+    sample_format::{IntSample, I24},
+};
+
+use crate::engine::fitting_frames;
+
+/// The format the samples are exchanged with the audio device in.
+/// Independent of it, the engine hands `f32` or `f64` buffers to the processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SampleFormat {
+    #[default]
+    F32,
+    I16,
+    I32,
+}
+
+/// A sample type buffers can be converted to and from `f32`, which is the format the engine
+/// gets from the backends. Processors implement `AudioProcessor<f64>` to process in `f64`.
+/// Integer types are converted with `realtime_tools::sample_format`, without dither.
+pub trait Sample: Copy + Default + Send + 'static {
+    fn from_f32(value: f32) -> Self;
+    fn to_f32(self) -> f32;
+}
+
+impl Sample for f32 {
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+impl Sample for f64 {
+    fn from_f32(value: f32) -> Self {
+        value as f64
+    }
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+}
+
+impl Sample for i16 {
+    fn from_f32(value: f32) -> Self {
+        IntSample::from_float(value)
+    }
+
+    fn to_f32(self) -> f32 {
+        self.to_float()
+    }
+}
+
+impl Sample for I24 {
+    fn from_f32(value: f32) -> Self {
+        IntSample::from_float(value)
+    }
+
+    fn to_f32(self) -> f32 {
+        self.to_float()
+    }
+}
+
+impl Sample for i32 {
+    fn from_f32(value: f32) -> Self {
+        IntSample::from_float(value)
+    }
+
+    fn to_f32(self) -> f32 {
+        self.to_float()
+    }
+}
+
+/// Converts the buffers of one sample type into another and back, e.g. from the `f32` buffers
+/// of the backend to an `f64` processor or from an `i16` device to the `f32` callback.
+/// All memory is allocated in `new`, buffers that are longer are processed in chunks.
+pub(crate) struct Converter<T: Sample> {
+    max_frames: usize,
+    output: Vec<T>,
+    input: Vec<T>,
+}
+
+impl<T: Sample> Converter<T> {
+    pub fn new(max_frames: usize, num_output_ch: usize, num_input_ch: usize) -> Self {
+        let max_frames = max_frames.max(1);
+        Self {
+            max_frames,
+            output: vec![T::default(); max_frames * num_output_ch],
+            input: vec![T::default(); max_frames * num_input_ch],
+        }
+    }
+
+    /// runs `process` with buffers of `T`, converting the input before and the output after.
+    /// if both are the same type, the buffers are passed through without copying.
+    pub fn process<S: Sample>(
+        &mut self,
+        mut output: InterleavedAudioMut<'_, S>,
+        input: InterleavedAudio<'_, S>,
+        mut process: impl FnMut(InterleavedAudioMut<'_, T>, InterleavedAudio<'_, T>),
+    ) {
+        let (num_output_ch, num_input_ch) = (output.num_channels(), input.num_channels());
+        if let (Some(native_output), Some(native_input)) = (
+            cast_mut::<S, T>(output.data_mut()),
+            cast::<S, T>(input.data()),
+        ) {
+            process(
+                InterleavedAudioMut::from_slice(native_output, num_output_ch),
+                InterleavedAudio::from_slice(native_input, num_input_ch),
+            );
+            return;
+        }
+
+        // input buffers can be shorter than the output, e.g. when there is no input device
+        let num_frames = if num_output_ch > 0 {
+            output.num_frames()
+        } else {
+            input.num_frames()
+        };
+        // buffers with more channels than the converter was created for are split further
+        let max_frames = self
+            .max_frames
+            .min(fitting_frames(&self.output, num_output_ch))
+            .min(fitting_frames(&self.input, num_input_ch));
+        if max_frames == 0 {
+            output.data_mut().fill(S::default());
+            return;
+        }
+        let mut start = 0;
+        while start < num_frames {
+            let end = (start + max_frames).min(num_frames);
+            let len = end - start;
+            let input_chunk = input.slice_frames(start..end);
+            let converted_input = &mut self.input[..input_chunk.data().len()];
+            convert(converted_input, input_chunk.data());
+
+            let converted_output = &mut self.output[..len * num_output_ch];
+            process(
+                InterleavedAudioMut::from_slice(&mut *converted_output, num_output_ch),
+                InterleavedAudio::from_slice(converted_input, num_input_ch),
+            );
+            convert(
+                output.slice_frames_mut(start..end).data_mut(),
+                converted_output,
+            );
+            start = end;
+        }
+    }
+}
+
+fn convert<S: Sample, T: Sample>(dest: &mut [T], source: &[S]) {
+    for (d, s) in dest.iter_mut().zip(source) {
+        *d = T::from_f32(s.to_f32());
+    }
+}
+
+fn cast<S: Sample, T: Sample>(data: &[S]) -> Option<&[T]> {
+    if TypeId::of::<S>() != TypeId::of::<T>() {
+        return None;
+    }
+    // SAFETY: the types are identical, so the layout of the slice doesn't change
+    Some(unsafe { std::slice::from_raw_parts(data.as_ptr() as *const T, data.len()) })
+}
+
+fn cast_mut<S: Sample, T: Sample>(data: &mut [S]) -> Option<&mut [T]> {
+    if TypeId::of::<S>() != TypeId::of::<T>() {
+        return None;
+    }
+    // SAFETY: the types are identical, so the layout of the slice doesn't change
+    Some(unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut T, data.len()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_samples() {
+        assert_eq!(i16::from_f32(-1.0), i16::MIN);
+        assert_eq!(i16::from_f32(1.0), i16::MAX);
+        assert_eq!(i16::from_f32(0.5).to_f32(), 0.5);
+        assert_eq!(i32::from_f32(1.0), i32::MAX);
+        assert_eq!(i32::from_f32(-0.25).to_f32(), -0.25);
+        assert_eq!(I24::from_f32(-1.0).get(), I24::MIN);
+    }
+
+    #[test]
+    fn converts_in_chunks() {
+        let mut converter = Converter::<f64>::new(3, 2, 1);
+        let input = [0.5f32, -0.5, 0.25, 1.0, 0.0];
+        let mut output = [0.0f32; 10];
+        let mut chunks = Vec::new();
+        converter.process(
+            InterleavedAudioMut::from_slice(&mut output, 2),
+            InterleavedAudio::from_slice(&input, 1),
+            |mut output, input| {
+                chunks.push(output.num_frames());
+                for (o, i) in output.frames_iter_mut().zip(input.data()) {
+                    o.fill(i * 2.0);
+                }
+            },
+        );
+        assert_eq!(chunks, [3, 2]);
+        assert_eq!(output, [1.0, 1.0, -1.0, -1.0, 0.5, 0.5, 2.0, 2.0, 0.0, 0.0]);
+
+        // the same type is passed through in one piece
+        let mut converter = Converter::<f32>::new(3, 1, 0);
+        converter.process(
+            InterleavedAudioMut::from_slice(&mut output, 1),
+            InterleavedAudio::from_slice(&[], 0),
+            |output, _| assert_eq!(output.num_frames(), 10),
+        );
+
+        // more channels than the converter was created for are processed in smaller chunks
+        let mut converter = Converter::<f64>::new(4, 1, 1);
+        let mut chunks = Vec::new();
+        converter.process(
+            InterleavedAudioMut::from_slice(&mut output, 2),
+            InterleavedAudio::from_slice(&input[..4], 2),
+            |output, _| chunks.push(output.num_frames()),
+        );
+        assert_eq!(chunks, [2, 2, 1]);
+        assert!(output.iter().all(|sample| *sample == 0.0));
+    }
+}
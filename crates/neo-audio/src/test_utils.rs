@@ -0,0 +1,37 @@
+//! Helpers shared by the tests of the processors and the graph.
+
+use crate::prelude::*;
+
+/// 16 frames per callback at 1000 Hz, so a frame is a millisecond
+pub(crate) fn config(num_output_ch: u16, num_input_ch: u16) -> DeviceConfig {
+    DeviceConfig::new(1000, 16, num_output_ch, num_input_ch)
+}
+
+/// the mono output of a processor without input
+pub(crate) fn process<P: AudioProcessor>(processor: &mut P, num_frames: usize) -> Vec<f32> {
+    process_channels(processor, 1, num_frames)
+}
+
+/// the interleaved output of a processor without input
+pub(crate) fn process_channels<P: AudioProcessor>(
+    processor: &mut P,
+    num_channels: usize,
+    num_frames: usize,
+) -> Vec<f32> {
+    let mut output = vec![0.0; num_frames * num_channels];
+    processor.process(
+        InterleavedAudioMut::from_slice(&mut output, num_channels),
+        InterleavedAudio::from_slice(&[], 0),
+    );
+    output
+}
+
+/// the mono output of a processor for the mono input
+pub(crate) fn process_input<P: AudioProcessor>(processor: &mut P, input: &[f32]) -> Vec<f32> {
+    let mut output = vec![0.0; input.len()];
+    processor.process(
+        InterleavedAudioMut::from_slice(&mut output, 1),
+        InterleavedAudio::from_slice(input, 1),
+    );
+    output
+}
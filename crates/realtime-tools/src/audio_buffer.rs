@@ -0,0 +1,271 @@
+use crate::{
+    interleaved_audio::{InterleavedAudio, InterleavedAudioMut},
+    planar_audio::{deinterleave, interleave, PlanarAudio, PlanarAudioMut},
+};
+
+/// How the samples of an `AudioBuffer` are ordered in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BufferLayout {
+    /// all channels of a frame are next to each other, like the buffers of the audio callback
+    #[default]
+    Interleaved,
+    /// all frames of a channel are next to each other
+    Planar,
+}
+
+/// Owned audio that borrows as interleaved or planar views.
+/// Allocate it with `new` or `resize` in `prepare`, so the audio thread only uses the views.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AudioBuffer<T: Copy> {
+    data: Vec<T>,
+    layout: BufferLayout,
+    num_channels: usize,
+    num_frames: usize,
+}
+
+impl<T: Copy + Default> AudioBuffer<T> {
+    /// creates a buffer filled with the default value of the sample type
+    pub fn new(layout: BufferLayout, num_channels: usize, num_frames: usize) -> Self {
+        Self {
+            data: vec![T::default(); num_channels * num_frames],
+            layout,
+            num_channels,
+            num_frames,
+        }
+    }
+
+    pub fn interleaved(num_channels: usize, num_frames: usize) -> Self {
+        Self::new(BufferLayout::Interleaved, num_channels, num_frames)
+    }
+
+    pub fn planar(num_channels: usize, num_frames: usize) -> Self {
+        Self::new(BufferLayout::Planar, num_channels, num_frames)
+    }
+
+    /// changes the size and resets all samples to the default value.
+    /// this only allocates if the buffer grows beyond its capacity, but should still be called in
+    /// `prepare` and not in the audio thread.
+    pub fn resize(&mut self, num_channels: usize, num_frames: usize) {
+        self.data.clear();
+        self.data.resize(num_channels * num_frames, T::default());
+        self.num_channels = num_channels;
+        self.num_frames = num_frames;
+    }
+
+    /// sets all samples to the default value
+    pub fn clear(&mut self) {
+        self.data.fill(T::default());
+    }
+}
+
+impl<T: Copy> AudioBuffer<T> {
+    /// takes the samples, which have to be ordered in the given layout
+    pub fn from_vec(data: Vec<T>, layout: BufferLayout, num_channels: usize) -> Self {
+        let num_frames = data.len().checked_div(num_channels).unwrap_or(0);
+        assert_eq!(
+            num_frames * num_channels,
+            data.len(),
+            "number of samples is not divisible by the number of channels"
+        );
+        Self {
+            data,
+            layout,
+            num_channels,
+            num_frames,
+        }
+    }
+
+    pub fn layout(&self) -> BufferLayout {
+        self.layout
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    pub fn fill(&mut self, value: T) {
+        self.data.fill(value);
+    }
+
+    pub fn sample(&self, channel: usize, frame: usize) -> T {
+        self.data[self.index(channel, frame)]
+    }
+
+    pub fn set_sample(&mut self, channel: usize, frame: usize, value: T) {
+        let index = self.index(channel, frame);
+        self.data[index] = value;
+    }
+
+    /// returns `None` if the buffer is planar
+    pub fn as_interleaved(&self) -> Option<InterleavedAudio<'_, T>> {
+        (self.layout == BufferLayout::Interleaved)
+            .then(|| InterleavedAudio::from_slice(&self.data, self.num_channels))
+    }
+
+    /// returns `None` if the buffer is planar
+    pub fn as_interleaved_mut(&mut self) -> Option<InterleavedAudioMut<'_, T>> {
+        (self.layout == BufferLayout::Interleaved)
+            .then(|| InterleavedAudioMut::from_slice(&mut self.data, self.num_channels))
+    }
+
+    /// returns `None` if the buffer is interleaved
+    pub fn as_planar(&self) -> Option<PlanarAudio<'_, T>> {
+        (self.layout == BufferLayout::Planar)
+            .then(|| PlanarAudio::from_slice(&self.data, self.num_channels))
+    }
+
+    /// returns `None` if the buffer is interleaved
+    pub fn as_planar_mut(&mut self) -> Option<PlanarAudioMut<'_, T>> {
+        (self.layout == BufferLayout::Planar)
+            .then(|| PlanarAudioMut::from_slice(&mut self.data, self.num_channels))
+    }
+
+    /// copies interleaved audio into the buffer, converting to its layout.
+    /// only the channels and frames both have are copied.
+    pub fn copy_from_interleaved(&mut self, audio: &InterleavedAudio<'_, T>)
+    where
+        T: 'static,
+    {
+        match self.layout {
+            BufferLayout::Interleaved => copy_frames(
+                &mut InterleavedAudioMut::from_slice(&mut self.data, self.num_channels),
+                audio,
+            ),
+            BufferLayout::Planar => deinterleave(
+                audio,
+                &mut PlanarAudioMut::from_slice(&mut self.data, self.num_channels),
+            ),
+        }
+    }
+
+    /// copies the buffer into interleaved audio, converting from its layout.
+    /// only the channels and frames both have are copied.
+    pub fn copy_to_interleaved(&self, audio: &mut InterleavedAudioMut<'_, T>)
+    where
+        T: 'static,
+    {
+        match self.layout {
+            BufferLayout::Interleaved => copy_frames(
+                audio,
+                &InterleavedAudio::from_slice(&self.data, self.num_channels),
+            ),
+            BufferLayout::Planar => interleave(
+                &PlanarAudio::from_slice(&self.data, self.num_channels),
+                audio,
+            ),
+        }
+    }
+
+    fn index(&self, channel: usize, frame: usize) -> usize {
+        assert!(channel < self.num_channels && frame < self.num_frames);
+        match self.layout {
+            BufferLayout::Interleaved => frame * self.num_channels + channel,
+            BufferLayout::Planar => channel * self.num_frames + frame,
+        }
+    }
+}
+
+fn copy_frames<T: Copy>(dest: &mut InterleavedAudioMut<'_, T>, source: &InterleavedAudio<'_, T>) {
+    for (dest, source) in dest.frames_iter_mut().zip(source.frames_iter()) {
+        let num_ch = dest.len().min(source.len());
+        dest[..num_ch].copy_from_slice(&source[..num_ch]);
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<T: Copy> AudioBuffer<T> {
+    /// a view with the shape (channels, frames), like the audio of the `PlayerProcessor`,
+    /// for both layouts without copying
+    pub fn as_array(&self) -> ndarray::ArrayView2<'_, T> {
+        ndarray::ArrayView2::from_shape(self.array_shape(), &self.data)
+            .expect("shape matches the buffer")
+    }
+
+    /// a mutable view with the shape (channels, frames), for both layouts without copying
+    pub fn as_array_mut(&mut self) -> ndarray::ArrayViewMut2<'_, T> {
+        let shape = self.array_shape();
+        ndarray::ArrayViewMut2::from_shape(shape, &mut self.data).expect("shape matches the buffer")
+    }
+
+    /// copies an array with the shape (channels, frames) into a new buffer
+    pub fn from_array(array: ndarray::ArrayView2<'_, T>, layout: BufferLayout) -> Self {
+        let data = match layout {
+            BufferLayout::Planar => array.iter().copied().collect(),
+            BufferLayout::Interleaved => array.t().iter().copied().collect(),
+        };
+        Self {
+            data,
+            layout,
+            num_channels: array.nrows(),
+            num_frames: array.ncols(),
+        }
+    }
+
+    fn array_shape(&self) -> ndarray::StrideShape<ndarray::Ix2> {
+        use ndarray::ShapeBuilder;
+
+        let shape = (self.num_channels, self.num_frames);
+        match self.layout {
+            BufferLayout::Interleaved => shape.strides((1, self.num_channels)),
+            BufferLayout::Planar => shape.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts() {
+        let mut interleaved =
+            AudioBuffer::from_vec(vec![1, 4, 2, 5, 3, 6], BufferLayout::Interleaved, 2);
+        let mut planar = AudioBuffer::planar(2, 3);
+        planar.copy_from_interleaved(&interleaved.as_interleaved().unwrap());
+        assert_eq!(planar.data(), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(planar.sample(1, 0), interleaved.sample(1, 0));
+        assert!(planar.as_interleaved().is_none());
+
+        planar.set_sample(0, 2, 7);
+        planar.copy_to_interleaved(&mut interleaved.as_interleaved_mut().unwrap());
+        assert_eq!(interleaved.data(), [1, 4, 2, 5, 7, 6]);
+
+        planar.resize(1, 2);
+        assert_eq!(planar.as_planar().unwrap().channel(0), [0, 0]);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn array_views() {
+        let interleaved =
+            AudioBuffer::from_vec(vec![1, 4, 2, 5, 3, 6], BufferLayout::Interleaved, 2);
+        let planar = AudioBuffer::from_vec(vec![1, 2, 3, 4, 5, 6], BufferLayout::Planar, 2);
+        let expected = ndarray::array![[1, 2, 3], [4, 5, 6]];
+        assert_eq!(interleaved.as_array(), expected);
+        assert_eq!(planar.as_array(), expected);
+        assert_eq!(
+            AudioBuffer::from_array(expected.view(), BufferLayout::Interleaved),
+            interleaved
+        );
+
+        let mut planar = planar;
+        planar.as_array_mut()[[1, 0]] = 0;
+        assert_eq!(planar.data(), [1, 2, 3, 0, 5, 6]);
+    }
+}
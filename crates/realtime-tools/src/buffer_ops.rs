@@ -0,0 +1,638 @@
+//! Vectorised operations on audio buffers.
+//!
+//! On x86_64 the functions use SSE2 and on aarch64 NEON, which both are always available on these
+//! architectures. Other architectures use the `scalar` versions, which are also the reference the
+//! vectorised versions are tested against.
+
+use crate::interleaved_audio::{InterleavedAudio, InterleavedAudioMut};
+/// the planar conversions, which use the vectorised versions for stereo `f32` audio
+pub use crate::planar_audio::{deinterleave, interleave};
+
+#[cfg(target_arch = "aarch64")]
+use neon as simd;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+use scalar as simd;
+#[cfg(target_arch = "x86_64")]
+use sse2 as simd;
+
+/// multiplies all samples with the gain
+pub fn apply_gain(data: &mut [f32], gain: f32) {
+    simd::apply_gain(data, gain);
+}
+
+/// fades the gain linearly from `start` at the first frame towards `end` after the last frame,
+/// so consecutive buffers can continue the ramp without a step
+pub fn apply_ramped_gain(data: &mut [f32], num_channels: usize, start: f32, end: f32) {
+    simd::apply_ramped_gain(data, num_channels, start, end);
+}
+
+/// adds the source multiplied with the gain to the destination
+pub fn mix_add(dest: &mut [f32], source: &[f32], gain: f32) {
+    simd::mix_add(dest, source, gain);
+}
+
+/// copies as many samples as both slices have
+pub fn copy(dest: &mut [f32], source: &[f32]) {
+    let len = dest.len().min(source.len());
+    dest[..len].copy_from_slice(&source[..len]);
+}
+
+pub fn clear(data: &mut [f32]) {
+    data.fill(0.0);
+}
+
+/// the largest absolute sample value
+pub fn peak(data: &[f32]) -> f32 {
+    simd::peak(data)
+}
+
+/// the root mean square of all samples
+pub fn rms(data: &[f32]) -> f32 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    (simd::sum_of_squares(data) / data.len() as f32).sqrt()
+}
+
+/// sums the channels of every interleaved frame into one sample of the destination
+pub fn channel_sum(dest: &mut [f32], source: &[f32], num_channels: usize) {
+    simd::channel_sum(dest, source, num_channels);
+}
+
+pub(crate) fn interleave_stereo(dest: &mut [f32], left: &[f32], right: &[f32]) {
+    simd::interleave_stereo(dest, left, right);
+}
+
+pub(crate) fn deinterleave_stereo(left: &mut [f32], right: &mut [f32], source: &[f32]) {
+    simd::deinterleave_stereo(left, right, source);
+}
+
+impl InterleavedAudioMut<'_, f32> {
+    pub fn apply_gain(&mut self, gain: f32) {
+        apply_gain(self.data_mut(), gain);
+    }
+
+    /// see `buffer_ops::apply_ramped_gain`
+    pub fn apply_ramped_gain(&mut self, start: f32, end: f32) {
+        let num_channels = self.num_channels();
+        apply_ramped_gain(self.data_mut(), num_channels, start, end);
+    }
+
+    /// adds the source multiplied with the gain, both need the same number of channels
+    pub fn mix_add(&mut self, source: &InterleavedAudio<'_, f32>, gain: f32) {
+        debug_assert_eq!(self.num_channels(), source.num_channels());
+        mix_add(self.data_mut(), source.data(), gain);
+    }
+
+    pub fn clear(&mut self) {
+        clear(self.data_mut());
+    }
+
+    pub fn peak(&self) -> f32 {
+        peak(self.data())
+    }
+
+    pub fn rms(&self) -> f32 {
+        rms(self.data())
+    }
+}
+
+impl InterleavedAudio<'_, f32> {
+    pub fn peak(&self) -> f32 {
+        peak(self.data())
+    }
+
+    pub fn rms(&self) -> f32 {
+        rms(self.data())
+    }
+}
+
+/// Plain versions of all operations, used on architectures without vectorised versions.
+pub mod scalar {
+    pub fn apply_gain(data: &mut [f32], gain: f32) {
+        data.iter_mut().for_each(|sample| *sample *= gain);
+    }
+
+    pub fn apply_ramped_gain(data: &mut [f32], num_channels: usize, start: f32, end: f32) {
+        apply_ramped_gain_from(data, num_channels, start, end, 0);
+    }
+
+    /// continues the ramp of `apply_ramped_gain` from a frame, used for the rest of a buffer
+    pub(crate) fn apply_ramped_gain_from(
+        data: &mut [f32],
+        num_channels: usize,
+        start: f32,
+        end: f32,
+        first_frame: usize,
+    ) {
+        let num_channels = num_channels.max(1);
+        let num_frames = data.len() / num_channels;
+        if num_frames == 0 {
+            return;
+        }
+        let step = (end - start) / num_frames as f32;
+        for (frame, samples) in data.chunks_mut(num_channels).enumerate().skip(first_frame) {
+            let gain = start + step * frame as f32;
+            samples.iter_mut().for_each(|sample| *sample *= gain);
+        }
+    }
+
+    pub fn mix_add(dest: &mut [f32], source: &[f32], gain: f32) {
+        dest.iter_mut()
+            .zip(source.iter())
+            .for_each(|(d, s)| *d += *s * gain);
+    }
+
+    pub fn peak(data: &[f32]) -> f32 {
+        data.iter().fold(0.0, |peak, sample| sample.abs().max(peak))
+    }
+
+    pub fn sum_of_squares(data: &[f32]) -> f32 {
+        data.iter().map(|sample| sample * sample).sum()
+    }
+
+    pub fn rms(data: &[f32]) -> f32 {
+        if data.is_empty() {
+            return 0.0;
+        }
+        (sum_of_squares(data) / data.len() as f32).sqrt()
+    }
+
+    pub fn channel_sum(dest: &mut [f32], source: &[f32], num_channels: usize) {
+        for (d, frame) in dest
+            .iter_mut()
+            .zip(source.chunks_exact(num_channels.max(1)))
+        {
+            *d = frame.iter().sum();
+        }
+    }
+
+    pub fn interleave_stereo(dest: &mut [f32], left: &[f32], right: &[f32]) {
+        for ((frame, l), r) in dest.chunks_exact_mut(2).zip(left).zip(right) {
+            frame[0] = *l;
+            frame[1] = *r;
+        }
+    }
+
+    pub fn deinterleave_stereo(left: &mut [f32], right: &mut [f32], source: &[f32]) {
+        for ((frame, l), r) in source.chunks_exact(2).zip(left).zip(right) {
+            *l = frame[0];
+            *r = frame[1];
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    use super::scalar;
+
+    const LANES: usize = 4;
+
+    pub fn apply_gain(data: &mut [f32], gain: f32) {
+        let mut chunks = data.chunks_exact_mut(LANES);
+        // SAFETY: sse2 is part of the x86_64 baseline and every chunk holds 4 floats
+        unsafe {
+            let gain = _mm_set1_ps(gain);
+            for chunk in &mut chunks {
+                let samples = _mm_loadu_ps(chunk.as_ptr());
+                _mm_storeu_ps(chunk.as_mut_ptr(), _mm_mul_ps(samples, gain));
+            }
+        }
+        scalar::apply_gain(chunks.into_remainder(), gain);
+    }
+
+    pub fn apply_ramped_gain(data: &mut [f32], num_channels: usize, start: f32, end: f32) {
+        // a vector only holds whole frames for up to 4 channels that divide the lanes
+        if !matches!(num_channels, 1 | 2 | 4) {
+            return scalar::apply_ramped_gain(data, num_channels, start, end);
+        }
+        let num_frames = data.len() / num_channels;
+        if num_frames == 0 {
+            return;
+        }
+        let step = (end - start) / num_frames as f32;
+        let frames_per_chunk = LANES / num_channels;
+        let mut chunks = data.chunks_exact_mut(LANES);
+        let mut frame = 0;
+        // SAFETY: sse2 is part of the x86_64 baseline and every chunk holds 4 floats
+        unsafe {
+            let offsets = match num_channels {
+                1 => _mm_setr_ps(0.0, 1.0, 2.0, 3.0),
+                2 => _mm_setr_ps(0.0, 0.0, 1.0, 1.0),
+                _ => _mm_setzero_ps(),
+            };
+            for chunk in &mut chunks {
+                // same formula as the scalar version, so the results are identical
+                let frames = _mm_add_ps(_mm_set1_ps(frame as f32), offsets);
+                let gain = _mm_add_ps(_mm_set1_ps(start), _mm_mul_ps(_mm_set1_ps(step), frames));
+                let samples = _mm_loadu_ps(chunk.as_ptr());
+                _mm_storeu_ps(chunk.as_mut_ptr(), _mm_mul_ps(samples, gain));
+                frame += frames_per_chunk;
+            }
+        }
+        scalar::apply_ramped_gain_from(data, num_channels, start, end, frame);
+    }
+
+    pub fn mix_add(dest: &mut [f32], source: &[f32], gain: f32) {
+        let len = dest.len().min(source.len());
+        let (dest, source) = (&mut dest[..len], &source[..len]);
+        let mut dest_chunks = dest.chunks_exact_mut(LANES);
+        let mut source_chunks = source.chunks_exact(LANES);
+        // SAFETY: sse2 is part of the x86_64 baseline and every chunk holds 4 floats
+        unsafe {
+            let gain = _mm_set1_ps(gain);
+            for (d, s) in (&mut dest_chunks).zip(&mut source_chunks) {
+                let sum = _mm_add_ps(
+                    _mm_loadu_ps(d.as_ptr()),
+                    _mm_mul_ps(_mm_loadu_ps(s.as_ptr()), gain),
+                );
+                _mm_storeu_ps(d.as_mut_ptr(), sum);
+            }
+        }
+        scalar::mix_add(
+            dest_chunks.into_remainder(),
+            source_chunks.remainder(),
+            gain,
+        );
+    }
+
+    pub fn peak(data: &[f32]) -> f32 {
+        let mut chunks = data.chunks_exact(LANES);
+        // SAFETY: sse2 is part of the x86_64 baseline and every chunk holds 4 floats
+        let peak = unsafe {
+            let sign = _mm_set1_ps(-0.0);
+            let mut peak = _mm_setzero_ps();
+            for chunk in &mut chunks {
+                let samples = _mm_andnot_ps(sign, _mm_loadu_ps(chunk.as_ptr()));
+                peak = _mm_max_ps(peak, samples);
+            }
+            let mut lanes = [0.0; LANES];
+            _mm_storeu_ps(lanes.as_mut_ptr(), peak);
+            lanes
+        };
+        peak.into_iter()
+            .fold(scalar::peak(chunks.remainder()), f32::max)
+    }
+
+    pub fn sum_of_squares(data: &[f32]) -> f32 {
+        let mut chunks = data.chunks_exact(LANES);
+        // SAFETY: sse2 is part of the x86_64 baseline and every chunk holds 4 floats
+        let sums = unsafe {
+            let mut sum = _mm_setzero_ps();
+            for chunk in &mut chunks {
+                let samples = _mm_loadu_ps(chunk.as_ptr());
+                sum = _mm_add_ps(sum, _mm_mul_ps(samples, samples));
+            }
+            let mut lanes = [0.0; LANES];
+            _mm_storeu_ps(lanes.as_mut_ptr(), sum);
+            lanes
+        };
+        sums.iter().sum::<f32>() + scalar::sum_of_squares(chunks.remainder())
+    }
+
+    pub fn channel_sum(dest: &mut [f32], source: &[f32], num_channels: usize) {
+        if num_channels != 2 {
+            return scalar::channel_sum(dest, source, num_channels);
+        }
+        let num_frames = dest.len().min(source.len() / 2);
+        let (dest, source) = (&mut dest[..num_frames], &source[..num_frames * 2]);
+        let mut dest_chunks = dest.chunks_exact_mut(LANES);
+        let mut source_chunks = source.chunks_exact(LANES * 2);
+        // SAFETY: sse2 is part of the x86_64 baseline, the chunks hold 4 and 8 floats
+        unsafe {
+            for (d, s) in (&mut dest_chunks).zip(&mut source_chunks) {
+                let a = _mm_loadu_ps(s.as_ptr());
+                let b = _mm_loadu_ps(s.as_ptr().add(LANES));
+                let left = _mm_shuffle_ps::<0b10_00_10_00>(a, b);
+                let right = _mm_shuffle_ps::<0b11_01_11_01>(a, b);
+                _mm_storeu_ps(d.as_mut_ptr(), _mm_add_ps(left, right));
+            }
+        }
+        scalar::channel_sum(dest_chunks.into_remainder(), source_chunks.remainder(), 2);
+    }
+
+    pub fn interleave_stereo(dest: &mut [f32], left: &[f32], right: &[f32]) {
+        let num_frames = left.len().min(right.len()).min(dest.len() / 2);
+        let (dest, left, right) = (
+            &mut dest[..num_frames * 2],
+            &left[..num_frames],
+            &right[..num_frames],
+        );
+        let mut dest_chunks = dest.chunks_exact_mut(LANES * 2);
+        let mut left_chunks = left.chunks_exact(LANES);
+        let mut right_chunks = right.chunks_exact(LANES);
+        // SAFETY: sse2 is part of the x86_64 baseline, the chunks hold 8 and 4 floats
+        unsafe {
+            for ((d, l), r) in (&mut dest_chunks)
+                .zip(&mut left_chunks)
+                .zip(&mut right_chunks)
+            {
+                let l = _mm_loadu_ps(l.as_ptr());
+                let r = _mm_loadu_ps(r.as_ptr());
+                _mm_storeu_ps(d.as_mut_ptr(), _mm_unpacklo_ps(l, r));
+                _mm_storeu_ps(d.as_mut_ptr().add(LANES), _mm_unpackhi_ps(l, r));
+            }
+        }
+        scalar::interleave_stereo(
+            dest_chunks.into_remainder(),
+            left_chunks.remainder(),
+            right_chunks.remainder(),
+        );
+    }
+
+    pub fn deinterleave_stereo(left: &mut [f32], right: &mut [f32], source: &[f32]) {
+        let num_frames = left.len().min(right.len()).min(source.len() / 2);
+        let (left, right, source) = (
+            &mut left[..num_frames],
+            &mut right[..num_frames],
+            &source[..num_frames * 2],
+        );
+        let mut left_chunks = left.chunks_exact_mut(LANES);
+        let mut right_chunks = right.chunks_exact_mut(LANES);
+        let mut source_chunks = source.chunks_exact(LANES * 2);
+        // SAFETY: sse2 is part of the x86_64 baseline, the chunks hold 4 and 8 floats
+        unsafe {
+            for ((l, r), s) in (&mut left_chunks)
+                .zip(&mut right_chunks)
+                .zip(&mut source_chunks)
+            {
+                let a = _mm_loadu_ps(s.as_ptr());
+                let b = _mm_loadu_ps(s.as_ptr().add(LANES));
+                _mm_storeu_ps(l.as_mut_ptr(), _mm_shuffle_ps::<0b10_00_10_00>(a, b));
+                _mm_storeu_ps(r.as_mut_ptr(), _mm_shuffle_ps::<0b11_01_11_01>(a, b));
+            }
+        }
+        scalar::deinterleave_stereo(
+            left_chunks.into_remainder(),
+            right_chunks.into_remainder(),
+            source_chunks.remainder(),
+        );
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::scalar;
+
+    const LANES: usize = 4;
+
+    pub fn apply_gain(data: &mut [f32], gain: f32) {
+        let mut chunks = data.chunks_exact_mut(LANES);
+        // SAFETY: neon is part of the aarch64 baseline and every chunk holds 4 floats
+        unsafe {
+            for chunk in &mut chunks {
+                let samples = vld1q_f32(chunk.as_ptr());
+                vst1q_f32(chunk.as_mut_ptr(), vmulq_n_f32(samples, gain));
+            }
+        }
+        scalar::apply_gain(chunks.into_remainder(), gain);
+    }
+
+    pub fn apply_ramped_gain(data: &mut [f32], num_channels: usize, start: f32, end: f32) {
+        // a vector only holds whole frames for up to 4 channels that divide the lanes
+        if !matches!(num_channels, 1 | 2 | 4) {
+            return scalar::apply_ramped_gain(data, num_channels, start, end);
+        }
+        let num_frames = data.len() / num_channels;
+        if num_frames == 0 {
+            return;
+        }
+        let step = (end - start) / num_frames as f32;
+        let frames_per_chunk = LANES / num_channels;
+        let offsets: [f32; LANES] = match num_channels {
+            1 => [0.0, 1.0, 2.0, 3.0],
+            2 => [0.0, 0.0, 1.0, 1.0],
+            _ => [0.0; LANES],
+        };
+        let mut chunks = data.chunks_exact_mut(LANES);
+        let mut frame = 0;
+        // SAFETY: neon is part of the aarch64 baseline and every chunk holds 4 floats
+        unsafe {
+            let offsets = vld1q_f32(offsets.as_ptr());
+            for chunk in &mut chunks {
+                // same formula as the scalar version, so the results are identical
+                let frames = vaddq_f32(vdupq_n_f32(frame as f32), offsets);
+                let gain = vaddq_f32(vdupq_n_f32(start), vmulq_n_f32(frames, step));
+                let samples = vld1q_f32(chunk.as_ptr());
+                vst1q_f32(chunk.as_mut_ptr(), vmulq_f32(samples, gain));
+                frame += frames_per_chunk;
+            }
+        }
+        scalar::apply_ramped_gain_from(data, num_channels, start, end, frame);
+    }
+
+    pub fn mix_add(dest: &mut [f32], source: &[f32], gain: f32) {
+        let len = dest.len().min(source.len());
+        let (dest, source) = (&mut dest[..len], &source[..len]);
+        let mut dest_chunks = dest.chunks_exact_mut(LANES);
+        let mut source_chunks = source.chunks_exact(LANES);
+        // SAFETY: neon is part of the aarch64 baseline and every chunk holds 4 floats
+        unsafe {
+            for (d, s) in (&mut dest_chunks).zip(&mut source_chunks) {
+                let scaled = vmulq_n_f32(vld1q_f32(s.as_ptr()), gain);
+                vst1q_f32(d.as_mut_ptr(), vaddq_f32(vld1q_f32(d.as_ptr()), scaled));
+            }
+        }
+        scalar::mix_add(
+            dest_chunks.into_remainder(),
+            source_chunks.remainder(),
+            gain,
+        );
+    }
+
+    pub fn peak(data: &[f32]) -> f32 {
+        let mut chunks = data.chunks_exact(LANES);
+        // SAFETY: neon is part of the aarch64 baseline and every chunk holds 4 floats
+        let peak = unsafe {
+            let mut peak = vdupq_n_f32(0.0);
+            for chunk in &mut chunks {
+                peak = vmaxq_f32(peak, vabsq_f32(vld1q_f32(chunk.as_ptr())));
+            }
+            vmaxvq_f32(peak)
+        };
+        peak.max(scalar::peak(chunks.remainder()))
+    }
+
+    pub fn sum_of_squares(data: &[f32]) -> f32 {
+        let mut chunks = data.chunks_exact(LANES);
+        // SAFETY: neon is part of the aarch64 baseline and every chunk holds 4 floats
+        let sum = unsafe {
+            let mut sum = vdupq_n_f32(0.0);
+            for chunk in &mut chunks {
+                let samples = vld1q_f32(chunk.as_ptr());
+                sum = vaddq_f32(sum, vmulq_f32(samples, samples));
+            }
+            vaddvq_f32(sum)
+        };
+        sum + scalar::sum_of_squares(chunks.remainder())
+    }
+
+    pub fn channel_sum(dest: &mut [f32], source: &[f32], num_channels: usize) {
+        if num_channels != 2 {
+            return scalar::channel_sum(dest, source, num_channels);
+        }
+        let num_frames = dest.len().min(source.len() / 2);
+        let (dest, source) = (&mut dest[..num_frames], &source[..num_frames * 2]);
+        let mut dest_chunks = dest.chunks_exact_mut(LANES);
+        let mut source_chunks = source.chunks_exact(LANES * 2);
+        // SAFETY: neon is part of the aarch64 baseline, the chunks hold 4 and 8 floats
+        unsafe {
+            for (d, s) in (&mut dest_chunks).zip(&mut source_chunks) {
+                let frames = vld2q_f32(s.as_ptr());
+                vst1q_f32(d.as_mut_ptr(), vaddq_f32(frames.0, frames.1));
+            }
+        }
+        scalar::channel_sum(dest_chunks.into_remainder(), source_chunks.remainder(), 2);
+    }
+
+    pub fn interleave_stereo(dest: &mut [f32], left: &[f32], right: &[f32]) {
+        let num_frames = left.len().min(right.len()).min(dest.len() / 2);
+        let (dest, left, right) = (
+            &mut dest[..num_frames * 2],
+            &left[..num_frames],
+            &right[..num_frames],
+        );
+        let mut dest_chunks = dest.chunks_exact_mut(LANES * 2);
+        let mut left_chunks = left.chunks_exact(LANES);
+        let mut right_chunks = right.chunks_exact(LANES);
+        // SAFETY: neon is part of the aarch64 baseline, the chunks hold 8 and 4 floats
+        unsafe {
+            for ((d, l), r) in (&mut dest_chunks)
+                .zip(&mut left_chunks)
+                .zip(&mut right_chunks)
+            {
+                let frames = float32x4x2_t(vld1q_f32(l.as_ptr()), vld1q_f32(r.as_ptr()));
+                vst2q_f32(d.as_mut_ptr(), frames);
+            }
+        }
+        scalar::interleave_stereo(
+            dest_chunks.into_remainder(),
+            left_chunks.remainder(),
+            right_chunks.remainder(),
+        );
+    }
+
+    pub fn deinterleave_stereo(left: &mut [f32], right: &mut [f32], source: &[f32]) {
+        let num_frames = left.len().min(right.len()).min(source.len() / 2);
+        let (left, right, source) = (
+            &mut left[..num_frames],
+            &mut right[..num_frames],
+            &source[..num_frames * 2],
+        );
+        let mut left_chunks = left.chunks_exact_mut(LANES);
+        let mut right_chunks = right.chunks_exact_mut(LANES);
+        let mut source_chunks = source.chunks_exact(LANES * 2);
+        // SAFETY: neon is part of the aarch64 baseline, the chunks hold 4 and 8 floats
+        unsafe {
+            for ((l, r), s) in (&mut left_chunks)
+                .zip(&mut right_chunks)
+                .zip(&mut source_chunks)
+            {
+                let frames = vld2q_f32(s.as_ptr());
+                vst1q_f32(l.as_mut_ptr(), frames.0);
+                vst1q_f32(r.as_mut_ptr(), frames.1);
+            }
+        }
+        scalar::deinterleave_stereo(
+            left_chunks.into_remainder(),
+            right_chunks.into_remainder(),
+            source_chunks.remainder(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planar_audio::{PlanarAudio, PlanarAudioMut};
+
+    /// deterministic noise in -1.0..1.0, long enough to have a remainder after the vectors
+    fn noise(len: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed.wrapping_mul(747796405).wrapping_add(2891336453);
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as f32 / u32::MAX as f32 * 2.0 - 1.0
+            })
+            .collect()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() <= 1e-4 * b.abs().max(1.0), "{a} != {b}");
+    }
+
+    #[test]
+    fn gains_match_scalar() {
+        for len in [0, 3, 4, 17, 64, 101] {
+            let input = noise(len, len as u32);
+
+            let (mut simd, mut reference) = (input.clone(), input.clone());
+            apply_gain(&mut simd, 0.7);
+            scalar::apply_gain(&mut reference, 0.7);
+            assert_eq!(simd, reference);
+
+            for num_channels in 1..=5 {
+                let len = len - len % num_channels;
+                let (mut simd, mut reference) = (input[..len].to_vec(), input[..len].to_vec());
+                apply_ramped_gain(&mut simd, num_channels, 0.2, 1.3);
+                scalar::apply_ramped_gain(&mut reference, num_channels, 0.2, 1.3);
+                assert_eq!(simd, reference);
+            }
+
+            let source = noise(len, 99);
+            let (mut simd, mut reference) = (input.clone(), input.clone());
+            mix_add(&mut simd, &source, -0.5);
+            scalar::mix_add(&mut reference, &source, -0.5);
+            assert_eq!(simd, reference);
+        }
+    }
+
+    #[test]
+    fn measurements_match_scalar() {
+        for len in [0, 3, 4, 17, 64, 101] {
+            let input = noise(len, 7 + len as u32);
+            assert_eq!(peak(&input), scalar::peak(&input));
+            assert_close(rms(&input), scalar::rms(&input));
+        }
+        assert_eq!(peak(&[0.5, -0.75, 0.25, 0.0, 0.1]), 0.75);
+    }
+
+    #[test]
+    fn channels_match_scalar() {
+        for num_frames in [0, 1, 4, 9, 33] {
+            let left = noise(num_frames, 1);
+            let right = noise(num_frames, 2);
+
+            let mut simd = vec![0.0; num_frames * 2];
+            let mut reference = vec![0.0; num_frames * 2];
+            interleave(
+                &PlanarAudio::from_slice(&[left.clone(), right.clone()].concat(), 2),
+                &mut InterleavedAudioMut::from_slice(&mut simd, 2),
+            );
+            scalar::interleave_stereo(&mut reference, &left, &right);
+            assert_eq!(simd, reference);
+
+            let mut planar = vec![0.0; num_frames * 2];
+            deinterleave(
+                &InterleavedAudio::from_slice(&simd, 2),
+                &mut PlanarAudioMut::from_slice(&mut planar, 2),
+            );
+            assert_eq!(planar, [left, right].concat());
+
+            for num_channels in 1..=3 {
+                let input = noise(num_frames * num_channels, 3);
+                let mut simd = vec![0.0; num_frames];
+                let mut reference = vec![0.0; num_frames];
+                channel_sum(&mut simd, &input, num_channels);
+                scalar::channel_sum(&mut reference, &input, num_channels);
+                assert_eq!(simd, reference);
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::slice::ChunksExact;
+
+use crate::interleaved_audio::{InterleavedAudio, InterleavedAudioMut};
+
+/// -3 dB, the gain of a channel that is split between two speakers or folded into another one
+const MINUS_3DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+/// -6 dB, the gain of surround channels folded into a mono center
+const MINUS_6DB: f32 = 0.5;
+
+/// The position of the speaker a channel is meant for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    SideLeft,
+    SideRight,
+    /// a discrete channel without position, it is only routed to the channel with the same index
+    Unknown,
+}
+
+/// The speakers of all channels of a stream or audio file, in the order of the interleaved
+/// channels. Surround layouts use the order of WAV files.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelLayout {
+    Mono,
+    #[default]
+    Stereo,
+    /// front left, front right, back left, back right
+    Quad,
+    /// front left, front right, center, LFE, back left, back right
+    Surround51,
+    /// front left, front right, center, LFE, back left, back right, side left, side right
+    Surround71,
+    Custom(Vec<Speaker>),
+}
+
+impl ChannelLayout {
+    /// the common layout for the number of channels, other numbers get unknown speakers
+    pub fn from_num_channels(num_channels: usize) -> Self {
+        match num_channels {
+            1 => Self::Mono,
+            2 => Self::Stereo,
+            4 => Self::Quad,
+            6 => Self::Surround51,
+            8 => Self::Surround71,
+            n => Self::Custom(vec![Speaker::Unknown; n]),
+        }
+    }
+
+    pub fn speakers(&self) -> &[Speaker] {
+        use Speaker::*;
+
+        match self {
+            Self::Mono => &[FrontCenter],
+            Self::Stereo => &[FrontLeft, FrontRight],
+            Self::Quad => &[FrontLeft, FrontRight, BackLeft, BackRight],
+            Self::Surround51 => &[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+            ],
+            Self::Surround71 => &[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+            ],
+            Self::Custom(speakers) => speakers,
+        }
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.speakers().len()
+    }
+
+    /// the index of the channel for the speaker
+    pub fn channel(&self, speaker: Speaker) -> Option<usize> {
+        self.speakers().iter().position(|s| *s == speaker)
+    }
+}
+
+/// Gains from every input to every output channel, to convert audio between channel layouts.
+/// The matrix is allocated in `new`, `process` is real-time safe.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MixMatrix {
+    num_inputs: usize,
+    num_outputs: usize,
+    // one row of input gains per output channel
+    coefficients: Vec<f32>,
+}
+
+impl MixMatrix {
+    /// a matrix with all gains set to zero
+    pub fn zeros(num_inputs: usize, num_outputs: usize) -> Self {
+        Self {
+            num_inputs,
+            num_outputs,
+            coefficients: vec![0.0; num_inputs * num_outputs],
+        }
+    }
+
+    /// the standard down- and up-mix after ITU-R BS.775.
+    /// channels that exist in both layouts are copied. missing ones are folded into the nearest
+    /// speakers with -3 dB, or -6 dB for surround channels folded into mono.
+    /// the LFE channel is dropped, if the output has none.
+    pub fn new(input: &ChannelLayout, output: &ChannelLayout) -> Self {
+        use Speaker::*;
+
+        let mut matrix = Self::zeros(input.num_channels(), output.num_channels());
+        for (in_ch, speaker) in input.speakers().iter().enumerate() {
+            if *speaker != Unknown {
+                if let Some(out_ch) = output.channel(*speaker) {
+                    matrix.set_coefficient(out_ch, in_ch, 1.0);
+                    continue;
+                }
+            }
+
+            let targets: &[(&[Speaker], f32)] = match speaker {
+                FrontLeft => &[(&[FrontCenter], MINUS_3DB)],
+                FrontRight => &[(&[FrontCenter], MINUS_3DB)],
+                FrontCenter => &[(&[FrontLeft, FrontRight], MINUS_3DB)],
+                SideLeft => &[
+                    (&[BackLeft], MINUS_3DB),
+                    (&[FrontLeft], MINUS_3DB),
+                    (&[FrontCenter], MINUS_6DB),
+                ],
+                SideRight => &[
+                    (&[BackRight], MINUS_3DB),
+                    (&[FrontRight], MINUS_3DB),
+                    (&[FrontCenter], MINUS_6DB),
+                ],
+                BackLeft => &[
+                    (&[SideLeft], MINUS_3DB),
+                    (&[FrontLeft], MINUS_3DB),
+                    (&[FrontCenter], MINUS_6DB),
+                ],
+                BackRight => &[
+                    (&[SideRight], MINUS_3DB),
+                    (&[FrontRight], MINUS_3DB),
+                    (&[FrontCenter], MINUS_6DB),
+                ],
+                LowFrequency => &[],
+                Unknown => {
+                    if in_ch < matrix.num_outputs {
+                        matrix.set_coefficient(in_ch, in_ch, 1.0);
+                    }
+                    continue;
+                }
+            };
+
+            // the first group of speakers the output has all of
+            let target = targets.iter().find_map(|(speakers, gain)| {
+                let channels = speakers
+                    .iter()
+                    .map(|speaker| output.channel(*speaker))
+                    .collect::<Option<Vec<_>>>()?;
+                Some((channels, *gain))
+            });
+            if let Some((channels, gain)) = target {
+                for out_ch in channels {
+                    matrix.set_coefficient(out_ch, in_ch, gain);
+                }
+            }
+        }
+        matrix
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.num_outputs
+    }
+
+    pub fn coefficient(&self, output: usize, input: usize) -> f32 {
+        self.coefficients[output * self.num_inputs + input]
+    }
+
+    pub fn set_coefficient(&mut self, output: usize, input: usize, gain: f32) {
+        self.coefficients[output * self.num_inputs + input] = gain;
+    }
+
+    /// the input gains of every output channel
+    pub fn rows(&self) -> ChunksExact<'_, f32> {
+        self.coefficients.chunks_exact(self.num_inputs.max(1))
+    }
+
+    /// mixes one interleaved frame, missing channels are treated as silent
+    pub fn mix_frame(&self, output: &mut [f32], input: &[f32]) {
+        for (o, row) in output.iter_mut().zip(self.rows()) {
+            *o = row.iter().zip(input).map(|(gain, i)| gain * i).sum();
+        }
+    }
+
+    /// mixes all frames both buffers have, the output is overwritten
+    pub fn process(
+        &self,
+        output: &mut InterleavedAudioMut<'_, f32>,
+        input: &InterleavedAudio<'_, f32>,
+    ) {
+        for (out_frame, in_frame) in output.frames_iter_mut().zip(input.frames_iter()) {
+            self.mix_frame(out_frame, in_frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mix(input: &ChannelLayout, output: &ChannelLayout, frame: &[f32]) -> Vec<f32> {
+        let mut result = vec![0.0; output.num_channels()];
+        MixMatrix::new(input, output).mix_frame(&mut result, frame);
+        result
+    }
+
+    #[test]
+    fn standard_mixes() {
+        let c = MINUS_3DB;
+
+        // mono plays on both speakers
+        assert_eq!(
+            mix(&ChannelLayout::Mono, &ChannelLayout::Stereo, &[1.0]),
+            [c, c]
+        );
+        assert_eq!(
+            mix(&ChannelLayout::Stereo, &ChannelLayout::Mono, &[1.0, 0.5]),
+            [1.5 * c]
+        );
+
+        // 5.1 folds the center and the surrounds into the front, the LFE is dropped
+        let surround = [1.0, 2.0, 3.0, 10.0, 4.0, 5.0];
+        assert_eq!(
+            mix(
+                &ChannelLayout::Surround51,
+                &ChannelLayout::Stereo,
+                &surround
+            ),
+            [1.0 + 3.0 * c + 4.0 * c, 2.0 + 3.0 * c + 5.0 * c]
+        );
+        assert_eq!(
+            mix(&ChannelLayout::Surround51, &ChannelLayout::Mono, &surround),
+            [(1.0 + 2.0) * c + 3.0 + (4.0 + 5.0) * 0.5]
+        );
+
+        // up-mixes don't invent content for the new channels
+        assert_eq!(
+            mix(
+                &ChannelLayout::Stereo,
+                &ChannelLayout::Surround71,
+                &[1.0, 2.0]
+            ),
+            [1.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            mix(
+                &ChannelLayout::Surround71,
+                &ChannelLayout::Surround51,
+                &[0., 0., 0., 0., 1., 0., 1., 0.]
+            ),
+            [0.0, 0.0, 0.0, 0.0, 1.0 + c, 0.0]
+        );
+    }
+
+    #[test]
+    fn custom_layouts() {
+        assert_eq!(
+            ChannelLayout::from_num_channels(3),
+            ChannelLayout::Custom(vec![Speaker::Unknown; 3])
+        );
+        let layout = ChannelLayout::Custom(vec![Speaker::FrontRight, Speaker::Unknown]);
+        assert_eq!(layout.channel(Speaker::FrontRight), Some(0));
+        assert_eq!(
+            mix(&ChannelLayout::Stereo, &layout, &[1.0, 2.0]),
+            [2.0, 0.0]
+        );
+        assert_eq!(
+            mix(
+                &ChannelLayout::from_num_channels(3),
+                &layout,
+                &[1.0, 2.0, 3.0]
+            ),
+            [1.0, 2.0]
+        );
+    }
+}
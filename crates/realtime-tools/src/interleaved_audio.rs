@@ -1,5 +1,6 @@
 use std::{
     iter::{Skip, StepBy},
+    ops::{Bound, Range, RangeBounds},
     slice::{Chunks, ChunksMut, Iter, IterMut},
 };
 
@@ -13,7 +14,7 @@ pub struct InterleavedAudio<'a, T: Copy> {
 impl<'a, T: Copy> InterleavedAudio<'a, T> {
     pub fn from_slice(data: &'a [T], num_channels: usize) -> Self {
         Self {
-            num_frames: data.len() / num_channels,
+            num_frames: data.len().checked_div(num_channels).unwrap_or(0),
             data,
             num_channels,
         }
@@ -47,6 +48,56 @@ impl<'a, T: Copy> InterleavedAudio<'a, T> {
         self.data.iter().skip(channel).step_by(self.num_channels)
     }
 
+    /// returns a view on a single channel, like `slice_frames` it never panics,
+    /// the view is empty if the channel is out of range
+    pub fn channel(&self, channel: usize) -> InterleavedChannel<'a, T> {
+        let in_range = channel < self.num_channels;
+        InterleavedChannel {
+            data: self
+                .data
+                .get(channel..)
+                .filter(|_| in_range)
+                .unwrap_or_default(),
+            num_channels: self.num_channels,
+            num_frames: if in_range { self.num_frames } else { 0 },
+        }
+    }
+
+    /// returns a view on the frames in the range. frames outside of the buffer are left out,
+    /// so the view can be shorter than the range, but it never panics in the audio thread.
+    pub fn slice_frames(&self, range: impl RangeBounds<usize>) -> InterleavedAudio<'a, T> {
+        let range = frame_range(range, self.num_frames);
+        Self::from_slice(
+            &self.data[range.start * self.num_channels..range.end * self.num_channels],
+            self.num_channels,
+        )
+    }
+
+    /// splits into the frames before and after `frame`, which is limited to the number of frames
+    pub fn split_at_frame(
+        &self,
+        frame: usize,
+    ) -> (InterleavedAudio<'a, T>, InterleavedAudio<'a, T>) {
+        let (first, second) = self
+            .data
+            .split_at(frame.min(self.num_frames) * self.num_channels);
+        (
+            Self::from_slice(first, self.num_channels),
+            Self::from_slice(second, self.num_channels),
+        )
+    }
+
+    /// this will return an iterator over views of `num_frames` frames, the last one can be shorter
+    pub fn chunks_frames(
+        &self,
+        num_frames: usize,
+    ) -> impl Iterator<Item = InterleavedAudio<'a, T>> {
+        let num_channels = self.num_channels;
+        self.data
+            .chunks((num_frames.max(1) * num_channels).max(1))
+            .map(move |chunk| InterleavedAudio::from_slice(chunk, num_channels))
+    }
+
     pub fn data(&self) -> &[T] {
         self.data
     }
@@ -70,7 +121,7 @@ pub struct InterleavedAudioMut<'a, T: Copy> {
 impl<'a, T: Copy> InterleavedAudioMut<'a, T> {
     pub fn from_slice(data: &'a mut [T], num_channels: usize) -> Self {
         Self {
-            num_frames: data.len() / num_channels,
+            num_frames: data.len().checked_div(num_channels).unwrap_or(0),
             data,
             num_channels,
         }
@@ -107,6 +158,73 @@ impl<'a, T: Copy> InterleavedAudioMut<'a, T> {
             .step_by(self.num_channels)
     }
 
+    /// returns a view on a single channel, like `slice_frames` it never panics,
+    /// the view is empty if the channel is out of range
+    pub fn channel(&self, channel: usize) -> InterleavedChannel<'_, T> {
+        InterleavedAudio::from_slice(self.data, self.num_channels).channel(channel)
+    }
+
+    /// returns a mutable view on a single channel, which is empty if the channel is out of range
+    pub fn channel_mut(&mut self, channel: usize) -> InterleavedChannelMut<'_, T> {
+        let in_range = channel < self.num_channels;
+        InterleavedChannelMut {
+            data: self
+                .data
+                .get_mut(channel..)
+                .filter(|_| in_range)
+                .unwrap_or_default(),
+            num_channels: self.num_channels,
+            num_frames: if in_range { self.num_frames } else { 0 },
+        }
+    }
+
+    /// returns a view on the frames in the range. frames outside of the buffer are left out,
+    /// so the view can be shorter than the range, but it never panics in the audio thread.
+    pub fn slice_frames(&self, range: impl RangeBounds<usize>) -> InterleavedAudio<'_, T> {
+        let range = frame_range(range, self.num_frames);
+        InterleavedAudio::from_slice(
+            &self.data[range.start * self.num_channels..range.end * self.num_channels],
+            self.num_channels,
+        )
+    }
+
+    /// like `slice_frames`, but the view can be written to
+    pub fn slice_frames_mut(
+        &mut self,
+        range: impl RangeBounds<usize>,
+    ) -> InterleavedAudioMut<'_, T> {
+        let range = frame_range(range, self.num_frames);
+        InterleavedAudioMut::from_slice(
+            &mut self.data[range.start * self.num_channels..range.end * self.num_channels],
+            self.num_channels,
+        )
+    }
+
+    /// splits into the frames before and after `frame`, which is limited to the number of frames
+    pub fn split_at_frame_mut(
+        &mut self,
+        frame: usize,
+    ) -> (InterleavedAudioMut<'_, T>, InterleavedAudioMut<'_, T>) {
+        let (first, second) = self
+            .data
+            .split_at_mut(frame.min(self.num_frames) * self.num_channels);
+        (
+            InterleavedAudioMut::from_slice(first, self.num_channels),
+            InterleavedAudioMut::from_slice(second, self.num_channels),
+        )
+    }
+
+    /// this will return an iterator over views of `num_frames` frames, the last one can be shorter
+    pub fn chunks_frames_mut(
+        &mut self,
+        num_frames: usize,
+    ) -> impl Iterator<Item = InterleavedAudioMut<'_, T>> {
+        let num_channels = self.num_channels;
+        self.data
+            .chunks_mut((num_frames.max(1) * num_channels).max(1))
+            .map(move |chunk| InterleavedAudioMut::from_slice(chunk, num_channels))
+    }
+
     pub fn data(&self) -> &[T] {
         self.data
     }
@@ -132,3 +250,169 @@ impl<'a, T: Copy> InterleavedAudioMut<'a, T> {
         usize::min(channel_buffer.len(), self.num_frames)
     }
 }
+
+/// A single channel of interleaved audio.
+pub struct InterleavedChannel<'a, T: Copy> {
+    // starts at the first sample of the channel
+    data: &'a [T],
+    num_channels: usize,
+    num_frames: usize,
+}
+
+impl<'a, T: Copy> InterleavedChannel<'a, T> {
+    pub fn len(&self) -> usize {
+        self.num_frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_frames == 0
+    }
+
+    pub fn get(&self, frame: usize) -> Option<T> {
+        if frame < self.num_frames {
+            self.data.get(frame * self.num_channels).copied()
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> StepBy<Iter<'a, T>> {
+        self.data.iter().step_by(self.num_channels.max(1))
+    }
+
+    /// returns the actual number of samples written
+    pub fn copy_to_slice(&self, buffer: &mut [T]) -> usize {
+        buffer
+            .iter_mut()
+            .zip(self.iter())
+            .for_each(|(o, i)| *o = *i);
+        usize::min(buffer.len(), self.num_frames)
+    }
+}
+
+/// A single channel of interleaved audio that can be written to.
+pub struct InterleavedChannelMut<'a, T: Copy> {
+    // starts at the first sample of the channel
+    data: &'a mut [T],
+    num_channels: usize,
+    num_frames: usize,
+}
+
+impl<T: Copy> InterleavedChannelMut<'_, T> {
+    pub fn len(&self) -> usize {
+        self.num_frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_frames == 0
+    }
+
+    pub fn get(&self, frame: usize) -> Option<T> {
+        if frame < self.num_frames {
+            self.data.get(frame * self.num_channels).copied()
+        } else {
+            None
+        }
+    }
+
+    /// frames out of range are ignored
+    pub fn set(&mut self, frame: usize, value: T) {
+        if frame < self.num_frames {
+            self.data[frame * self.num_channels] = value;
+        }
+    }
+
+    pub fn iter(&self) -> StepBy<Iter<'_, T>> {
+        self.data.iter().step_by(self.num_channels.max(1))
+    }
+
+    pub fn iter_mut(&mut self) -> StepBy<IterMut<'_, T>> {
+        self.data.iter_mut().step_by(self.num_channels.max(1))
+    }
+
+    pub fn fill(&mut self, value: T) {
+        self.iter_mut().for_each(|sample| *sample = value);
+    }
+
+    /// returns the actual number of samples written
+    pub fn copy_to_slice(&self, buffer: &mut [T]) -> usize {
+        buffer
+            .iter_mut()
+            .zip(self.iter())
+            .for_each(|(o, i)| *o = *i);
+        usize::min(buffer.len(), self.num_frames)
+    }
+
+    /// returns the actual number of samples written
+    pub fn copy_from_slice(&mut self, buffer: &[T]) -> usize {
+        self.iter_mut()
+            .zip(buffer.iter())
+            .for_each(|(o, i)| *o = *i);
+        usize::min(buffer.len(), self.num_frames)
+    }
+}
+
+/// limits the range to the frames of the buffer
+pub(crate) fn frame_range(range: impl RangeBounds<usize>, num_frames: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(start) => *start,
+        Bound::Excluded(start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(end) => end.saturating_add(1),
+        Bound::Excluded(end) => *end,
+        Bound::Unbounded => num_frames,
+    }
+    .min(num_frames);
+    start.min(end)..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_views() {
+        let data = (0..12).collect::<Vec<_>>();
+        let audio = InterleavedAudio::from_slice(&data, 2);
+
+        let slice = audio.slice_frames(2..4);
+        assert_eq!(slice.data(), [4, 5, 6, 7]);
+        assert_eq!(audio.slice_frames(4..10).num_frames(), 2);
+
+        let (first, second) = audio.split_at_frame(1);
+        assert_eq!((first.num_frames(), second.num_frames()), (1, 5));
+
+        let chunks = audio
+            .chunks_frames(4)
+            .map(|c| c.num_frames())
+            .collect::<Vec<_>>();
+        assert_eq!(chunks, [4, 2]);
+        assert_eq!(
+            audio.channel(1).iter().copied().collect::<Vec<_>>(),
+            [1, 3, 5, 7, 9, 11]
+        );
+        assert_eq!(audio.channel(1).get(5), Some(11));
+        assert_eq!(audio.channel(1).get(6), None);
+        assert!(audio.channel(2).is_empty());
+        assert_eq!(audio.channel(2).iter().count(), 0);
+    }
+
+    #[test]
+    fn mutable_frame_views() {
+        let mut data = vec![0; 8];
+        let mut audio = InterleavedAudioMut::from_slice(&mut data, 2);
+
+        let (mut first, mut second) = audio.split_at_frame_mut(1);
+        first.data_mut().fill(1);
+        second.channel_mut(1).fill(2);
+        for (index, mut chunk) in audio.chunks_frames_mut(2).enumerate() {
+            chunk.channel_mut(0).set(1, 3 + index);
+        }
+        audio.slice_frames_mut(..).channel_mut(0).set(0, 0);
+        audio.channel_mut(2).fill(9);
+        audio.channel_mut(1).set(4, 9);
+        assert_eq!(data, [0, 1, 3, 2, 0, 2, 4, 2]);
+    }
+}
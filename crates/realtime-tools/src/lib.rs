@@ -1,4 +1,10 @@
+pub mod audio_buffer;
+pub mod buffer_ops;
+pub mod channel_layout;
 pub mod interleaved_audio;
 pub mod level_meter;
 pub mod parameters;
+pub mod planar_audio;
+pub mod resample;
+pub mod sample_format;
 pub mod smooth_value;
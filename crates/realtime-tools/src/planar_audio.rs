@@ -0,0 +1,679 @@
+use std::{
+    any::TypeId,
+    marker::PhantomData,
+    ops::{Range, RangeBounds},
+    slice::{self, Iter, IterMut},
+};
+
+use crate::{
+    buffer_ops,
+    interleaved_audio::{frame_range, InterleavedAudio, InterleavedAudioMut},
+};
+
+/// Non-interleaved audio. The channels are either stored one after the other in a single slice,
+/// where each channel starts `stride` samples after the previous one, so views on a part of a
+/// larger buffer can be created without allocating a slice of channel slices. Or every channel
+/// has its own slice, like the buffers of most plugin hosts.
+#[derive(Clone)]
+pub struct PlanarAudio<'a, T: Copy> {
+    channels: Channels<'a, T>,
+    num_channels: usize,
+    num_frames: usize,
+}
+
+#[derive(Clone)]
+enum Channels<'a, T> {
+    Strided {
+        data: &'a [T],
+        stride: usize,
+    },
+    /// the frames of the view start at `offset` in every channel
+    Separate {
+        channels: &'a [&'a [T]],
+        offset: usize,
+    },
+}
+
+impl<'a, T: Copy> PlanarAudio<'a, T> {
+    /// the slice is split into `num_channels` channels of equal length
+    pub fn from_slice(data: &'a [T], num_channels: usize) -> Self {
+        let num_frames = data.len().checked_div(num_channels).unwrap_or(0);
+        Self::from_slice_with_stride(data, num_channels, num_frames, num_frames)
+    }
+
+    /// channels start `stride` samples apart, but only the first `num_frames` of each are used
+    pub fn from_slice_with_stride(
+        data: &'a [T],
+        num_channels: usize,
+        num_frames: usize,
+        stride: usize,
+    ) -> Self {
+        assert!(num_frames <= stride, "number of frames exceeds the stride");
+        assert!(
+            data.len() >= required_len(num_channels, num_frames, stride),
+            "slice too short for the number of channels and frames"
+        );
+        Self {
+            channels: Channels::Strided { data, stride },
+            num_channels,
+            num_frames,
+        }
+    }
+
+    /// a slice for every channel, the number of frames is the length of the shortest one
+    pub fn from_channels(channels: &'a [&'a [T]]) -> Self {
+        Self {
+            channels: Channels::Separate {
+                channels,
+                offset: 0,
+            },
+            num_channels: channels.len(),
+            num_frames: channels.iter().map(|c| c.len()).min().unwrap_or(0),
+        }
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// the distance between the starts of the channels, `None` if they have their own slices
+    pub fn stride(&self) -> Option<usize> {
+        match self.channels {
+            Channels::Strided { stride, .. } => Some(stride),
+            Channels::Separate { .. } => None,
+        }
+    }
+
+    /// returns all frames of one channel as slice, which is empty if the channel is out of range
+    pub fn channel(&self, channel: usize) -> &'a [T] {
+        if channel >= self.num_channels {
+            return &[];
+        }
+        match self.channels {
+            Channels::Strided { data, stride } => {
+                let start = channel * stride;
+                &data[start..start + self.num_frames]
+            }
+            Channels::Separate { channels, offset } => {
+                &channels[channel][offset..offset + self.num_frames]
+            }
+        }
+    }
+
+    /// returns a view on a single channel, which is empty if the channel is out of range
+    pub fn single_channel(&self, channel: usize) -> PlanarAudio<'a, T> {
+        if channel >= self.num_channels {
+            return Self::from_slice(&[], 0);
+        }
+        match self.channels {
+            Channels::Strided { .. } => Self::from_slice(self.channel(channel), 1),
+            Channels::Separate { channels, offset } => Self {
+                channels: Channels::Separate {
+                    channels: &channels[channel..channel + 1],
+                    offset,
+                },
+                num_channels: 1,
+                num_frames: self.num_frames,
+            },
+        }
+    }
+
+    /// this will return an iterator over one channel
+    pub fn channel_iter(&self, channel: usize) -> Iter<'a, T> {
+        self.channel(channel).iter()
+    }
+
+    /// this will return an iterator over all channels as slices
+    pub fn channels_iter(&self) -> impl Iterator<Item = &'a [T]> {
+        let audio = self.clone();
+        (0..self.num_channels).map(move |channel| audio.channel(channel))
+    }
+
+    /// returns a view on the frames in the range. frames outside of the buffer are left out,
+    /// so the view can be shorter than the range, but it never panics in the audio thread.
+    pub fn slice_frames(&self, range: impl RangeBounds<usize>) -> PlanarAudio<'a, T> {
+        let range = frame_range(range, self.num_frames);
+        let channels = match self.channels {
+            Channels::Strided { data, stride } => Channels::Strided {
+                // without channels the slice can be shorter than the frames
+                data: data.get(range.start..).unwrap_or_default(),
+                stride,
+            },
+            Channels::Separate { channels, offset } => Channels::Separate {
+                channels,
+                offset: offset + range.start,
+            },
+        };
+        Self {
+            channels,
+            num_channels: self.num_channels,
+            num_frames: range.len(),
+        }
+    }
+
+    /// splits into the frames before and after `frame`, which is limited to the number of frames
+    pub fn split_at_frame(&self, frame: usize) -> (PlanarAudio<'a, T>, PlanarAudio<'a, T>) {
+        (self.slice_frames(..frame), self.slice_frames(frame..))
+    }
+
+    /// this will return an iterator over views of `num_frames` frames, the last one can be shorter
+    pub fn chunks_frames(&self, num_frames: usize) -> impl Iterator<Item = PlanarAudio<'a, T>> {
+        let audio = self.clone();
+        let num_frames = num_frames.max(1);
+        (0..self.num_frames)
+            .step_by(num_frames)
+            .map(move |start| audio.slice_frames(start..start + num_frames))
+    }
+
+    /// returns the actual number of samples written
+    pub fn copy_in_channel_buffer(&self, channel_buffer: &mut [T], channel: usize) -> usize {
+        let len = usize::min(channel_buffer.len(), self.num_frames);
+        channel_buffer[..len].copy_from_slice(&self.channel(channel)[..len]);
+        len
+    }
+}
+
+/// Like `PlanarAudio`, but the samples can be written to. The channels are kept as pointers,
+/// so the view can be split into frames without borrowing every channel slice again.
+pub struct PlanarAudioMut<'a, T: Copy> {
+    channels: ChannelsMut<T>,
+    num_channels: usize,
+    num_frames: usize,
+    _borrow: PhantomData<&'a mut [T]>,
+}
+
+// SAFETY: the view has unique access to its samples, like a `&mut [T]`
+unsafe impl<T: Copy + Send> Send for PlanarAudioMut<'_, T> {}
+// SAFETY: shared access only reads the samples, like a `&[T]`
+unsafe impl<T: Copy + Sync> Sync for PlanarAudioMut<'_, T> {}
+
+enum ChannelsMut<T> {
+    Strided {
+        data: *mut T,
+        stride: usize,
+    },
+    /// the frames of the view start at `offset` in every channel
+    Separate {
+        channels: *const *mut [T],
+        offset: usize,
+    },
+}
+
+impl<T> Clone for ChannelsMut<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ChannelsMut<T> {}
+
+impl<T: Copy> ChannelsMut<T> {
+    /// SAFETY: the channel has to exist
+    unsafe fn channel_ptr(self, channel: usize) -> *mut T {
+        match self {
+            Self::Strided { data, stride } => data.add(channel * stride),
+            Self::Separate { channels, offset } => (*channels.add(channel)).cast::<T>().add(offset),
+        }
+    }
+
+    /// moves the start of the channels forward by `num_frames`
+    fn advance(self, num_frames: usize) -> Self {
+        match self {
+            // without channels the pointer can be at the end of the slice, so it is never read
+            Self::Strided { data, stride } => Self::Strided {
+                data: data.wrapping_add(num_frames),
+                stride,
+            },
+            Self::Separate { channels, offset } => Self::Separate {
+                channels,
+                offset: offset + num_frames,
+            },
+        }
+    }
+
+    /// SAFETY: the frames have to be in the view the storage comes from, and no other view
+    /// on them may be used while the returned one lives
+    unsafe fn frames<'b>(self, num_channels: usize, range: Range<usize>) -> PlanarAudioMut<'b, T> {
+        PlanarAudioMut {
+            channels: self.advance(range.start),
+            num_channels,
+            num_frames: range.len(),
+            _borrow: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: Copy> PlanarAudioMut<'a, T> {
+    /// the slice is split into `num_channels` channels of equal length
+    pub fn from_slice(data: &'a mut [T], num_channels: usize) -> Self {
+        let num_frames = data.len().checked_div(num_channels).unwrap_or(0);
+        Self::from_slice_with_stride(data, num_channels, num_frames, num_frames)
+    }
+
+    /// channels start `stride` samples apart, but only the first `num_frames` of each are used
+    pub fn from_slice_with_stride(
+        data: &'a mut [T],
+        num_channels: usize,
+        num_frames: usize,
+        stride: usize,
+    ) -> Self {
+        assert!(num_frames <= stride, "number of frames exceeds the stride");
+        assert!(
+            data.len() >= required_len(num_channels, num_frames, stride),
+            "slice too short for the number of channels and frames"
+        );
+        Self {
+            channels: ChannelsMut::Strided {
+                data: data.as_mut_ptr(),
+                stride,
+            },
+            num_channels,
+            num_frames,
+            _borrow: PhantomData,
+        }
+    }
+
+    /// a slice for every channel, the number of frames is the length of the shortest one
+    pub fn from_channels_mut<'b: 'a>(channels: &'a mut [&'b mut [T]]) -> Self {
+        Self {
+            num_channels: channels.len(),
+            num_frames: channels.iter().map(|c| c.len()).min().unwrap_or(0),
+            // `&mut [T]` and `*mut [T]` have the same layout
+            channels: ChannelsMut::Separate {
+                channels: channels.as_mut_ptr() as *const *mut [T],
+                offset: 0,
+            },
+            _borrow: PhantomData,
+        }
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// the distance between the starts of the channels, `None` if they have their own slices
+    pub fn stride(&self) -> Option<usize> {
+        match self.channels {
+            ChannelsMut::Strided { stride, .. } => Some(stride),
+            ChannelsMut::Separate { .. } => None,
+        }
+    }
+
+    /// returns all frames of one channel as slice, which is empty if the channel is out of range
+    pub fn channel(&self, channel: usize) -> &[T] {
+        if channel >= self.num_channels {
+            return &[];
+        }
+        // SAFETY: the channel exists and has at least `num_frames` frames after the start
+        unsafe { slice::from_raw_parts(self.channels.channel_ptr(channel), self.num_frames) }
+    }
+
+    /// like `channel`, but the samples can be written to
+    pub fn channel_mut(&mut self, channel: usize) -> &mut [T] {
+        if channel >= self.num_channels {
+            return &mut [];
+        }
+        // SAFETY: as in `channel`, and `self` is borrowed mutably
+        unsafe { slice::from_raw_parts_mut(self.channels.channel_ptr(channel), self.num_frames) }
+    }
+
+    /// returns a view on a single channel, which is empty if the channel is out of range
+    pub fn single_channel_mut(&mut self, channel: usize) -> PlanarAudioMut<'_, T> {
+        if channel >= self.num_channels {
+            return PlanarAudioMut::from_slice(&mut [], 0);
+        }
+        PlanarAudioMut {
+            // SAFETY: the channel exists
+            channels: ChannelsMut::Strided {
+                data: unsafe { self.channels.channel_ptr(channel) },
+                stride: self.num_frames,
+            },
+            num_channels: 1,
+            num_frames: self.num_frames,
+            _borrow: PhantomData,
+        }
+    }
+
+    /// this will return an iterator over one channel
+    pub fn channel_iter(&self, channel: usize) -> Iter<'_, T> {
+        self.channel(channel).iter()
+    }
+
+    pub fn channel_iter_mut(&mut self, channel: usize) -> IterMut<'_, T> {
+        self.channel_mut(channel).iter_mut()
+    }
+
+    /// this will return an iterator over all channels as slices
+    pub fn channels_iter(&self) -> impl Iterator<Item = &[T]> {
+        let (channels, num_frames) = (self.channels, self.num_frames);
+        // SAFETY: every channel exists and `self` is borrowed
+        (0..self.num_channels).map(move |channel| unsafe {
+            slice::from_raw_parts(channels.channel_ptr(channel), num_frames)
+        })
+    }
+
+    pub fn channels_iter_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        let (channels, num_frames) = (self.channels, self.num_frames);
+        // SAFETY: every channel exists and is returned once, channels don't overlap, because
+        // the stride is at least the number of frames and separate channels are separate borrows
+        (0..self.num_channels).map(move |channel| unsafe {
+            slice::from_raw_parts_mut(channels.channel_ptr(channel), num_frames)
+        })
+    }
+
+    /// returns a view on the frames in the range. frames outside of the buffer are left out,
+    /// so the view can be shorter than the range, but it never panics in the audio thread.
+    pub fn slice_frames(&self, range: impl RangeBounds<usize>) -> PlanarAudio<'_, T> {
+        let range = frame_range(range, self.num_frames);
+        let channels = match self.channels {
+            ChannelsMut::Strided { data, stride } => Channels::Strided {
+                // SAFETY: the slice covers all channels, it is only read while `self` is borrowed
+                data: unsafe {
+                    slice::from_raw_parts(
+                        data,
+                        required_len(self.num_channels, self.num_frames, stride),
+                    )
+                },
+                stride,
+            },
+            ChannelsMut::Separate { channels, offset } => Channels::Separate {
+                // SAFETY: `*mut [T]` and `&[T]` have the same layout, the channels are only read
+                // while `self` is borrowed
+                channels: unsafe {
+                    slice::from_raw_parts(channels as *const &[T], self.num_channels)
+                },
+                offset,
+            },
+        };
+        PlanarAudio {
+            channels,
+            num_channels: self.num_channels,
+            num_frames: self.num_frames,
+        }
+        .slice_frames(range)
+    }
+
+    /// like `slice_frames`, but the view can be written to
+    pub fn slice_frames_mut(&mut self, range: impl RangeBounds<usize>) -> PlanarAudioMut<'_, T> {
+        let range = frame_range(range, self.num_frames);
+        // SAFETY: the range is limited to the frames and `self` is borrowed mutably
+        unsafe { self.channels.frames(self.num_channels, range) }
+    }
+
+    /// splits into the frames before and after `frame`, which is limited to the number of frames
+    pub fn split_at_frame_mut(
+        &mut self,
+        frame: usize,
+    ) -> (PlanarAudioMut<'_, T>, PlanarAudioMut<'_, T>) {
+        let frame = frame.min(self.num_frames);
+        // SAFETY: both views are in the frames, they don't overlap and `self` is borrowed mutably
+        unsafe {
+            (
+                self.channels.frames(self.num_channels, 0..frame),
+                self.channels
+                    .frames(self.num_channels, frame..self.num_frames),
+            )
+        }
+    }
+
+    /// this will return an iterator over views of `num_frames` frames, the last one can be shorter
+    pub fn chunks_frames_mut(
+        &mut self,
+        num_frames: usize,
+    ) -> impl Iterator<Item = PlanarAudioMut<'_, T>> {
+        let (channels, num_channels, total) = (self.channels, self.num_channels, self.num_frames);
+        let num_frames = num_frames.max(1);
+        // SAFETY: the chunks are in the frames, they don't overlap and `self` is borrowed mutably
+        (0..total).step_by(num_frames).map(move |start| unsafe {
+            channels.frames(num_channels, start..(start + num_frames).min(total))
+        })
+    }
+
+    /// fills all channels with the value, samples between the channels are not touched
+    pub fn fill(&mut self, value: T) {
+        self.channels_iter_mut()
+            .for_each(|channel| channel.fill(value));
+    }
+
+    /// returns the actual number of samples written
+    pub fn copy_in_channel_buffer(&self, channel_buffer: &mut [T], channel: usize) -> usize {
+        let len = usize::min(channel_buffer.len(), self.num_frames);
+        channel_buffer[..len].copy_from_slice(&self.channel(channel)[..len]);
+        len
+    }
+
+    /// returns the actual number of samples written
+    pub fn copy_from_channel_buffer(&mut self, channel_buffer: &[T], channel: usize) -> usize {
+        let len = usize::min(channel_buffer.len(), self.num_frames);
+        self.channel_mut(channel)[..len].copy_from_slice(&channel_buffer[..len]);
+        len
+    }
+}
+
+/// the samples as `f32`, if that is their type
+fn as_f32<T: 'static>(data: &[T]) -> Option<&[f32]> {
+    // SAFETY: `T` is `f32`
+    (TypeId::of::<T>() == TypeId::of::<f32>())
+        .then(|| unsafe { slice::from_raw_parts(data.as_ptr().cast(), data.len()) })
+}
+
+fn as_f32_mut<T: 'static>(data: &mut [T]) -> Option<&mut [f32]> {
+    // SAFETY: `T` is `f32`
+    (TypeId::of::<T>() == TypeId::of::<f32>())
+        .then(|| unsafe { slice::from_raw_parts_mut(data.as_mut_ptr().cast(), data.len()) })
+}
+
+fn required_len(num_channels: usize, num_frames: usize, stride: usize) -> usize {
+    match num_channels {
+        0 => 0,
+        n => (n - 1) * stride + num_frames,
+    }
+}
+
+/// Copies planar audio into an interleaved buffer.
+/// Only the channels and frames both buffers have are copied, the rest is not touched.
+/// Stereo `f32` audio uses the vectorised version from `buffer_ops`.
+pub fn interleave<T: Copy + 'static>(
+    planar: &PlanarAudio<'_, T>,
+    interleaved: &mut InterleavedAudioMut<'_, T>,
+) {
+    let num_ch = planar.num_channels().min(interleaved.num_channels());
+    let num_frames = planar.num_frames().min(interleaved.num_frames());
+    let interleaved_ch = interleaved.num_channels();
+    let data = &mut interleaved.data_mut()[..num_frames * interleaved_ch];
+
+    // the common layouts iterate over whole frames, which the compiler can vectorise
+    match (num_ch, interleaved_ch) {
+        (1, 1) => data.copy_from_slice(&planar.channel(0)[..num_frames]),
+        (2, 2) => {
+            let (left, right) = (planar.channel(0), planar.channel(1));
+            match (as_f32_mut(data), as_f32(left), as_f32(right)) {
+                (Some(data), Some(left), Some(right)) => {
+                    buffer_ops::interleave_stereo(data, left, right)
+                }
+                _ => {
+                    for ((frame, l), r) in data.chunks_exact_mut(2).zip(left).zip(right) {
+                        frame[0] = *l;
+                        frame[1] = *r;
+                    }
+                }
+            }
+        }
+        _ => {
+            for ch in 0..num_ch {
+                data.iter_mut()
+                    .skip(ch)
+                    .step_by(interleaved_ch)
+                    .zip(planar.channel(ch))
+                    .for_each(|(o, i)| *o = *i);
+            }
+        }
+    }
+}
+
+/// Copies interleaved audio into a planar buffer.
+/// Only the channels and frames both buffers have are copied, the rest is not touched.
+/// Stereo `f32` audio uses the vectorised version from `buffer_ops`.
+pub fn deinterleave<T: Copy + 'static>(
+    interleaved: &InterleavedAudio<'_, T>,
+    planar: &mut PlanarAudioMut<'_, T>,
+) {
+    let num_ch = planar.num_channels().min(interleaved.num_channels());
+    let num_frames = planar.num_frames().min(interleaved.num_frames());
+    let interleaved_ch = interleaved.num_channels();
+    let data = &interleaved.data()[..num_frames * interleaved_ch];
+
+    match (num_ch, interleaved_ch) {
+        (1, 1) => planar.channel_mut(0)[..num_frames].copy_from_slice(data),
+        (2, 2) => {
+            let mut channels = planar.channels_iter_mut();
+            if let (Some(left), Some(right)) = (channels.next(), channels.next()) {
+                match (as_f32_mut(left), as_f32_mut(right), as_f32(data)) {
+                    (Some(left), Some(right), Some(data)) => {
+                        buffer_ops::deinterleave_stereo(left, right, data)
+                    }
+                    _ => {
+                        for ((frame, l), r) in data.chunks_exact(2).zip(left).zip(right) {
+                            *l = frame[0];
+                            *r = frame[1];
+                        }
+                    }
+                }
+            }
+        }
+        _ => {
+            for (ch, channel) in planar.channels_iter_mut().take(num_ch).enumerate() {
+                channel
+                    .iter_mut()
+                    .zip(data.iter().skip(ch).step_by(interleaved_ch))
+                    .for_each(|(o, i)| *o = *i);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_with_stride() {
+        let data = [1, 2, 3, 0, 4, 5, 6, 0];
+        let planar = PlanarAudio::from_slice_with_stride(&data, 2, 3, 4);
+        assert_eq!(planar.channel(1), [4, 5, 6]);
+        assert_eq!(
+            planar.channels_iter().collect::<Vec<_>>(),
+            [[1, 2, 3], [4, 5, 6]]
+        );
+
+        let planar = PlanarAudio::from_slice(&data[..6], 3);
+        assert_eq!(planar.num_frames(), 2);
+        assert_eq!(planar.channel(2), [4, 5]);
+    }
+
+    #[test]
+    fn separate_channels() {
+        let (left, right) = ([1, 2, 3], [4, 5, 6, 7]);
+        let channels = [&left[..], &right[..]];
+        let planar = PlanarAudio::from_channels(&channels);
+        assert_eq!(planar.num_frames(), 3);
+        assert_eq!(planar.stride(), None);
+        let mut interleaved = [0; 6];
+        interleave(
+            &planar,
+            &mut InterleavedAudioMut::from_slice(&mut interleaved, 2),
+        );
+        assert_eq!(interleaved, [1, 4, 2, 5, 3, 6]);
+
+        let (mut left, mut right) = ([0; 3], [0; 3]);
+        let mut channels = [&mut left[..], &mut right[..]];
+        let mut planar = PlanarAudioMut::from_channels_mut(&mut channels);
+        deinterleave(&InterleavedAudio::from_slice(&interleaved, 2), &mut planar);
+        planar.channel_mut(0)[0] = 9;
+        assert_eq!(left, [9, 2, 3]);
+        assert_eq!(right, [4, 5, 6]);
+    }
+
+    #[test]
+    fn interleave_roundtrip() {
+        for num_ch in 1..=4 {
+            let interleaved = (0..num_ch * 5).collect::<Vec<_>>();
+            let mut planar = vec![0; num_ch * 5];
+            deinterleave(
+                &InterleavedAudio::from_slice(&interleaved, num_ch),
+                &mut PlanarAudioMut::from_slice(&mut planar, num_ch),
+            );
+            assert_eq!(&planar[..5], (0..5).map(|f| f * num_ch).collect::<Vec<_>>());
+
+            let mut result = vec![0; num_ch * 5];
+            interleave(
+                &PlanarAudio::from_slice(&planar, num_ch),
+                &mut InterleavedAudioMut::from_slice(&mut result, num_ch),
+            );
+            assert_eq!(result, interleaved);
+        }
+    }
+
+    #[test]
+    fn frame_views() {
+        let data = [1, 2, 3, 0, 4, 5, 6, 0];
+        let planar = PlanarAudio::from_slice_with_stride(&data, 2, 3, 4);
+        let sliced = planar.slice_frames(1..10);
+        assert_eq!(sliced.num_frames(), 2);
+        assert_eq!(sliced.channel(1), [5, 6]);
+        let (first, second) = planar.split_at_frame(1);
+        assert_eq!(
+            (first.channel(0), second.channel(0)),
+            (&[1][..], &[2, 3][..])
+        );
+        assert_eq!(
+            planar
+                .chunks_frames(2)
+                .map(|c| c.channel(1).to_vec())
+                .collect::<Vec<_>>(),
+            [vec![4, 5], vec![6]]
+        );
+        assert_eq!(planar.single_channel(1).slice_frames(2..).channel(0), [6]);
+        assert!(planar.channel(2).is_empty());
+        assert_eq!(planar.single_channel(2).num_channels(), 0);
+
+        let (left, right) = ([1, 2, 3], [4, 5, 6]);
+        let channels = [&left[..], &right[..]];
+        let planar = PlanarAudio::from_channels(&channels);
+        let single = planar.slice_frames(1..).single_channel(1);
+        assert_eq!((single.num_channels(), single.channel(0)), (1, &[5, 6][..]));
+    }
+
+    #[test]
+    fn frame_views_mut() {
+        let mut data = [0; 8];
+        let mut planar = PlanarAudioMut::from_slice_with_stride(&mut data, 2, 3, 4);
+        let (mut first, mut second) = planar.split_at_frame_mut(1);
+        first.fill(1);
+        second.channel_mut(1).fill(2);
+        for (i, mut chunk) in planar.chunks_frames_mut(2).enumerate() {
+            chunk.channel_mut(0)[0] += i as i32 * 10;
+        }
+        planar.slice_frames_mut(2..).single_channel_mut(0).fill(7);
+        assert_eq!(planar.slice_frames(1..).channel(1), [2, 2]);
+        assert_eq!(data, [1, 0, 7, 0, 1, 2, 2, 0]);
+
+        let (mut left, mut right) = ([0; 3], [0; 3]);
+        let mut channels = [&mut left[..], &mut right[..]];
+        let mut planar = PlanarAudioMut::from_channels_mut(&mut channels);
+        for (i, mut chunk) in planar.chunks_frames_mut(2).enumerate() {
+            chunk.fill(i as i32 + 1);
+        }
+        planar.split_at_frame_mut(1).1.single_channel_mut(1).fill(9);
+        assert_eq!(planar.slice_frames(..2).channel(0), [1, 1]);
+        assert_eq!((left, right), ([1, 1, 2], [1, 9, 9]));
+    }
+}
@@ -0,0 +1,175 @@
+//! Sample rate conversion of whole signals with a windowed sinc filter, e.g. to play a file
+//! with another sample rate than the device. This allocates and is meant to run before the
+//! audio is played, not in the audio thread.
+
+use std::f64::consts::PI;
+
+/// zero crossings of the sinc on each side of the center
+const ZERO_CROSSINGS: usize = 32;
+/// table entries per zero crossing, values in between are interpolated
+const TABLE_RESOLUTION: usize = 512;
+/// shape of the kaiser window, about 100 dB stopband attenuation
+const KAISER_BETA: f64 = 10.0;
+/// the passband ends a little below the lower nyquist frequency, so the filter can roll off
+const PASSBAND: f64 = 0.95;
+
+/// A windowed sinc filter for one ratio of sample rates.
+/// The filter is calculated in `new`, so it can be used for all channels of a signal.
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    // the cutoff relative to the input nyquist frequency
+    cutoff: f64,
+    // one side of the symmetric kernel, over the zero crossings of the sinc
+    table: Vec<f64>,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        let input_rate = input_rate.max(1);
+        let output_rate = output_rate.max(1);
+        let cutoff = (output_rate as f64 / input_rate as f64).min(1.0) * PASSBAND;
+        let table_len = ZERO_CROSSINGS * TABLE_RESOLUTION + 2;
+        let table = (0..table_len)
+            .map(|i| {
+                let x = i as f64 / TABLE_RESOLUTION as f64;
+                sinc(x) * kaiser(x / ZERO_CROSSINGS as f64)
+            })
+            .collect();
+        Self {
+            input_rate,
+            output_rate,
+            cutoff,
+            table,
+        }
+    }
+
+    /// the number of frames a signal of this length has after the conversion
+    pub fn output_len(&self, input_len: usize) -> usize {
+        let len = input_len as u128 * self.output_rate as u128;
+        let input_rate = self.input_rate as u128;
+        ((len + input_rate - 1) / input_rate) as usize
+    }
+
+    /// converts one channel, samples before and after the input are treated as silent.
+    /// the output should have the length of `output_len`, longer outputs are filled with the
+    /// decay of the filter.
+    pub fn process(&self, output: &mut [f32], input: &[f32]) {
+        if self.input_rate == self.output_rate {
+            output.fill(0.0);
+            let len = output.len().min(input.len());
+            output[..len].copy_from_slice(&input[..len]);
+            return;
+        }
+
+        let step = self.input_rate as f64 / self.output_rate as f64;
+        // the kernel is stretched when the cutoff is below the input nyquist frequency
+        let radius = ZERO_CROSSINGS as f64 / self.cutoff;
+        for (n, sample) in output.iter_mut().enumerate() {
+            let center = n as f64 * step;
+            let first = (center - radius).ceil().max(0.0) as usize;
+            let last = ((center + radius).floor() as usize).min(input.len().saturating_sub(1));
+            let mut sum = 0.0;
+            if first <= last && !input.is_empty() {
+                for (k, x) in input[first..=last].iter().enumerate() {
+                    let distance = (center - (first + k) as f64).abs() * self.cutoff;
+                    sum += *x as f64 * self.kernel(distance);
+                }
+            }
+            *sample = (sum * self.cutoff) as f32;
+        }
+    }
+
+    /// the kernel at a distance in zero crossings, interpolated from the table
+    fn kernel(&self, distance: f64) -> f64 {
+        let position = distance * TABLE_RESOLUTION as f64;
+        let index = position as usize;
+        if index + 1 >= self.table.len() {
+            return 0.0;
+        }
+        let fraction = position - index as f64;
+        self.table[index] + (self.table[index + 1] - self.table[index]) * fraction
+    }
+}
+
+/// converts one channel from one sample rate to another
+pub fn resample(input: &[f32], input_rate: u32, output_rate: u32) -> Vec<f32> {
+    let resampler = Resampler::new(input_rate, output_rate);
+    let mut output = vec![0.0; resampler.output_len(input.len())];
+    resampler.process(&mut output, input);
+    output
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// the kaiser window, for positions from -1.0 to 1.0
+fn kaiser(x: f64) -> f64 {
+    if x.abs() > 1.0 {
+        return 0.0;
+    }
+    bessel_i0(KAISER_BETA * (1.0 - x * x).sqrt()) / bessel_i0(KAISER_BETA)
+}
+
+/// the modified bessel function of the first kind and order zero, as a power series
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..50 {
+        term *= half / k as f64;
+        sum += term * term;
+        if term * term < sum * 1e-16 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| (2.0 * PI * frequency * n as f64 / sample_rate as f64).sin() as f32)
+            .collect()
+    }
+
+    /// the largest error in the middle of the signal, away from the edges
+    fn max_error(output: &[f32], expected: &[f32]) -> f32 {
+        let middle = output.len() / 4..output.len() * 3 / 4;
+        output[middle.clone()]
+            .iter()
+            .zip(&expected[middle])
+            .map(|(o, e)| (o - e).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn convert_sine() {
+        let input = sine(1000.0, 44100, 4410);
+        let output = resample(&input, 44100, 48000);
+        assert_eq!(output.len(), 4800);
+        assert!(max_error(&output, &sine(1000.0, 48000, 4800)) < 1e-3);
+
+        let output = resample(&input, 44100, 22050);
+        assert_eq!(output.len(), 2205);
+        assert!(max_error(&output, &sine(1000.0, 22050, 2205)) < 1e-3);
+
+        assert_eq!(resample(&input, 44100, 44100), input);
+    }
+
+    #[test]
+    fn remove_aliasing() {
+        // 10 kHz can't be represented at 16 kHz and is removed instead of folded back
+        let input = sine(10000.0, 48000, 4800);
+        let output = resample(&input, 48000, 16000);
+        assert!(max_error(&output, &vec![0.0; output.len()]) < 1e-3);
+    }
+}
@@ -0,0 +1,371 @@
+//! Conversion between float samples and integer formats, like the ones of audio files and
+//! devices without float support.
+//!
+//! Floats in the range -1.0..1.0 are scaled by `2^(bits - 1)`, rounded and clipped at full
+//! scale. The slice functions are simple loops the compiler vectorises. When reducing the bit
+//! depth of audio that is listened to, use a `Quantizer` with dither, so the rounding error
+//! becomes a constant noise floor instead of distortion.
+
+/// A float sample type, `f32` or `f64`.
+pub trait FloatSample: Copy + Default + Send + 'static {
+    fn to_f64(self) -> f64;
+    fn from_f64(value: f64) -> Self;
+}
+
+impl FloatSample for f32 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl FloatSample for f64 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// An integer sample type. Unsigned types are offset by half their range, like 8 bit WAV files.
+pub trait IntSample: Copy + Default + Send + 'static {
+    /// the integer value of a float sample of 1.0, which itself is clipped to the maximum
+    const SCALE: f64;
+    const MIN: f64;
+    const MAX: f64;
+
+    /// takes a signed integer value, that is already rounded and clipped
+    fn from_scaled(value: f64) -> Self;
+    /// the signed integer value
+    fn to_scaled(self) -> f64;
+
+    /// rounds to the nearest value without dither
+    #[inline]
+    fn from_float<F: FloatSample>(value: F) -> Self {
+        Self::from_scaled(
+            (value.to_f64() * Self::SCALE)
+                .round()
+                .clamp(Self::MIN, Self::MAX),
+        )
+    }
+
+    #[inline]
+    fn to_float<F: FloatSample>(self) -> F {
+        F::from_f64(self.to_scaled() / Self::SCALE)
+    }
+}
+
+impl IntSample for i16 {
+    const SCALE: f64 = 32768.0;
+    const MIN: f64 = i16::MIN as f64;
+    const MAX: f64 = i16::MAX as f64;
+
+    #[inline]
+    fn from_scaled(value: f64) -> Self {
+        value as i16
+    }
+
+    #[inline]
+    fn to_scaled(self) -> f64 {
+        self as f64
+    }
+}
+
+impl IntSample for i32 {
+    const SCALE: f64 = 2147483648.0;
+    const MIN: f64 = i32::MIN as f64;
+    const MAX: f64 = i32::MAX as f64;
+
+    #[inline]
+    fn from_scaled(value: f64) -> Self {
+        value as i32
+    }
+
+    #[inline]
+    fn to_scaled(self) -> f64 {
+        self as f64
+    }
+}
+
+impl IntSample for u8 {
+    const SCALE: f64 = 128.0;
+    const MIN: f64 = -128.0;
+    const MAX: f64 = 127.0;
+
+    #[inline]
+    fn from_scaled(value: f64) -> Self {
+        (value + 128.0) as u8
+    }
+
+    #[inline]
+    fn to_scaled(self) -> f64 {
+        self as f64 - 128.0
+    }
+}
+
+/// A 24 bit sample packed in three little endian bytes, like in WAV files.
+/// Slices of it can be written to and read from byte buffers without padding.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct I24(pub [u8; 3]);
+
+impl I24 {
+    pub const MIN: i32 = -(1 << 23);
+    pub const MAX: i32 = (1 << 23) - 1;
+
+    /// the value is clipped to the 24 bit range
+    pub fn new(value: i32) -> Self {
+        let [a, b, c, _] = value.clamp(Self::MIN, Self::MAX).to_le_bytes();
+        Self([a, b, c])
+    }
+
+    pub fn get(self) -> i32 {
+        let [a, b, c] = self.0;
+        // shifting back from the upper bytes extends the sign
+        i32::from_le_bytes([0, a, b, c]) >> 8
+    }
+}
+
+impl IntSample for I24 {
+    const SCALE: f64 = 8388608.0;
+    const MIN: f64 = I24::MIN as f64;
+    const MAX: f64 = I24::MAX as f64;
+
+    #[inline]
+    fn from_scaled(value: f64) -> Self {
+        I24::new(value as i32)
+    }
+
+    #[inline]
+    fn to_scaled(self) -> f64 {
+        self.get() as f64
+    }
+}
+
+/// converts as many samples as both slices have, rounding to the nearest value without dither
+pub fn float_to_int<F: FloatSample, I: IntSample>(dest: &mut [I], source: &[F]) {
+    for (d, s) in dest.iter_mut().zip(source) {
+        *d = I::from_float(*s);
+    }
+}
+
+/// converts as many samples as both slices have
+pub fn int_to_float<I: IntSample, F: FloatSample>(dest: &mut [F], source: &[I]) {
+    for (d, s) in dest.iter_mut().zip(source) {
+        *d = s.to_float();
+    }
+}
+
+/// converts as many samples as both slices have, e.g. to process `f64` audio from `f32` buffers
+pub fn float_to_float<A: FloatSample, B: FloatSample>(dest: &mut [B], source: &[A]) {
+    for (d, s) in dest.iter_mut().zip(source) {
+        *d = B::from_f64(s.to_f64());
+    }
+}
+
+/// What is added before the samples are rounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dither {
+    /// plain rounding, the error is correlated with the signal
+    #[default]
+    None,
+    /// triangular noise of +-1 least significant bit, which makes the error independent of the
+    /// signal
+    Tpdf,
+    /// TPDF dither and a second order feedback of the error, that moves the noise from the low
+    /// frequencies, where the ear is most sensitive, up to half the sample rate
+    NoiseShaped,
+}
+
+/// Converts interleaved float audio to integers with dither and noise shaping.
+/// The state of the noise shaping is kept per channel, so the buffers of a stream can be
+/// converted one after another. All memory is allocated in `new`.
+pub struct Quantizer {
+    dither: Dither,
+    num_channels: usize,
+    // the last two errors of every channel in least significant bits
+    errors: Vec<[f64; 2]>,
+    random: Random,
+}
+
+impl Quantizer {
+    pub fn new(dither: Dither, num_channels: usize) -> Self {
+        Self {
+            dither,
+            num_channels: num_channels.max(1),
+            errors: vec![[0.0; 2]; num_channels.max(1)],
+            random: Random::new(0x2545_f491),
+        }
+    }
+
+    pub fn dither(&self) -> Dither {
+        self.dither
+    }
+
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.dither = dither;
+        self.reset();
+    }
+
+    /// starts the noise generator from a seed, so the output can be reproduced
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = Random::new(seed);
+    }
+
+    /// clears the noise shaping state, e.g. after seeking
+    pub fn reset(&mut self) {
+        self.errors.fill([0.0; 2]);
+    }
+
+    /// converts interleaved samples, as many as both slices have
+    pub fn process<F: FloatSample, I: IntSample>(&mut self, dest: &mut [I], source: &[F]) {
+        match self.dither {
+            Dither::None => float_to_int(dest, source),
+            Dither::Tpdf => {
+                for (d, s) in dest.iter_mut().zip(source) {
+                    let value = s.to_f64() * I::SCALE + self.random.triangular();
+                    *d = I::from_scaled(value.round().clamp(I::MIN, I::MAX));
+                }
+            }
+            Dither::NoiseShaped => {
+                let frames = dest
+                    .chunks_mut(self.num_channels)
+                    .zip(source.chunks(self.num_channels));
+                for (dest_frame, source_frame) in frames {
+                    let channels = dest_frame.iter_mut().zip(source_frame);
+                    for ((d, s), error) in channels.zip(self.errors.iter_mut()) {
+                        // the error is filtered with (1 - z^-1)^2, which is zero at 0 Hz
+                        let wanted = s.to_f64() * I::SCALE - 2.0 * error[0] + error[1];
+                        let quantized = (wanted + self.random.triangular())
+                            .round()
+                            .clamp(I::MIN, I::MAX);
+                        // limited, so clipping doesn't make the feedback run away
+                        *error = [(quantized - wanted).clamp(-2.0, 2.0), error[0]];
+                        *d = I::from_scaled(quantized);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// xorshift64*, fast and good enough for dither noise
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        Self {
+            // the state must never be zero
+            state: seed | 1,
+        }
+    }
+
+    /// uniform in 0.0..1.0
+    fn uniform(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (value >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// triangular in -1.0..1.0, the sum of two uniform values
+    fn triangular(&mut self) -> f64 {
+        self.uniform() - self.uniform()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_formats() {
+        let floats = [-1.0f32, -0.5, 0.0, 0.25, 1.0];
+
+        let mut ints = [0i16; 5];
+        float_to_int(&mut ints, &floats);
+        assert_eq!(ints, [i16::MIN, -16384, 0, 8192, i16::MAX]);
+
+        let mut bytes = [0u8; 5];
+        float_to_int(&mut bytes, &floats);
+        assert_eq!(bytes, [0, 64, 128, 160, 255]);
+
+        let mut packed = [I24::default(); 5];
+        float_to_int(&mut packed, &floats);
+        assert_eq!(packed[0].get(), I24::MIN);
+        assert_eq!(packed[1].get(), -4194304);
+        assert_eq!(packed[4].get(), I24::MAX);
+        assert_eq!(I24::new(-2).0, [0xfe, 0xff, 0xff]);
+
+        let mut ints = [0i32; 5];
+        float_to_int(&mut ints, &floats);
+        assert_eq!(ints[4], i32::MAX);
+
+        // all formats return to the same floats, except the clipped full scale
+        let mut result = [0.0f64; 5];
+        int_to_float(&mut result, &ints);
+        assert_eq!(result[..4], [-1.0, -0.5, 0.0, 0.25]);
+        int_to_float(&mut result, &packed);
+        assert_eq!(result[..4], [-1.0, -0.5, 0.0, 0.25]);
+        int_to_float(&mut result, &bytes);
+        assert_eq!(result[..4], [-1.0, -0.5, 0.0, 0.25]);
+    }
+
+    /// a quiet sine, two thirds of a least significant bit of 16 bit
+    fn quiet_sine(len: usize) -> Vec<f64> {
+        (0..len)
+            .map(|n| (n as f64 * 0.002).sin() * 0.66 / 32768.0)
+            .collect()
+    }
+
+    /// the error of 16 bit samples, averaged over 256 samples to keep the low frequencies
+    fn low_frequency_error(output: &[i16], input: &[f64]) -> f64 {
+        let errors = output
+            .iter()
+            .zip(input)
+            .map(|(o, i)| *o as f64 - i * 32768.0)
+            .collect::<Vec<_>>();
+        let averages = errors.chunks(256).map(|c| c.iter().sum::<f64>() / 256.0);
+        averages.map(|a| a * a).sum::<f64>().sqrt()
+    }
+
+    #[test]
+    fn dither() {
+        let input = quiet_sine(32768);
+        let mut output = vec![0i16; input.len()];
+
+        // without dither, a signal below one bit is lost
+        Quantizer::new(Dither::None, 1).process(&mut output, &input);
+        assert!(output
+            .iter()
+            .all(|sample| *sample == 0 || sample.abs() == 1));
+        let plain = low_frequency_error(&output, &input);
+
+        let mut quantizer = Quantizer::new(Dither::Tpdf, 1);
+        quantizer.process(&mut output, &input);
+        assert!(output.iter().all(|sample| sample.abs() <= 2));
+        let tpdf = low_frequency_error(&output, &input);
+
+        let mut quantizer = Quantizer::new(Dither::NoiseShaped, 2);
+        let stereo = input.iter().flat_map(|s| [*s, *s]).collect::<Vec<_>>();
+        let mut stereo_output = vec![0i16; stereo.len()];
+        quantizer.process(&mut stereo_output, &stereo);
+        let left = stereo_output.iter().step_by(2).copied().collect::<Vec<_>>();
+        let shaped = low_frequency_error(&left, &input);
+
+        assert!(tpdf < plain, "{tpdf} >= {plain}");
+        assert!(shaped < tpdf / 4.0, "{shaped} >= {tpdf} / 4");
+    }
+}
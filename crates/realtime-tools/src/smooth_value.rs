@@ -46,6 +46,15 @@ impl SmoothValue {
         self.counter = self.num_steps + 1;
     }
 
+    pub fn target_value(&self) -> f32 {
+        self.target
+    }
+
+    /// returns true until the target value is reached
+    pub fn is_smoothing(&self) -> bool {
+        self.counter <= self.num_steps && self.change != 0.0
+    }
+
     pub fn next_value(&mut self) -> f32 {
         if self.counter > self.num_steps {
             self.target
@@ -26,7 +26,7 @@ fn main() {
 
 struct NeoAudioEguiExample {
     neo_audio: NeoAudio<PortAudioBackend>,
-    sender: Option<Sender<MyMessage>>,
+    sender: Option<MessageSender<MyMessage>>,
     audio_running: bool,
     config: DeviceConfig,
     gain: f32,
@@ -41,7 +41,9 @@ impl NeoAudioEguiExample {
         // Restore app state using cc.storage (requires the "persistence" feature).
         // Use the cc.gl (a glow::Context) to create graphics shaders and buffers that you can use
         // for e.g. egui::PaintCallback.
-        let neo_audio = NeoAudio::<PortAudioBackend>::new().unwrap();
+        let mut neo_audio = NeoAudio::<PortAudioBackend>::new().unwrap();
+        // the gain slider can send a lot of messages, only the latest one is important
+        neo_audio.set_overflow_policy(OverflowPolicy::Coalesce);
         let backend = neo_audio.backend();
         let (ui_sender, ui_receiver) = bounded(1024);
         let mut input_level = SmoothValue::new(-60.0, Linear::ease_in_out);
@@ -246,6 +248,12 @@ impl AudioProcessor for MyProcessor {
         }
     }
 
+    fn coalesce_key(message: &MyMessage) -> Option<u64> {
+        match message {
+            MyMessage::Gain(_) => Some(0),
+        }
+    }
+
     fn process(
         &mut self,
         mut output: InterleavedAudioMut<'_, f32>,
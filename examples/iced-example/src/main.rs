@@ -40,7 +40,7 @@ enum AppMessage {
 
 struct NeoAudioIcedApp {
     neo_audio: NeoAudio<PortAudioBackend>,
-    sender: Option<Sender<MyMessage>>,
+    sender: Option<MessageSender<MyMessage>>,
     apis: combo_box::State<String>,
     output_devices: combo_box::State<String>,
     output_channels: combo_box::State<u16>,
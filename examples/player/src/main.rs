@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use crossbeam_channel::RecvTimeoutError;
 use neo_audio::{backends::portaudio_backend::PortAudioBackend, prelude::*, processors::player::*};
 
 fn generate_sine_wave(freq: f32, sample_rate: u32, duration: f32) -> Vec<f32> {
@@ -14,24 +17,38 @@ fn main() -> Result<(), NeoAudioError> {
     // construct audio engine with selected backend and message type
     let mut neo_audio = NeoAudio::<PortAudioBackend>::new()?;
 
-    // generate stereo sine
-    let sine_left = generate_sine_wave(440.0, neo_audio.backend().sample_rate(), 1.0);
-    let sine_right = generate_sine_wave(600.0, neo_audio.backend().sample_rate(), 1.0);
-    let mut stereo_sine = Array2::default((2, sine_left.len()));
-    sine_left.iter().enumerate().for_each(|(i, v)| {
-        stereo_sine[[0, i]] = *v;
-    });
-    sine_right.iter().enumerate().for_each(|(i, v)| {
-        stereo_sine[[1, i]] = *v;
-    });
+    // load the file given as argument or generate a stereo sine
+    let mut player = PlayerProcessor::default();
+    match std::env::args().nth(1) {
+        Some(path) => {
+            let decoded = decode_file(path)?;
+            println!(
+                "Loaded {} channels with {} Hz, {:.1} seconds",
+                decoded.num_channels(),
+                decoded.sample_rate,
+                decoded.duration()
+            );
+            // converted to the sample rate of the device, when the audio is started
+            player.set_audio_with_sample_rate(decoded.audio, decoded.sample_rate);
+        }
+        None => {
+            let sine_left = generate_sine_wave(440.0, neo_audio.backend().sample_rate(), 1.0);
+            let sine_right = generate_sine_wave(600.0, neo_audio.backend().sample_rate(), 1.0);
+            let mut stereo_sine = Array2::default((2, sine_left.len()));
+            sine_left.iter().enumerate().for_each(|(i, v)| {
+                stereo_sine[[0, i]] = *v;
+            });
+            sine_right.iter().enumerate().for_each(|(i, v)| {
+                stereo_sine[[1, i]] = *v;
+            });
+            player.set_audio(stereo_sine);
+        }
+    }
 
-    // generate channel
-    let (sender, receiver) = crossbeam_channel::bounded(1024);
+    // the status can be read while the processor is owned by the audio thread
+    let status = player.status();
 
     // start the audio engine with an implemented audio processor
-    let mut player = PlayerProcessor::default();
-    player.set_audio(stereo_sine);
-    player.set_progress_sender(sender);
     let sender = neo_audio.start_audio(player)?;
 
     // send thread-safe messages to the processor
@@ -39,16 +56,12 @@ fn main() -> Result<(), NeoAudioError> {
     sender.send(PlayerMessage::Gain(0.5)).unwrap();
 
     // let it run until the whole file was played
-    'outer: loop {
-        for _ in 0..receiver.len() {
-            match receiver.try_recv() {
-                Ok(progress) => {
-                    println!("Progress {}%", (progress * 100.0) as usize);
-                    if progress == 1.0 {
-                        break 'outer;
-                    }
-                }
-                _ => break,
+    loop {
+        match status.events().recv_timeout(Duration::from_millis(500)) {
+            Ok(PlayerEvent::Finished) | Err(RecvTimeoutError::Disconnected) => break,
+            Ok(event) => println!("{event:?}"),
+            Err(RecvTimeoutError::Timeout) => {
+                println!("Progress {}%", (status.progress() * 100.0) as usize);
             }
         }
     }